@@ -0,0 +1,394 @@
+use std::fmt::Display;
+use std::io::{self, Write};
+
+use crate::objects_m68k::MetrowerksObject;
+use crate::symtable_m68k::SymbolTable;
+use crate::types_m68k::{BasicDataType, DataType, OtherDataType, TypeDefinition, TypeTable};
+use crate::util::NameIdFromObject;
+
+impl SymbolTable {
+    /// Writes the type table out as C declarations, each type after the ones it uses.
+    ///
+    /// Structs are forward declared up front so pointers can refer to them before
+    /// their definition, which also covers recursive types. Types without a name
+    /// are called `anon_<id>`, and Pascal types become the closest C equivalent.
+    pub fn emit_c_types(&self, obj: &MetrowerksObject, w: &mut impl Write) -> io::Result<()> {
+        let types = self.type_table();
+        let order = types
+            .topological_order()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        let c = CNames { types, obj };
+
+        writeln!(w, "/* Types from the CodeWarrior symbol table */")?;
+
+        for def in order.iter() {
+            if let OtherDataType::TypeStruct(_) = def.definition() {
+                let name = c.type_name(def);
+                writeln!(w, "typedef struct {} {};", name, name)?;
+            }
+        }
+
+        for def in order.iter() {
+            let name = c.type_name(def);
+            writeln!(w)?;
+
+            match def.definition() {
+                OtherDataType::Undefined => (),
+                OtherDataType::TypePointer(p) => {
+                    let stars = "*".repeat(usize::max(1, p.number() as usize));
+                    writeln!(
+                        w,
+                        "typedef {} {}{};",
+                        c.ref_name(p.data_type()),
+                        stars,
+                        name
+                    )?;
+                }
+                OtherDataType::TypeArray(a) => {
+                    let count = a.size().checked_div(a.esize()).unwrap_or(0);
+                    writeln!(
+                        w,
+                        "typedef {} {}[{}];",
+                        c.ref_name(a.data_type()),
+                        name,
+                        count
+                    )?;
+                }
+                OtherDataType::TypeStruct(s) => {
+                    writeln!(w, "struct {} {{", name)?;
+                    for m in s.iter() {
+                        let member = match m.name(obj) {
+                            "" => format!("field_{}", m.offset()),
+                            x => x.to_owned(),
+                        };
+                        writeln!(w, "    {} {};", c.ref_name(m.data_type()), member)?;
+                    }
+                    writeln!(w, "}};")?;
+                }
+                OtherDataType::TypeEnum(e) => {
+                    writeln!(w, "typedef enum {{")?;
+                    for (idx, m) in e.iter().enumerate() {
+                        let member = match m.name(obj) {
+                            "" => format!("{}_{}", name, idx),
+                            x => x.to_owned(),
+                        };
+                        let sep = if idx + 1 < e.len() { "," } else { "" };
+                        writeln!(w, "    {} = {}{}", member, m.value(), sep)?;
+                    }
+                    writeln!(w, "}} {};", name)?;
+                }
+                OtherDataType::TypePascalArray(pa) => {
+                    let elem = c.ref_name(pa.eid());
+                    writeln!(
+                        w,
+                        "typedef {} {}[{} / sizeof({})];",
+                        elem,
+                        name,
+                        pa.size(),
+                        elem
+                    )?;
+                }
+                OtherDataType::TypePascalRange(pr) => {
+                    writeln!(
+                        w,
+                        "typedef {} {}; /* {}..{} */",
+                        c.ref_name(pr.data_type()),
+                        name,
+                        pr.lower(),
+                        pr.upper()
+                    )?;
+                }
+                OtherDataType::TypePascalSet(ps) => {
+                    writeln!(
+                        w,
+                        "typedef unsigned char {}[{}]; /* set of {} */",
+                        name,
+                        ps.size(),
+                        c.ref_name(ps.base())
+                    )?;
+                }
+                OtherDataType::TypePascalEnum(pe) => {
+                    writeln!(w, "typedef enum {{")?;
+                    for (idx, id) in pe.iter().enumerate() {
+                        let member = match obj.name_by_id(*id) {
+                            Some(x) => x.name().to_owned(),
+                            None => format!("{}_{}", name, idx),
+                        };
+                        let sep = if idx + 1 < pe.len() { "," } else { "" };
+                        writeln!(w, "    {} = {}{}", member, idx, sep)?;
+                    }
+                    writeln!(w, "}} {};", name)?;
+                }
+                OtherDataType::TypePascalString(ps) => {
+                    writeln!(w, "typedef unsigned char {}[{}];", name, ps.size())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct CNames<'a> {
+    types: &'a TypeTable,
+    obj: &'a MetrowerksObject,
+}
+
+/// The C spelling of a basic type, e.g. `unsigned long` or `char *`.
+impl Display for BasicDataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", basic_c_name(self))
+    }
+}
+
+/// The C spelling of a basic type, with table types shown as `type#<id>`; use
+/// `DataType::display_with` to name them.
+impl Display for DataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataType::Undefined(_) => write!(f, "void"),
+            DataType::BasicDataType(b) => write!(f, "{}", b),
+            DataType::Other(id) => write!(f, "type#{}", id),
+        }
+    }
+}
+
+impl DataType {
+    /// Like the `Display` output, but names table types after their struct,
+    /// enum, or Pascal type in `obj` when they have one.
+    pub fn display_with(&self, obj: &MetrowerksObject) -> String {
+        let name = match self {
+            DataType::Other(id) => obj
+                .symbols()
+                .and_then(|x| x.type_table().type_by_id(*id))
+                .map(|def| declared_name(def, obj))
+                .unwrap_or_default(),
+            _ => "",
+        };
+
+        match name {
+            "" => self.to_string(),
+            x => x.to_owned(),
+        }
+    }
+}
+
+/// The name a type was declared with, or `""` for pointers, arrays, and
+/// anonymous types.
+fn declared_name<'a>(def: &'a TypeDefinition, obj: &'a MetrowerksObject) -> &'a str {
+    match def.definition() {
+        OtherDataType::TypeStruct(s) => s.name(obj),
+        OtherDataType::TypeEnum(e) => e.name(obj),
+        OtherDataType::TypePascalArray(pa) => pa.name(obj),
+        OtherDataType::TypePascalRange(pr) => pr.name(obj),
+        OtherDataType::TypePascalSet(ps) => ps.name(obj),
+        OtherDataType::TypePascalEnum(pe) => pe.name(obj),
+        OtherDataType::TypePascalString(ps) => ps.name(obj),
+        _ => "",
+    }
+}
+
+impl CNames<'_> {
+    fn type_name(&self, def: &TypeDefinition) -> String {
+        match declared_name(def, self.obj) {
+            "" => format!("anon_{}", def.type_id()),
+            x => x.to_owned(),
+        }
+    }
+
+    fn ref_name(&self, typ: &DataType) -> String {
+        match typ {
+            DataType::Undefined(_) => "void".to_owned(),
+            DataType::BasicDataType(b) => basic_c_name(b).to_owned(),
+            DataType::Other(id) => match self.types.type_by_id(*id) {
+                Some(def) => self.type_name(def),
+                None => format!("int /* unknown type {} */", id),
+            },
+        }
+    }
+}
+
+fn basic_c_name(typ: &BasicDataType) -> &'static str {
+    match typ {
+        BasicDataType::BasicTypeVoid => "void",
+        BasicDataType::BasicTypePstring => "unsigned char",
+        BasicDataType::BasicTypeUlong => "unsigned long",
+        BasicDataType::BasicTypeLong => "long",
+        BasicDataType::BasicTypeFloat10 => "long double",
+        BasicDataType::BasicTypeBoolean => "unsigned char",
+        BasicDataType::BasicTypeUbyte => "unsigned char",
+        BasicDataType::BasicTypeByte => "signed char",
+        BasicDataType::BasicTypeChar => "char",
+        BasicDataType::BasicTypeWchar => "unsigned short",
+        BasicDataType::BasicTypeUword => "unsigned short",
+        BasicDataType::BasicTypeWord => "short",
+        BasicDataType::BasicTypeFloat4 => "float",
+        BasicDataType::BasicTypeFloat8 => "double",
+        BasicDataType::BasicTypeFloat12 => "long double",
+        BasicDataType::BasicTypeComp => "long long",
+        BasicDataType::BasicTypeCstring => "char *",
+        BasicDataType::BasicTypeAIstring => "char *",
+        BasicDataType::MyBasicTypeVoidPtr => "void *",
+        BasicDataType::MyBasicTypeVoidHdl => "void **",
+        BasicDataType::MyBasicTypeCharPtr => "char *",
+        BasicDataType::MyBasicTypeCharHdl => "char **",
+        BasicDataType::MyBasicTypeUcharPtr => "unsigned char *",
+        BasicDataType::MyBasicTypeUcharHdl => "unsigned char **",
+        BasicDataType::MyBasicTypeFunc => "void *",
+        BasicDataType::MyBasicTypeStringPtr => "unsigned char *",
+        BasicDataType::MyBasicTypePstringPtr => "unsigned char *",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects_m68k::NameEntry;
+    use crate::test_util::load_library;
+
+    fn type_entry(bytes: &mut Vec<u8>, tag: u16, id: u32, fields: &[u32]) {
+        bytes.extend(tag.to_be_bytes());
+        bytes.extend(id.to_be_bytes());
+        for f in fields {
+            bytes.extend(f.to_be_bytes());
+        }
+    }
+
+    #[test]
+    fn test_emit_c_types() {
+        let lut = load_library("test/data/add.lib.metro");
+        let mut obj = lut[0].object().clone();
+        for (id, name) in [
+            (4, "node"),
+            (5, "next"),
+            (6, "value"),
+            (7, "color"),
+            (8, "red"),
+        ] {
+            obj.push_name(NameEntry::new(id, name)).unwrap();
+        }
+
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend(b"SYMH");
+        bytes.extend(32u32.to_be_bytes()); // type offset
+        bytes.extend(4u32.to_be_bytes()); // type count
+        bytes.extend([0; 20]);
+
+        // struct node { anon_201 next; long value; }
+        type_entry(&mut bytes, 2, 200, &[4, 8]);
+        bytes.extend(2u16.to_be_bytes());
+        bytes.extend([5u32, 201, 0, 6, 3, 4].iter().flat_map(|x| x.to_be_bytes()));
+        // node *
+        type_entry(&mut bytes, 0, 201, &[]);
+        bytes.extend(1u16.to_be_bytes());
+        bytes.extend(200u32.to_be_bytes());
+        // enum color : unsigned short { red = 0, <unnamed> = 1 }
+        type_entry(&mut bytes, 3, 202, &[7]);
+        bytes.extend(10u16.to_be_bytes());
+        bytes.extend(2u16.to_be_bytes());
+        bytes.extend([8u32, 0, 0, 1].iter().flat_map(|x| x.to_be_bytes()));
+        // short[8]
+        type_entry(&mut bytes, 1, 203, &[16, 2, 11]);
+
+        let symtab = SymbolTable::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(0, symtab.routines().len());
+
+        let mut out: Vec<u8> = vec![];
+        symtab.emit_c_types(&obj, &mut out).unwrap();
+        let header = String::from_utf8(out).unwrap();
+
+        let expected = "/* Types from the CodeWarrior symbol table */
+typedef struct node node;
+
+typedef node *anon_201;
+
+struct node {
+    anon_201 next;
+    long value;
+};
+
+typedef enum {
+    red = 0,
+    color_1 = 1
+} color;
+
+typedef short anon_203[8];
+";
+        assert_eq!(expected, header);
+    }
+
+    #[test]
+    fn test_emit_pointer_after_pointee() {
+        let lut = load_library("test/data/add.lib.metro");
+        let obj = lut[0].object();
+
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend(b"SYMH");
+        bytes.extend(32u32.to_be_bytes()); // type offset
+        bytes.extend(2u32.to_be_bytes()); // type count
+        bytes.extend([0; 20]);
+
+        // anon_201 *
+        type_entry(&mut bytes, 0, 200, &[]);
+        bytes.extend(1u16.to_be_bytes());
+        bytes.extend(201u32.to_be_bytes());
+        // short[8]
+        type_entry(&mut bytes, 1, 201, &[16, 2, 11]);
+
+        let symtab = SymbolTable::try_from(bytes.as_slice()).unwrap();
+        let mut out: Vec<u8> = vec![];
+        symtab.emit_c_types(obj, &mut out).unwrap();
+        let header = String::from_utf8(out).unwrap();
+
+        let expected = "/* Types from the CodeWarrior symbol table */
+
+typedef short anon_201[8];
+
+typedef anon_201 *anon_200;
+";
+        assert_eq!(expected, header);
+    }
+
+    #[test]
+    fn test_data_type_display() {
+        for (typ, name) in [
+            (BasicDataType::BasicTypeUlong, "unsigned long"),
+            (BasicDataType::BasicTypeWord, "short"),
+            (BasicDataType::BasicTypeFloat8, "double"),
+            (BasicDataType::MyBasicTypeCharPtr, "char *"),
+            (BasicDataType::MyBasicTypeVoidHdl, "void **"),
+        ] {
+            assert_eq!(name, typ.to_string());
+            assert_eq!(name, DataType::BasicDataType(typ).to_string());
+        }
+        assert_eq!("void", DataType::Undefined(()).to_string());
+        assert_eq!("type#200", DataType::Other(200).to_string());
+
+        let lut = load_library("test/data/add.lib.metro");
+        let mut obj = lut[0].object().clone();
+        obj.push_name(NameEntry::new(4, "point")).unwrap();
+
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend(b"SYMH");
+        bytes.extend(32u32.to_be_bytes()); // type offset
+        bytes.extend(2u32.to_be_bytes()); // type count
+        bytes.extend([0; 20]);
+        // struct point { }
+        type_entry(&mut bytes, 2, 200, &[4, 0]);
+        bytes.extend(0u16.to_be_bytes());
+        // point *
+        type_entry(&mut bytes, 0, 201, &[]);
+        bytes.extend(1u16.to_be_bytes());
+        bytes.extend(200u32.to_be_bytes());
+        *obj.symbols_mut().unwrap() = SymbolTable::try_from(bytes.as_slice()).unwrap();
+
+        assert_eq!("point", DataType::Other(200).display_with(&obj));
+        // Pointers have no name of their own, nor do ids missing from the table
+        assert_eq!("type#201", DataType::Other(201).display_with(&obj));
+        assert_eq!("type#202", DataType::Other(202).display_with(&obj));
+        assert_eq!(
+            "long",
+            DataType::BasicDataType(BasicDataType::BasicTypeLong).display_with(&obj)
+        );
+    }
+}
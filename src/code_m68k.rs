@@ -1,15 +1,23 @@
-use std::ops::Deref;
+use std::collections::HashMap;
+use std::io::Write;
+use std::ops::{Deref, Range};
 
 use chrono::{DateTime, Local};
 
-use crate::util::{from_mac_datetime, RawLength};
+use crate::error::LibmetroError;
+use crate::mwob_library::LibraryProcessor;
+use crate::objects_m68k::{MetrowerksObject, NameEntry};
+use crate::symtable_m68k::Routine;
+use crate::util::{check_len, MacDateTime, ParseOptions, RawLength, Serializable};
 
 use super::util::{convert_be_u16, convert_be_u32, NameIdFromObject};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ReservedHunk {}
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObjSimpleHunk {}
 
 impl RawLength for ObjSimpleHunk {
@@ -18,7 +26,8 @@ impl RawLength for ObjSimpleHunk {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ObjCodeFlag {
     None,
     GlobalMultiDef,
@@ -26,7 +35,26 @@ pub enum ObjCodeFlag {
     CFMExport,
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+/// One instruction as reported by a [`CodeDecoder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecodedInsn {
+    pub addr: u32,
+    pub len: usize,
+    pub text: String,
+}
+
+/// Turns machine code into instructions.
+///
+/// No decoder ships with the crate; implement this to plug one in for
+/// [`ObjCodeHunk::disassemble_with`].
+pub trait CodeDecoder {
+    /// Decodes `code`, whose first byte sits at address `base`.
+    fn decode(&self, code: &[u8], base: u32) -> Vec<DecodedInsn>;
+}
+
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObjCodeHunk {
     name_id: u32,
     sym_offset: u32,
@@ -45,7 +73,7 @@ impl Deref for ObjCodeHunk {
 
 impl RawLength for ObjCodeHunk {
     fn raw_length(&self) -> usize {
-        12 + self.code.len()
+        16 + self.code.len()
     }
 }
 
@@ -54,6 +82,10 @@ impl ObjCodeHunk {
         self.sym_offset != 0x80000000
     }
 
+    pub fn sym_offset(&self) -> u32 {
+        self.sym_offset
+    }
+
     pub fn sym_decl_offset(&self) -> u32 {
         self.sym_decl_offset
     }
@@ -61,9 +93,28 @@ impl ObjCodeHunk {
     pub fn flag(&self) -> ObjCodeFlag {
         self.special_flag
     }
+
+    /// The routine in `obj`'s symbol table that describes this hunk.
+    ///
+    /// `None` when the hunk has no symbols, `obj` has no symbol table, or no
+    /// routine starts at the hunk's `sym_offset`.
+    pub fn routine<'a>(&self, obj: &'a MetrowerksObject) -> Option<&'a Routine> {
+        if !self.has_symtab() {
+            return None;
+        }
+
+        obj.symbols()?.routine_at_offset(self.sym_offset as usize)
+    }
+
+    /// Runs `d` over this hunk's code, with addresses counted from the start
+    /// of the hunk.
+    pub fn disassemble_with<D: CodeDecoder>(&self, d: &D) -> Vec<DecodedInsn> {
+        d.decode(&self.code, 0)
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObjInitHunk {
     code: Vec<u8>,
 }
@@ -76,9 +127,29 @@ impl Deref for ObjInitHunk {
     }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+impl RawLength for ObjInitHunk {
+    fn raw_length(&self) -> usize {
+        4 + self.code.len()
+    }
+}
+
+impl ObjInitHunk {
+    pub fn new(code: &[u8]) -> ObjInitHunk {
+        ObjInitHunk {
+            code: code.to_vec(),
+        }
+    }
+
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+}
+
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObjDataHunk {
     name_id: u32,
+    size: u32,
     sym_offset: u32,
     sym_decl_offset: u32,
     data: Vec<u8>,
@@ -93,6 +164,44 @@ impl Deref for ObjDataHunk {
 }
 
 impl ObjDataHunk {
+    /// A data hunk carrying its initial contents, for the `*InitializedData` kinds.
+    pub fn new_initialized(
+        name_id: u32,
+        sym_offset: u32,
+        sym_decl_offset: u32,
+        data: &[u8],
+    ) -> ObjDataHunk {
+        ObjDataHunk {
+            name_id,
+            size: data.len() as u32,
+            sym_offset,
+            sym_decl_offset,
+            data: data.to_vec(),
+        }
+    }
+
+    /// A data hunk reserving `size` zeroed bytes, for the `*UninitializedData` kinds.
+    pub fn new_uninitialized(
+        name_id: u32,
+        sym_offset: u32,
+        sym_decl_offset: u32,
+        size: u32,
+    ) -> ObjDataHunk {
+        ObjDataHunk {
+            name_id,
+            size,
+            sym_offset,
+            sym_decl_offset,
+            data: vec![],
+        }
+    }
+
+    /// Size of the data in bytes; for uninitialized data this is the space to
+    /// reserve, as there are no bytes to go with it.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
     pub fn sym_offset(&self) -> u32 {
         self.sym_offset
     }
@@ -102,19 +211,47 @@ impl ObjDataHunk {
     }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObjEntryHunk {
     name_id: u32,
     offset: u32,
 }
 
+impl RawLength for ObjDataHunk {
+    fn raw_length(&self) -> usize {
+        16 + self.data.len()
+    }
+}
+
+/// Writes the hunk body after the tag. The data bytes follow the header only
+/// for initialized data, uninitialized data has nothing to write.
+impl Serializable for ObjDataHunk {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        if !self.data.is_empty() && self.data.len() != self.size as usize {
+            return Err(LibmetroError::Invalid(format!(
+                "Data hunk size {} does not match its {} bytes of data",
+                self.size,
+                self.data.len()
+            )));
+        }
+
+        w.write_all(&self.name_id.to_be_bytes())?;
+        w.write_all(&self.size.to_be_bytes())?;
+        w.write_all(&self.sym_offset.to_be_bytes())?;
+        w.write_all(&self.sym_decl_offset.to_be_bytes())?;
+        Ok(w.write_all(&self.data)?)
+    }
+}
+
 impl ObjEntryHunk {
     pub fn offset(&self) -> u32 {
         self.offset
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObjXRefPair {
     offset: u32,
     value: u32,
@@ -130,12 +267,14 @@ impl ObjXRefPair {
     }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObjXRefHunk {
     name_id: u32,
     pairs: Vec<ObjXRefPair>,
 }
 
+/// Kept for existing callers, prefer `entries`, `len` and `get`.
 impl Deref for ObjXRefHunk {
     type Target = Vec<ObjXRefPair>;
 
@@ -144,7 +283,56 @@ impl Deref for ObjXRefHunk {
     }
 }
 
-#[derive(Debug, Clone)]
+/// What an xref pair patches in, see `ObjXRefHunk::relocations`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XRefTarget<'a> {
+    /// The referenced symbol, with the pair's value.
+    Symbol(&'a NameEntry, u32),
+    /// The hunk's name isn't in the object, so only the pair's raw value is known.
+    Raw(u32),
+}
+
+impl ObjXRefHunk {
+    /// The offset and value pairs, in stream order.
+    pub fn entries(&self) -> &[ObjXRefPair] {
+        &self.pairs
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&ObjXRefPair> {
+        self.pairs.get(index)
+    }
+
+    /// Each pair's offset into the preceding code or data hunk, with the symbol
+    /// referenced there.
+    ///
+    /// Every pair refers to the symbol the hunk names, a pair's value doesn't
+    /// name a symbol of its own.
+    pub fn relocations<'a>(
+        &'a self,
+        obj: &'a MetrowerksObject,
+    ) -> impl Iterator<Item = (u32, XRefTarget<'a>)> + 'a {
+        let symbol = obj.name_by_id(self.name_id);
+
+        self.pairs.iter().map(move |p| {
+            let target = match symbol {
+                Some(name) => XRefTarget::Symbol(name, p.value),
+                None => XRefTarget::Raw(p.value),
+            };
+            (p.offset, target)
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObjExceptInfo {
     info: Vec<u8>,
 }
@@ -157,7 +345,8 @@ impl Deref for ObjExceptInfo {
     }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObjContainerHunk {
     name_id: u32,
     old_def_version: u32,
@@ -177,14 +366,39 @@ impl ObjContainerHunk {
     pub fn current_version(&self) -> u32 {
         self.current_version
     }
+
+    /// `(old_def_version, old_imp_version, current_version)`
+    pub fn versions(&self) -> (u32, u32, u32) {
+        (
+            self.old_def_version,
+            self.old_imp_version,
+            self.current_version,
+        )
+    }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObjImportHunk {
     name_id: u32,
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+/// A PowerPC `HUNK_FORCE_ACTIVE`, which keeps the named symbol from being dead
+/// stripped by the linker.
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ObjForceActiveHunk {
+    name_id: u32,
+}
+
+impl ObjForceActiveHunk {
+    pub fn new(name_id: u32) -> ObjForceActiveHunk {
+        ObjForceActiveHunk { name_id }
+    }
+}
+
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DataPointerHunk {
     name_id: u32,
     data_name: u32,
@@ -194,49 +408,107 @@ impl DataPointerHunk {
     pub fn data_name_id(&self) -> u32 {
         self.data_name
     }
+
+    /// Name of the data the pointer refers to, or "" if it isn't in the object's name table.
+    pub fn data_name<'a>(&self, obj: &'a MetrowerksObject) -> &'a str {
+        resolve_name(obj, self.data_name)
+    }
+
+    /// The pointer's own name and the name of the data it refers to,
+    /// each `None` if it isn't in the object's name table.
+    pub fn resolve<'a>(
+        &self,
+        obj: &'a MetrowerksObject,
+    ) -> (Option<&'a NameEntry>, Option<&'a NameEntry>) {
+        (obj.name_by_id(self.name_id), obj.name_by_id(self.data_name))
+    }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct XPointerHunk {
     name_id: u32,
     xvector_name: u32,
 }
 
 impl XPointerHunk {
-    pub fn xvector_name(&self) -> u32 {
+    pub fn xvector_name_id(&self) -> u32 {
         self.xvector_name
     }
+
+    /// Name of the transition vector the pointer refers to, or "" if it isn't in the object's name table.
+    pub fn xvector_name<'a>(&self, obj: &'a MetrowerksObject) -> &'a str {
+        resolve_name(obj, self.xvector_name)
+    }
+
+    /// The pointer's own name and the name of the transition vector it refers to,
+    /// each `None` if it isn't in the object's name table.
+    pub fn resolve<'a>(
+        &self,
+        obj: &'a MetrowerksObject,
+    ) -> (Option<&'a NameEntry>, Option<&'a NameEntry>) {
+        (
+            obj.name_by_id(self.name_id),
+            obj.name_by_id(self.xvector_name),
+        )
+    }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct XVectorHunk {
     name_id: u32,
     function_name: u32,
 }
 
 impl XVectorHunk {
-    pub fn function_name(&self) -> u32 {
+    pub fn function_name_id(&self) -> u32 {
         self.function_name
     }
+
+    /// Name of the function the transition vector points at, or "" if it isn't in the object's name table.
+    pub fn function_name<'a>(&self, obj: &'a MetrowerksObject) -> &'a str {
+        resolve_name(obj, self.function_name)
+    }
+
+    /// The transition vector's own name and the name of the function it points at,
+    /// each `None` if it isn't in the object's name table.
+    pub fn resolve<'a>(
+        &self,
+        obj: &'a MetrowerksObject,
+    ) -> (Option<&'a NameEntry>, Option<&'a NameEntry>) {
+        (
+            obj.name_by_id(self.name_id),
+            obj.name_by_id(self.function_name),
+        )
+    }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObjSourceHunk {
     name_id: u32,
-    moddate: DateTime<Local>,
+    moddate: MacDateTime,
 }
 impl ObjSourceHunk {
     pub fn moddate(&self) -> DateTime<Local> {
+        self.moddate.to_datetime().into()
+    }
+
+    /// The modification date as stored, see `MacDateTime`.
+    pub fn mac_moddate(&self) -> MacDateTime {
         self.moddate
     }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObjSegHunk {
     name_id: u32,
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObjMethHunk {
     name_id: u32,
     size: u32,
@@ -247,7 +519,8 @@ impl ObjMethHunk {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObjClassPair {
     base_id: u32,
     bias: u32,
@@ -262,7 +535,8 @@ impl ObjClassPair {
     }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObjClassHunk {
     name_id: u32,
     methods: u16,
@@ -281,9 +555,22 @@ impl ObjClassHunk {
     pub fn methods(&self) -> u16 {
         self.methods
     }
+
+    /// Each base class, resolved in `obj`'s name table, with its bias.
+    ///
+    /// Bases whose name isn't in the name table are skipped.
+    pub fn bases<'a>(
+        &'a self,
+        obj: &'a MetrowerksObject,
+    ) -> impl Iterator<Item = (&'a NameEntry, u32)> + 'a {
+        self.pairs
+            .iter()
+            .filter_map(move |p| Some((obj.name_by_id(p.base_id)?, p.bias)))
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HunkType {
     Undefined,
     Start(ObjSimpleHunk),
@@ -314,7 +601,7 @@ pub enum HunkType {
     GlobalOverload(ObjSimpleHunk),
     XRefCode16Bit(ObjXRefHunk),
     XRefCode32Bit(ObjXRefHunk),
-    ForceActive(ReservedHunk), // PPC-only
+    ForceActive(ObjForceActiveHunk), // PPC-only
     GlobalDataPointer(DataPointerHunk),
     GlobalXPointer(XPointerHunk),
     GlobalXVector(XVectorHunk),
@@ -336,9 +623,387 @@ pub enum HunkType {
     WeakImportContainer(ObjContainerHunk),
 }
 
+impl HunkType {
+    /// The on-disk tag for this hunk, as written before its body.
+    ///
+    /// `HunkType::Undefined` has no on-disk representation and reports `0`,
+    /// which is never a valid tag.
+    pub fn tag(&self) -> u16 {
+        let raw = match self {
+            HunkType::Undefined => return 0,
+            HunkType::Start(_) => RawHunkType::HUNK_START,
+            HunkType::End(_) => RawHunkType::HUNK_END,
+            HunkType::LocalCode(_) => RawHunkType::HUNK_LOCAL_CODE,
+            HunkType::GlobalCode(_) => RawHunkType::HUNK_GLOBAL_CODE,
+            HunkType::LocalUninitializedData(_) => RawHunkType::HUNK_LOCAL_UDATA,
+            HunkType::GlobalUninitializedData(_) => RawHunkType::HUNK_GLOBAL_UDATA,
+            HunkType::LocalInitializedData(_) => RawHunkType::HUNK_LOCAL_IDATA,
+            HunkType::GlobalInitializedData(_) => RawHunkType::HUNK_GLOBAL_IDATA,
+            HunkType::LocalFarUninitializedData(_) => RawHunkType::HUNK_LOCAL_FARUDATA,
+            HunkType::GlobalFarUninitializedData(_) => RawHunkType::HUNK_GLOBAL_FARUDATA,
+            HunkType::LocalFarInitializedData(_) => RawHunkType::HUNK_LOCAL_FARIDATA,
+            HunkType::GlobalFarInitializedData(_) => RawHunkType::HUNK_GLOBAL_FARIDATA,
+            HunkType::XRefCodeJT16Bit(_) => RawHunkType::HUNK_XREF_CODEJT16BIT,
+            HunkType::XRefData16Bit(_) => RawHunkType::HUNK_XREF_DATA16BIT,
+            HunkType::XRef32Bit(_) => RawHunkType::HUNK_XREF_32BIT,
+            HunkType::LibraryBreak(_) => RawHunkType::HUNK_LIBRARY_BREAK,
+            HunkType::GlobalEntry(_) => RawHunkType::HUNK_GLOBAL_ENTRY,
+            HunkType::LocalEntry(_) => RawHunkType::HUNK_LOCAL_ENTRY,
+            HunkType::Diff8Bit(_) => RawHunkType::HUNK_DIFF_8BIT,
+            HunkType::Diff16Bit(_) => RawHunkType::HUNK_DIFF_16BIT,
+            HunkType::Diff32Bit(_) => RawHunkType::HUNK_DIFF_32BIT,
+            HunkType::Segment(_) => RawHunkType::HUNK_SEGMENT,
+            HunkType::InitCode(_) => RawHunkType::HUNK_INIT_CODE,
+            HunkType::DeInitCode(_) => RawHunkType::HUNK_DEINIT_CODE,
+            HunkType::GlobalMultiDef(_) => RawHunkType::HUNK_MULTIDEF_GLOBAL,
+            HunkType::GlobalOverload(_) => RawHunkType::HUNK_OVERLOAD_GLOBAL,
+            HunkType::XRefCode16Bit(_) => RawHunkType::HUNK_XREF_CODE16BIT,
+            HunkType::XRefCode32Bit(_) => RawHunkType::HUNK_XREF_CODE32BIT,
+            HunkType::ForceActive(_) => RawHunkType::HUNK_FORCE_ACTIVE,
+            HunkType::GlobalDataPointer(_) => RawHunkType::HUNK_GLOBAL_DATAPOINTER,
+            HunkType::GlobalXPointer(_) => RawHunkType::HUNK_GLOBAL_XPOINTER,
+            HunkType::GlobalXVector(_) => RawHunkType::HUNK_GLOBAL_XVECTOR,
+            HunkType::XRefPCRelative32Bit(_) => RawHunkType::HUNK_XREF_PCREL32BIT,
+            HunkType::Illegal1(_) => RawHunkType::HUNK_ILLEGAL1,
+            HunkType::Illegal2(_) => RawHunkType::HUNK_ILLEGAL2,
+            HunkType::CFMExport(_) => RawHunkType::HUNK_CFM_EXPORT,
+            HunkType::CFMImport(_) => RawHunkType::HUNK_CFM_IMPORT,
+            HunkType::CFMImportContainer(_) => RawHunkType::HUNK_CFM_IMPORT_CONTAINER,
+            HunkType::SrcBreak(_) => RawHunkType::HUNK_SRC_BREAK,
+            HunkType::LocalDataPointer(_) => RawHunkType::HUNK_LOCAL_DATAPOINTER,
+            HunkType::LocalXPointer(_) => RawHunkType::HUNK_LOCAL_XPOINTER,
+            HunkType::LocalXVector(_) => RawHunkType::HUNK_LOCAL_XVECTOR,
+            HunkType::ExceptionInfo(_) => RawHunkType::HUNK_EXCEPTION_INFO,
+            HunkType::CFMInternal(_) => RawHunkType::HUNK_CFM_INTERNAL,
+            HunkType::MethodReference(_) => RawHunkType::HUNK_METHOD_REF,
+            HunkType::MethodClassDefinition(_) => RawHunkType::HUNK_METHOD_CLASS_DEF,
+            HunkType::XRefAmbiguous16Bit(_) => RawHunkType::HUNK_XREF_AMBIGUOUS16BIT,
+            HunkType::WeakImportContainer(_) => RawHunkType::HUNK_WEAK_IMPORT_CONTAINER,
+        };
+
+        raw as u16
+    }
+
+    /// The name of this hunk's variant, e.g. `"GlobalCode"`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            HunkType::Undefined => "Undefined",
+            HunkType::Start(_) => "Start",
+            HunkType::End(_) => "End",
+            HunkType::LocalCode(_) => "LocalCode",
+            HunkType::GlobalCode(_) => "GlobalCode",
+            HunkType::LocalUninitializedData(_) => "LocalUninitializedData",
+            HunkType::GlobalUninitializedData(_) => "GlobalUninitializedData",
+            HunkType::LocalInitializedData(_) => "LocalInitializedData",
+            HunkType::GlobalInitializedData(_) => "GlobalInitializedData",
+            HunkType::LocalFarUninitializedData(_) => "LocalFarUninitializedData",
+            HunkType::GlobalFarUninitializedData(_) => "GlobalFarUninitializedData",
+            HunkType::LocalFarInitializedData(_) => "LocalFarInitializedData",
+            HunkType::GlobalFarInitializedData(_) => "GlobalFarInitializedData",
+            HunkType::XRefCodeJT16Bit(_) => "XRefCodeJT16Bit",
+            HunkType::XRefData16Bit(_) => "XRefData16Bit",
+            HunkType::XRef32Bit(_) => "XRef32Bit",
+            HunkType::LibraryBreak(_) => "LibraryBreak",
+            HunkType::GlobalEntry(_) => "GlobalEntry",
+            HunkType::LocalEntry(_) => "LocalEntry",
+            HunkType::Diff8Bit(_) => "Diff8Bit",
+            HunkType::Diff16Bit(_) => "Diff16Bit",
+            HunkType::Diff32Bit(_) => "Diff32Bit",
+            HunkType::Segment(_) => "Segment",
+            HunkType::InitCode(_) => "InitCode",
+            HunkType::DeInitCode(_) => "DeInitCode",
+            HunkType::GlobalMultiDef(_) => "GlobalMultiDef",
+            HunkType::GlobalOverload(_) => "GlobalOverload",
+            HunkType::XRefCode16Bit(_) => "XRefCode16Bit",
+            HunkType::XRefCode32Bit(_) => "XRefCode32Bit",
+            HunkType::ForceActive(_) => "ForceActive",
+            HunkType::GlobalDataPointer(_) => "GlobalDataPointer",
+            HunkType::GlobalXPointer(_) => "GlobalXPointer",
+            HunkType::GlobalXVector(_) => "GlobalXVector",
+            HunkType::XRefPCRelative32Bit(_) => "XRefPCRelative32Bit",
+            HunkType::Illegal1(_) => "Illegal1",
+            HunkType::Illegal2(_) => "Illegal2",
+            HunkType::CFMExport(_) => "CFMExport",
+            HunkType::CFMImport(_) => "CFMImport",
+            HunkType::CFMImportContainer(_) => "CFMImportContainer",
+            HunkType::SrcBreak(_) => "SrcBreak",
+            HunkType::LocalDataPointer(_) => "LocalDataPointer",
+            HunkType::LocalXPointer(_) => "LocalXPointer",
+            HunkType::LocalXVector(_) => "LocalXVector",
+            HunkType::ExceptionInfo(_) => "ExceptionInfo",
+            HunkType::CFMInternal(_) => "CFMInternal",
+            HunkType::MethodReference(_) => "MethodReference",
+            HunkType::MethodClassDefinition(_) => "MethodClassDefinition",
+            HunkType::XRefAmbiguous16Bit(_) => "XRefAmbiguous16Bit",
+            HunkType::WeakImportContainer(_) => "WeakImportContainer",
+        }
+    }
+
+    /// The name id of a code, data, or entry hunk, which must have an entry in
+    /// the object's name table.
+    pub fn name_id(&self) -> Option<u32> {
+        match self {
+            HunkType::LocalCode(c) | HunkType::GlobalCode(c) => Some(c.name_id()),
+            HunkType::LocalInitializedData(d)
+            | HunkType::GlobalInitializedData(d)
+            | HunkType::LocalFarInitializedData(d)
+            | HunkType::GlobalFarInitializedData(d)
+            | HunkType::LocalUninitializedData(d)
+            | HunkType::GlobalUninitializedData(d)
+            | HunkType::LocalFarUninitializedData(d)
+            | HunkType::GlobalFarUninitializedData(d) => Some(d.name_id()),
+            HunkType::LocalEntry(e) | HunkType::GlobalEntry(e) => Some(e.name_id()),
+            _ => None,
+        }
+    }
+
+    /// Hunks the parser can't read because their layout isn't documented, when
+    /// the processor isn't known. See `is_reserved_for`.
+    pub fn is_reserved(&self) -> bool {
+        self.is_reserved_for(LibraryProcessor::Unknown)
+    }
+
+    /// Hunks with no documented layout in an object for `proc`. `ForceActive`
+    /// only has one for PowerPC, so it counts as reserved in an m68k object.
+    pub fn is_reserved_for(&self, proc: LibraryProcessor) -> bool {
+        match self {
+            HunkType::ForceActive(_) => proc == LibraryProcessor::M68k,
+            HunkType::LibraryBreak(_)
+            | HunkType::Diff8Bit(_)
+            | HunkType::Diff16Bit(_)
+            | HunkType::Diff32Bit(_)
+            | HunkType::DeInitCode(_)
+            | HunkType::Illegal1(_)
+            | HunkType::Illegal2(_)
+            | HunkType::CFMInternal(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether this hunk may appear in an object for `proc`.
+    ///
+    /// Only `Segment` (m68k) and `ForceActive` (PowerPC) are tied to a
+    /// processor; everything is accepted for an `Unknown` processor.
+    pub fn is_valid_for(&self, proc: LibraryProcessor) -> bool {
+        match self {
+            HunkType::Segment(_) => proc != LibraryProcessor::PowerPC,
+            HunkType::ForceActive(_) => proc != LibraryProcessor::M68k,
+            _ => true,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Hunk {
     hunk: HunkType,
+    span: Range<usize>,
+}
+
+// Equality is on the hunk only, where it was read from doesn't matter
+impl PartialEq for Hunk {
+    fn eq(&self, other: &Self) -> bool {
+        self.hunk == other.hunk
+    }
+}
+
+impl Eq for Hunk {}
+
+impl From<HunkType> for Hunk {
+    fn from(value: HunkType) -> Self {
+        Hunk {
+            hunk: value,
+            span: 0..0,
+        }
+    }
+}
+
+/// The tag plus the body, as laid out in the object. Reserved hunks have no
+/// known layout and count as just their tag.
+impl RawLength for Hunk {
+    fn raw_length(&self) -> usize {
+        let body = match &self.hunk {
+            HunkType::LocalCode(c) | HunkType::GlobalCode(c) => c.raw_length(),
+            HunkType::LocalUninitializedData(d)
+            | HunkType::GlobalUninitializedData(d)
+            | HunkType::LocalInitializedData(d)
+            | HunkType::GlobalInitializedData(d)
+            | HunkType::LocalFarUninitializedData(d)
+            | HunkType::GlobalFarUninitializedData(d)
+            | HunkType::LocalFarInitializedData(d)
+            | HunkType::GlobalFarInitializedData(d) => d.raw_length(),
+            HunkType::XRefCodeJT16Bit(x)
+            | HunkType::XRefData16Bit(x)
+            | HunkType::XRef32Bit(x)
+            | HunkType::XRefCode16Bit(x)
+            | HunkType::XRefCode32Bit(x)
+            | HunkType::XRefPCRelative32Bit(x)
+            | HunkType::XRefAmbiguous16Bit(x) => 6 + 8 * x.len(),
+            HunkType::MethodClassDefinition(c) => 8 + 8 * c.len(),
+            HunkType::InitCode(c) => c.raw_length(),
+            HunkType::ExceptionInfo(e) => 4 + e.len(),
+            HunkType::CFMImportContainer(_) | HunkType::WeakImportContainer(_) => 16,
+            HunkType::GlobalEntry(_)
+            | HunkType::LocalEntry(_)
+            | HunkType::GlobalDataPointer(_)
+            | HunkType::LocalDataPointer(_)
+            | HunkType::GlobalXPointer(_)
+            | HunkType::LocalXPointer(_)
+            | HunkType::GlobalXVector(_)
+            | HunkType::LocalXVector(_)
+            | HunkType::SrcBreak(_)
+            | HunkType::MethodReference(_) => 8,
+            HunkType::Segment(_) | HunkType::CFMImport(_) | HunkType::ForceActive(_) => 4,
+            _ => 0,
+        };
+
+        2 + body
+    }
+}
+
+impl Hunk {
+    pub fn hunk_type(&self) -> &HunkType {
+        &self.hunk
+    }
+
+    pub fn tag(&self) -> u16 {
+        self.hunk.tag()
+    }
+
+    /// The bytes this hunk was parsed from, tag included, as offsets into the
+    /// hunk stream given to `CodeHunks::try_from`. Empty for hunks that weren't
+    /// parsed, and not updated if the hunk is changed.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+}
+
+/// Writes the tag followed by the body, in the layout `CodeHunks` parses.
+/// Reserved hunks have no known body and can't be written.
+impl Serializable for Hunk {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        if let HunkType::Undefined = self.hunk {
+            return Err(LibmetroError::Invalid(
+                "Undefined hunk cannot be serialized".to_owned(),
+            ));
+        }
+        if self.hunk.is_reserved() {
+            return Err(LibmetroError::ReservedHunk(self.tag()));
+        }
+
+        let mut out: Vec<u8> = vec![];
+        out.extend(self.tag().to_be_bytes());
+
+        match &self.hunk {
+            HunkType::LocalCode(c) | HunkType::GlobalCode(c) => {
+                out.extend(c.name_id.to_be_bytes());
+                out.extend((c.code.len() as u32).to_be_bytes());
+                out.extend(c.sym_offset.to_be_bytes());
+                out.extend(c.sym_decl_offset.to_be_bytes());
+                out.extend(&c.code);
+            }
+            HunkType::LocalInitializedData(d)
+            | HunkType::GlobalInitializedData(d)
+            | HunkType::LocalFarInitializedData(d)
+            | HunkType::GlobalFarInitializedData(d) => {
+                if d.data.len() != d.size as usize {
+                    return Err(LibmetroError::Invalid(format!(
+                        "Initialized data hunk has size {} but {} bytes of data",
+                        d.size,
+                        d.data.len()
+                    )));
+                }
+                d.serialize_out(&mut out)?;
+            }
+            HunkType::LocalUninitializedData(d)
+            | HunkType::GlobalUninitializedData(d)
+            | HunkType::LocalFarUninitializedData(d)
+            | HunkType::GlobalFarUninitializedData(d) => {
+                if !d.data.is_empty() {
+                    return Err(LibmetroError::Invalid(format!(
+                        "Uninitialized data hunk carries {} bytes of data",
+                        d.data.len()
+                    )));
+                }
+                d.serialize_out(&mut out)?;
+            }
+            HunkType::XRefCodeJT16Bit(x)
+            | HunkType::XRefData16Bit(x)
+            | HunkType::XRef32Bit(x)
+            | HunkType::XRefCode16Bit(x)
+            | HunkType::XRefCode32Bit(x)
+            | HunkType::XRefPCRelative32Bit(x)
+            | HunkType::XRefAmbiguous16Bit(x) => {
+                let count = u16::try_from(x.len()).map_err(|_| {
+                    LibmetroError::Invalid(format!("XRef hunk has too many pairs: {}", x.len()))
+                })?;
+
+                out.extend(x.name_id.to_be_bytes());
+                out.extend(count.to_be_bytes());
+                for p in x.iter() {
+                    out.extend(p.offset.to_be_bytes());
+                    out.extend(p.value.to_be_bytes());
+                }
+            }
+            HunkType::MethodClassDefinition(c) => {
+                let count = u16::try_from(c.len()).map_err(|_| {
+                    LibmetroError::Invalid(format!("Class hunk has too many pairs: {}", c.len()))
+                })?;
+
+                out.extend(c.name_id.to_be_bytes());
+                out.extend(c.methods.to_be_bytes());
+                out.extend(count.to_be_bytes());
+                for p in c.iter() {
+                    out.extend(p.base_id.to_be_bytes());
+                    out.extend(p.bias.to_be_bytes());
+                }
+            }
+            HunkType::InitCode(c) => {
+                out.extend((c.len() as u32).to_be_bytes());
+                out.extend(c.iter());
+            }
+            HunkType::ExceptionInfo(e) => {
+                out.extend((e.len() as u32).to_be_bytes());
+                out.extend(e.iter());
+            }
+            HunkType::CFMImportContainer(c) | HunkType::WeakImportContainer(c) => {
+                out.extend(c.name_id.to_be_bytes());
+                out.extend(c.old_def_version.to_be_bytes());
+                out.extend(c.old_imp_version.to_be_bytes());
+                out.extend(c.current_version.to_be_bytes());
+            }
+            HunkType::GlobalEntry(e) | HunkType::LocalEntry(e) => {
+                out.extend(e.name_id.to_be_bytes());
+                out.extend(e.offset.to_be_bytes());
+            }
+            HunkType::GlobalDataPointer(p) | HunkType::LocalDataPointer(p) => {
+                out.extend(p.name_id.to_be_bytes());
+                out.extend(p.data_name.to_be_bytes());
+            }
+            HunkType::GlobalXPointer(p) | HunkType::LocalXPointer(p) => {
+                out.extend(p.name_id.to_be_bytes());
+                out.extend(p.xvector_name.to_be_bytes());
+            }
+            HunkType::GlobalXVector(v) | HunkType::LocalXVector(v) => {
+                out.extend(v.name_id.to_be_bytes());
+                out.extend(v.function_name.to_be_bytes());
+            }
+            HunkType::SrcBreak(s) => {
+                out.extend(s.name_id.to_be_bytes());
+                out.extend(s.moddate.as_raw().to_be_bytes());
+            }
+            HunkType::MethodReference(m) => {
+                out.extend(m.name_id.to_be_bytes());
+                out.extend(m.size.to_be_bytes());
+            }
+            HunkType::Segment(s) => out.extend(s.name_id.to_be_bytes()),
+            HunkType::CFMImport(i) => out.extend(i.name_id.to_be_bytes()),
+            HunkType::ForceActive(f) => out.extend(f.name_id.to_be_bytes()),
+            _ => (),
+        }
+
+        Ok(w.write_all(&out)?)
+    }
 }
 
 #[allow(non_camel_case_types)]
@@ -443,7 +1108,7 @@ impl Default for HunkParseState {
 }
 
 impl TryFrom<u16> for HunkParseState {
-    type Error = &'static str;
+    type Error = LibmetroError;
 
     fn try_from(value: u16) -> Result<Self, Self::Error> {
         match value {
@@ -533,7 +1198,7 @@ impl TryFrom<u16> for HunkParseState {
                 RawHunkType::HUNK_XREF_CODE32BIT,
             )),
             x if x == RawHunkType::HUNK_FORCE_ACTIVE as u16 => Ok(
-                HunkParseState::ParseReservedHunk(RawHunkType::HUNK_FORCE_ACTIVE),
+                HunkParseState::ParseObjSegmentHunk(RawHunkType::HUNK_FORCE_ACTIVE),
             ),
             x if x == RawHunkType::HUNK_GLOBAL_DATAPOINTER as u16 => Ok(
                 HunkParseState::ParseDataPointerHunk(RawHunkType::HUNK_GLOBAL_DATAPOINTER),
@@ -592,16 +1257,18 @@ impl TryFrom<u16> for HunkParseState {
             x if x == RawHunkType::HUNK_WEAK_IMPORT_CONTAINER as u16 => Ok(
                 HunkParseState::ParseObjContainerHunk(RawHunkType::HUNK_WEAK_IMPORT_CONTAINER),
             ),
-            _ => Err("Bad branch select for hunk"),
+            _ => Err(LibmetroError::BadHunkTag(value)),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CodeHunks {
     hunks: Vec<Hunk>,
 }
 
+/// Kept for existing callers, prefer `entries`, `len` and `get`.
 impl Deref for CodeHunks {
     type Target = Vec<Hunk>;
 
@@ -610,11 +1277,540 @@ impl Deref for CodeHunks {
     }
 }
 
+impl IntoIterator for CodeHunks {
+    type Item = Hunk;
+    type IntoIter = std::vec::IntoIter<Hunk>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.hunks.into_iter()
+    }
+}
+
+impl From<Vec<Hunk>> for CodeHunks {
+    fn from(value: Vec<Hunk>) -> Self {
+        CodeHunks { hunks: value }
+    }
+}
+
+impl RawLength for CodeHunks {
+    fn raw_length(&self) -> usize {
+        self.hunks.iter().map(|x| x.raw_length()).sum()
+    }
+}
+
+impl Serializable for CodeHunks {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        for hunk in self.hunks.iter() {
+            hunk.serialize_out(w)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A lightweight triage report over a parsed hunk stream.
+#[derive(Debug, Clone)]
+pub struct HunkSummary {
+    count: usize,
+    has_start: bool,
+    has_end: bool,
+    unknown: Vec<u16>,
+    kinds: HashMap<&'static str, usize>,
+}
+
+impl HunkSummary {
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Whether the first hunk is a `Start` hunk.
+    pub fn has_start(&self) -> bool {
+        self.has_start
+    }
+
+    /// Whether the last hunk is an `End` hunk.
+    pub fn has_end(&self) -> bool {
+        self.has_end
+    }
+
+    /// Tags of any reserved hunks, in stream order. The parser keeps these, but
+    /// their layout isn't known.
+    pub fn unknown(&self) -> &[u16] {
+        &self.unknown
+    }
+
+    /// Number of hunks seen for each `HunkType::kind`.
+    pub fn kinds(&self) -> &HashMap<&'static str, usize> {
+        &self.kinds
+    }
+
+    /// Starts with `Start`, ends with `End`, and holds no reserved hunks.
+    pub fn is_well_formed(&self) -> bool {
+        self.has_start && self.has_end && self.unknown.is_empty()
+    }
+}
+
+/// XRef and class pairs are both 8 bytes, make sure the declared count fits
+/// before walking them.
+fn check_pair_count(num_pairs: u16, remaining: usize, kind: &str) -> Result<(), LibmetroError> {
+    let needed = num_pairs as usize * 8;
+    if needed > remaining {
+        return Err(LibmetroError::Malformed(format!(
+            "{} hunk declares {} pairs ({} bytes), but only {} bytes remain",
+            kind, num_pairs, needed, remaining
+        )));
+    }
+
+    Ok(())
+}
+
+fn resolve_name(obj: &MetrowerksObject, name_id: u32) -> &str {
+    obj.name_by_id(name_id)
+        .map(|x| x.name().as_str())
+        .unwrap_or_default()
+}
+
+fn pad_to(bytes: &mut Vec<u8>, alignment: usize) {
+    if alignment > 1 {
+        bytes.resize(bytes.len().next_multiple_of(alignment), 0);
+    }
+}
+
+impl CodeHunks {
+    /// The hunks, in stream order.
+    pub fn entries(&self) -> &[Hunk] {
+        &self.hunks
+    }
+
+    pub fn len(&self) -> usize {
+        self.hunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hunks.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Hunk> {
+        self.hunks.get(index)
+    }
+
+    /// Inserts `hunk` at `index`, which must fall after the `Start` hunk and no
+    /// later than the `End` hunk.
+    ///
+    /// `Start` and `End` hunks can't be inserted, and nothing can be inserted
+    /// into hunks that don't begin with `Start` and finish with `End`.
+    pub fn insert_hunk(&mut self, index: usize, hunk: Hunk) -> Result<(), LibmetroError> {
+        if matches!(hunk.hunk, HunkType::Start(_) | HunkType::End(_)) {
+            return Err(LibmetroError::Invalid(format!(
+                "Can't insert a {} hunk",
+                hunk.hunk.kind()
+            )));
+        }
+
+        self.check_bookends(index, self.hunks.len().saturating_sub(1))?;
+        self.hunks.insert(index, hunk);
+
+        Ok(())
+    }
+
+    /// Removes and returns the hunk at `index`, which can't be the `Start` or
+    /// `End` hunk.
+    pub fn remove_hunk(&mut self, index: usize) -> Result<Hunk, LibmetroError> {
+        self.check_bookends(index, self.hunks.len().saturating_sub(2))?;
+
+        Ok(self.hunks.remove(index))
+    }
+
+    fn check_bookends(&self, index: usize, last: usize) -> Result<(), LibmetroError> {
+        let summary = self.structural_summary();
+        if !summary.has_start() || !summary.has_end() {
+            return Err(LibmetroError::Invalid(
+                "Hunks must start with a Start hunk and finish with an End hunk".to_owned(),
+            ));
+        }
+
+        if index == 0 || index > last {
+            return Err(LibmetroError::Invalid(format!(
+                "Hunk index {} is outside the Start and End hunks, expected: 1..={}",
+                index, last
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Whether any hunk is one the format reserves (see `HunkType::is_reserved`).
+    pub fn has_reserved(&self) -> bool {
+        self.has_reserved_for(LibraryProcessor::Unknown)
+    }
+
+    /// Whether any hunk is reserved in an object for `proc`, see
+    /// `HunkType::is_reserved_for`.
+    pub fn has_reserved_for(&self, proc: LibraryProcessor) -> bool {
+        self.hunks.iter().any(|h| h.hunk.is_reserved_for(proc))
+    }
+
+    /// The local and global code hunks.
+    pub fn code_hunks(&self) -> impl Iterator<Item = &ObjCodeHunk> {
+        self.hunks.iter().filter_map(|h| match &h.hunk {
+            HunkType::LocalCode(c) | HunkType::GlobalCode(c) => Some(c),
+            _ => None,
+        })
+    }
+
+    /// Each code hunk's name id and code, local and global, in file order.
+    pub fn iter_code(&self) -> impl Iterator<Item = (u32, &[u8])> {
+        self.code_hunks().map(|c| (c.name_id, c.code.as_slice()))
+    }
+
+    /// The code of every code hunk joined together in file order.
+    pub fn concatenated_code(&self) -> Vec<u8> {
+        self.iter_code()
+            .flat_map(|(_, code)| code)
+            .copied()
+            .collect()
+    }
+
+    /// The initialized and uninitialized data hunks, near and far, local and global.
+    pub fn data_hunks(&self) -> impl Iterator<Item = &ObjDataHunk> {
+        self.hunks.iter().filter_map(|h| match &h.hunk {
+            HunkType::LocalUninitializedData(d)
+            | HunkType::GlobalUninitializedData(d)
+            | HunkType::LocalInitializedData(d)
+            | HunkType::GlobalInitializedData(d)
+            | HunkType::LocalFarUninitializedData(d)
+            | HunkType::GlobalFarUninitializedData(d)
+            | HunkType::LocalFarInitializedData(d)
+            | HunkType::GlobalFarInitializedData(d) => Some(d),
+            _ => None,
+        })
+    }
+
+    /// The cross reference hunks of every width.
+    pub fn xref_hunks(&self) -> impl Iterator<Item = &ObjXRefHunk> {
+        self.hunks.iter().filter_map(|h| match &h.hunk {
+            HunkType::XRefCodeJT16Bit(x)
+            | HunkType::XRefData16Bit(x)
+            | HunkType::XRef32Bit(x)
+            | HunkType::XRefCode16Bit(x)
+            | HunkType::XRefCode32Bit(x)
+            | HunkType::XRefPCRelative32Bit(x)
+            | HunkType::XRefAmbiguous16Bit(x) => Some(x),
+            _ => None,
+        })
+    }
+
+    /// The local and global entry point hunks.
+    pub fn entry_hunks(&self) -> impl Iterator<Item = &ObjEntryHunk> {
+        self.hunks.iter().filter_map(|h| match &h.hunk {
+            HunkType::LocalEntry(e) | HunkType::GlobalEntry(e) => Some(e),
+            _ => None,
+        })
+    }
+
+    /// The total bytes of code in the `LocalCode` and `GlobalCode` hunks.
+    ///
+    /// `InitCode` hunks aren't counted, matching the object header's code size.
+    pub fn code_length(&self) -> usize {
+        self.hunks
+            .iter()
+            .map(|h| match &h.hunk {
+                HunkType::LocalCode(c) | HunkType::GlobalCode(c) => c.len(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// The total size of the `LocalUninitializedData`, `GlobalUninitializedData`,
+    /// `LocalFarUninitializedData` and `GlobalFarUninitializedData` hunks.
+    pub fn udata_length(&self) -> usize {
+        self.hunks
+            .iter()
+            .map(|h| match &h.hunk {
+                HunkType::LocalUninitializedData(d)
+                | HunkType::GlobalUninitializedData(d)
+                | HunkType::LocalFarUninitializedData(d)
+                | HunkType::GlobalFarUninitializedData(d) => d.size() as usize,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// The total size of the `LocalInitializedData`, `GlobalInitializedData`,
+    /// `LocalFarInitializedData` and `GlobalFarInitializedData` hunks.
+    pub fn idata_length(&self) -> usize {
+        self.hunks
+            .iter()
+            .map(|h| match &h.hunk {
+                HunkType::LocalInitializedData(d)
+                | HunkType::GlobalInitializedData(d)
+                | HunkType::LocalFarInitializedData(d)
+                | HunkType::GlobalFarInitializedData(d) => d.size() as usize,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// The total code, uninitialized data, and initialized data sizes of these
+    /// hunks, as recorded in the object header.
+    pub(crate) fn section_sizes(&self) -> (usize, usize, usize) {
+        (self.code_length(), self.udata_length(), self.idata_length())
+    }
+
+    /// Checks the code, uninitialized data, and initialized data sizes an
+    /// object header would record against the sizes of these hunks.
+    ///
+    /// Fails with a `SizeMismatch` for the first section that differs.
+    pub fn validate_sizes(
+        &self,
+        code: usize,
+        udata: usize,
+        idata: usize,
+    ) -> Result<(), LibmetroError> {
+        let (code_size, udata_size, idata_size) = self.section_sizes();
+
+        for (section, expected, computed) in [
+            ("code", code, code_size),
+            ("uninitialized data", udata, udata_size),
+            ("initialized data", idata, idata_size),
+        ] {
+            if expected != computed {
+                return Err(LibmetroError::SizeMismatch {
+                    section,
+                    expected,
+                    computed,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A copy of these hunks with every code and initialized data payload zero
+    /// padded to a multiple of `alignment` bytes.
+    pub fn aligned(&self, alignment: usize) -> CodeHunks {
+        let mut hunks = self.hunks.clone();
+
+        for h in hunks.iter_mut() {
+            match &mut h.hunk {
+                HunkType::LocalCode(c) | HunkType::GlobalCode(c) => pad_to(&mut c.code, alignment),
+                HunkType::InitCode(c) => pad_to(&mut c.code, alignment),
+                HunkType::LocalInitializedData(d)
+                | HunkType::GlobalInitializedData(d)
+                | HunkType::LocalFarInitializedData(d)
+                | HunkType::GlobalFarInitializedData(d) => {
+                    pad_to(&mut d.data, alignment);
+                    d.size = d.data.len() as u32;
+                }
+                _ => (),
+            }
+        }
+
+        CodeHunks { hunks }
+    }
+
+    /// Points each code hunk that has symbols at the routine for it, assigning
+    /// `offsets` to those hunks in order.
+    pub(crate) fn link_routines(&mut self, offsets: &[usize]) -> Result<(), LibmetroError> {
+        let mut code: Vec<&mut ObjCodeHunk> = self
+            .hunks
+            .iter_mut()
+            .filter_map(|h| match &mut h.hunk {
+                HunkType::LocalCode(c) | HunkType::GlobalCode(c) if c.has_symtab() => Some(c),
+                _ => None,
+            })
+            .collect();
+
+        if code.len() != offsets.len() {
+            return Err(LibmetroError::Invalid(format!(
+                "{} code hunks have symbols, but there are {} routines",
+                code.len(),
+                offsets.len()
+            )));
+        }
+
+        for (c, off) in code.iter_mut().zip(offsets) {
+            c.sym_offset = *off as u32;
+        }
+
+        Ok(())
+    }
+
+    pub fn structural_summary(&self) -> HunkSummary {
+        let mut unknown = vec![];
+        let mut kinds: HashMap<&'static str, usize> = HashMap::new();
+
+        for h in self.hunks.iter() {
+            if h.hunk.is_reserved() {
+                unknown.push(h.tag());
+            }
+            *kinds.entry(h.hunk.kind()).or_default() += 1;
+        }
+
+        HunkSummary {
+            count: self.hunks.len(),
+            has_start: matches!(
+                self.hunks.first().map(|x| &x.hunk),
+                Some(HunkType::Start(_))
+            ),
+            has_end: matches!(self.hunks.last().map(|x| &x.hunk), Some(HunkType::End(_))),
+            unknown,
+            kinds,
+        }
+    }
+}
+
+/// Walks a hunk stream best-effort, returning the name id (where the hunk has
+/// one) and bytes of every code, init code, and initialized data hunk found.
+///
+/// Stops at the first unknown or reserved tag. A hunk cut short by the end of
+/// the buffer still yields whatever bytes of it are present.
+pub(crate) fn salvage_hunks(value: &[u8]) -> Vec<(Option<u32>, Vec<u8>)> {
+    let mut data: &[u8] = value;
+    let mut blobs: Vec<(Option<u32>, Vec<u8>)> = vec![];
+
+    let field = |d: &[u8], at: usize| -> Option<u32> {
+        Some(convert_be_u32(d.get(at..at + 4)?.try_into().unwrap()))
+    };
+
+    while data.len() >= 2 {
+        let tag = convert_be_u16(&data[0..2].try_into().unwrap());
+        data = &data[2..];
+
+        let state = match HunkParseState::try_from(tag) {
+            Ok(x) => x,
+            Err(_) => break,
+        };
+
+        // Body length of this hunk, and the blob it carries if any
+        let (len, blob) = match state {
+            HunkParseState::ParseObjSimpleHunk(_) => (0, None),
+            HunkParseState::ParseObjCodeHunk(_) => {
+                let (Some(name_id), Some(size)) = (field(data, 0), field(data, 4)) else {
+                    break;
+                };
+                let code = data.get(16..).unwrap_or_default();
+                let code = &code[..usize::min(size as usize, code.len())];
+
+                (16 + size as usize, Some((Some(name_id), code)))
+            }
+            HunkParseState::ParseDataHunk(tag) => {
+                let (Some(name_id), Some(size)) = (field(data, 0), field(data, 4)) else {
+                    break;
+                };
+                match tag {
+                    RawHunkType::HUNK_GLOBAL_IDATA
+                    | RawHunkType::HUNK_LOCAL_IDATA
+                    | RawHunkType::HUNK_GLOBAL_FARIDATA
+                    | RawHunkType::HUNK_LOCAL_FARIDATA => {
+                        let bytes = data.get(16..).unwrap_or_default();
+                        let bytes = &bytes[..usize::min(size as usize, bytes.len())];
+
+                        (16 + size as usize, Some((Some(name_id), bytes)))
+                    }
+                    _ => (16, None),
+                }
+            }
+            HunkParseState::ParseInitCodeHunk(_) | HunkParseState::ParseExceptInfoHunk(_) => {
+                let Some(size) = field(data, 0) else {
+                    break;
+                };
+                let bytes = data.get(4..).unwrap_or_default();
+                let bytes = &bytes[..usize::min(size as usize, bytes.len())];
+
+                let blob = match state {
+                    HunkParseState::ParseInitCodeHunk(_) => Some((None, bytes)),
+                    _ => None,
+                };
+                (4 + size as usize, blob)
+            }
+            HunkParseState::ParseXRefHunk(_) => match data.get(4..6) {
+                Some(n) => (
+                    6 + 8 * convert_be_u16(&n.try_into().unwrap()) as usize,
+                    None,
+                ),
+                None => break,
+            },
+            HunkParseState::ParseObjClassHunk(_) => match data.get(6..8) {
+                Some(n) => (
+                    8 + 8 * convert_be_u16(&n.try_into().unwrap()) as usize,
+                    None,
+                ),
+                None => break,
+            },
+            HunkParseState::ParseObjContainerHunk(_) => (16, None),
+            HunkParseState::ParseObjImportHunk(_) | HunkParseState::ParseObjSegmentHunk(_) => {
+                (4, None)
+            }
+            HunkParseState::ParseAltEntryHunk(_)
+            | HunkParseState::ParseDataPointerHunk(_)
+            | HunkParseState::ParseXPointerHunk(_)
+            | HunkParseState::ParseXVectorHunk(_)
+            | HunkParseState::ParseObjSourceHunk(_)
+            | HunkParseState::ParseObjMethHunk(_) => (8, None),
+            _ => break,
+        };
+
+        if let Some((name_id, bytes)) = blob {
+            if !bytes.is_empty() {
+                blobs.push((name_id, bytes.to_vec()));
+            }
+        }
+
+        if len > data.len() {
+            break;
+        }
+        data = &data[len..];
+    }
+
+    blobs
+}
+
+/// Parses a hunk stream without regard to processor, accepting every hunk the
+/// format defines.
 impl TryFrom<&[u8]> for CodeHunks {
-    type Error = String;
+    type Error = LibmetroError;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        let mut data: &[u8] = value;
+        CodeHunks::try_from((value, LibraryProcessor::Unknown))
+    }
+}
+
+/// Parses a hunk stream from an object built for the given processor.
+///
+/// Both processors share the hunk tags and layouts; hunks that aren't legal for
+/// the processor, such as `HUNK_SEGMENT` in a PowerPC object or
+/// `HUNK_FORCE_ACTIVE` in an m68k one, are rejected. Reserved hunks are kept.
+impl TryFrom<(&[u8], LibraryProcessor)> for CodeHunks {
+    type Error = LibmetroError;
+
+    fn try_from(value: (&[u8], LibraryProcessor)) -> Result<Self, Self::Error> {
+        CodeHunks::try_from_with_options(value.0, value.1, &ParseOptions::default())
+    }
+}
+
+impl CodeHunks {
+    /// Parses a hunk stream like `try_from`, checking reserved hunks and the
+    /// End hunk as `options` asks.
+    pub fn try_from_with_options(
+        value: &[u8],
+        proc: LibraryProcessor,
+        options: &ParseOptions,
+    ) -> Result<Self, LibmetroError> {
+        let mut hunk_start = 0;
+
+        CodeHunks::parse(value, proc, options, &mut hunk_start).map_err(|e| e.at_offset(hunk_start))
+    }
+
+    /// The hunk stream parser, leaving `hunk_start` at the offset of the hunk
+    /// it was reading when it stopped.
+    fn parse(
+        value: &[u8],
+        proc: LibraryProcessor,
+        options: &ParseOptions,
+        hunk_start: &mut usize,
+    ) -> Result<Self, LibmetroError> {
+        let mut data = value;
 
         let mut hunks: Vec<Hunk> = vec![];
 
@@ -622,11 +1818,13 @@ impl TryFrom<&[u8]> for CodeHunks {
         while state != HunkParseState::End {
             state = match state {
                 HunkParseState::ParseTag => {
+                    *hunk_start = value.len() - data.len();
+                    check_len(data, 2)?;
                     let tag = convert_be_u16(&data[0..2].try_into().unwrap());
 
                     data = &data[2..];
 
-                    HunkParseState::try_from(tag).unwrap()
+                    HunkParseState::try_from(tag)?
                 }
                 HunkParseState::ParseObjSimpleHunk(tag) => {
                     let hunk = match tag {
@@ -643,14 +1841,14 @@ impl TryFrom<&[u8]> for CodeHunks {
                         RawHunkType::HUNK_CFM_EXPORT => HunkType::CFMExport(ObjSimpleHunk {}),
 
                         _ => {
-                            return Err(format!(
+                            return Err(LibmetroError::Malformed(format!(
                                 "Bad branch selection in {:#?} for tag: {:#?}",
                                 state, tag
-                            ))
+                            )))
                         }
                     };
 
-                    HunkParseState::CommitHunk(Hunk { hunk: hunk })
+                    HunkParseState::CommitHunk(Hunk::from(hunk))
                 }
                 HunkParseState::ParseReservedHunk(tag) => {
                     let hunk = match tag {
@@ -665,33 +1863,36 @@ impl TryFrom<&[u8]> for CodeHunks {
                         RawHunkType::HUNK_ILLEGAL1 => HunkType::Illegal1(ReservedHunk {}),
                         RawHunkType::HUNK_ILLEGAL2 => HunkType::Illegal2(ReservedHunk {}),
 
+                        RawHunkType::HUNK_CFM_INTERNAL => HunkType::CFMInternal(ReservedHunk {}),
+
                         _ => {
-                            return Err(format!(
+                            return Err(LibmetroError::Malformed(format!(
                                 "Bad branch selection in {:#?} for tag: {:#?}",
                                 state, tag
-                            ))
+                            )))
                         }
                     };
 
-                    return Err(format!("Encountered Reserved Hunk: {:?}", hunk));
-
-                    // Commit nothing cause we found reserved hunks we can't process
-                    // HunkParseState::CommitHunk(Hunk { hunk: hunk })
+                    // Nothing is known of their layout past the tag, so they
+                    // are kept as a bare tag and left for the caller to judge
+                    HunkParseState::CommitHunk(Hunk::from(hunk))
                 }
                 HunkParseState::ParseObjCodeHunk(tag) => {
-                    let special = match &hunks.last().unwrap().hunk {
-                        HunkType::CFMExport(_) => ObjCodeFlag::CFMExport,
-                        HunkType::GlobalOverload(_) => ObjCodeFlag::GlobalOverload,
-                        HunkType::GlobalMultiDef(_) => ObjCodeFlag::GlobalMultiDef,
+                    let special = match hunks.last().map(|x| &x.hunk) {
+                        Some(HunkType::CFMExport(_)) => ObjCodeFlag::CFMExport,
+                        Some(HunkType::GlobalOverload(_)) => ObjCodeFlag::GlobalOverload,
+                        Some(HunkType::GlobalMultiDef(_)) => ObjCodeFlag::GlobalMultiDef,
                         _ => ObjCodeFlag::None,
                     };
 
+                    check_len(data, 16)?;
                     let name_id = convert_be_u32(&data[0..4].try_into().unwrap());
                     let size = convert_be_u32(&data[4..8].try_into().unwrap());
                     let sym_offset = convert_be_u32(&data[8..12].try_into().unwrap());
                     let sym_decl_offset = convert_be_u32(&data[12..16].try_into().unwrap());
 
                     data = &data[16..];
+                    check_len(data, size as usize)?;
                     let code = &data[0..size as usize];
                     data = &data[size as usize..];
 
@@ -708,41 +1909,42 @@ impl TryFrom<&[u8]> for CodeHunks {
                         RawHunkType::HUNK_GLOBAL_CODE => HunkType::GlobalCode(obj_hunk),
 
                         _ => {
-                            return Err(format!(
+                            return Err(LibmetroError::Malformed(format!(
                                 "Bad branch selection in {:#?} for tag: {:#?}",
                                 state, tag
-                            ))
+                            )))
                         }
                     };
 
-                    HunkParseState::CommitHunk(Hunk { hunk: hunk })
+                    HunkParseState::CommitHunk(Hunk::from(hunk))
                 }
                 HunkParseState::ParseInitCodeHunk(tag) => {
+                    check_len(data, 4)?;
                     let size = convert_be_u32(&data[0..4].try_into().unwrap());
 
                     data = &data[4..];
+                    check_len(data, size as usize)?;
                     let code = &data[0..size as usize];
                     data = &data[size as usize..];
 
-                    let obj_hunk = ObjInitHunk {
-                        code: code.to_owned(),
-                    };
+                    let obj_hunk = ObjInitHunk::new(code);
 
                     let hunk = match tag {
                         RawHunkType::HUNK_INIT_CODE => HunkType::InitCode(obj_hunk),
 
                         _ => {
-                            return Err(format!(
+                            return Err(LibmetroError::Malformed(format!(
                                 "Bad branch selection in {:#?} for tag: {:#?}",
                                 state, tag
-                            ))
+                            )))
                         }
                     };
 
-                    HunkParseState::CommitHunk(Hunk { hunk: hunk })
+                    HunkParseState::CommitHunk(Hunk::from(hunk))
                 }
 
                 HunkParseState::ParseDataHunk(tag) => {
+                    check_len(data, 16)?;
                     let name_id = convert_be_u32(&data[0..4].try_into().unwrap());
                     let size = convert_be_u32(&data[4..8].try_into().unwrap());
                     let sym_offset = convert_be_u32(&data[8..12].try_into().unwrap());
@@ -756,6 +1958,7 @@ impl TryFrom<&[u8]> for CodeHunks {
                         | RawHunkType::HUNK_LOCAL_IDATA
                         | RawHunkType::HUNK_GLOBAL_FARIDATA
                         | RawHunkType::HUNK_LOCAL_FARIDATA => {
+                            check_len(data, size as usize)?;
                             let c = &data[0..size as usize];
                             data = &data[size as usize..];
                             c
@@ -765,6 +1968,7 @@ impl TryFrom<&[u8]> for CodeHunks {
 
                     let obj_hunk = ObjDataHunk {
                         name_id: name_id,
+                        size,
                         sym_offset: sym_offset,
                         sym_decl_offset: sym_decl_offset,
                         data: code.to_owned(),
@@ -790,16 +1994,17 @@ impl TryFrom<&[u8]> for CodeHunks {
                             HunkType::LocalFarUninitializedData(obj_hunk)
                         }
                         _ => {
-                            return Err(format!(
+                            return Err(LibmetroError::Malformed(format!(
                                 "Bad branch selection in {:#?} for tag: {:#?}",
                                 state, tag
-                            ))
+                            )))
                         }
                     };
 
-                    HunkParseState::CommitHunk(Hunk { hunk: hunk })
+                    HunkParseState::CommitHunk(Hunk::from(hunk))
                 }
                 HunkParseState::ParseAltEntryHunk(tag) => {
+                    check_len(data, 8)?;
                     let name_id = convert_be_u32(&data[0..4].try_into().unwrap());
                     let offset = convert_be_u32(&data[4..8].try_into().unwrap());
 
@@ -814,20 +2019,22 @@ impl TryFrom<&[u8]> for CodeHunks {
                         RawHunkType::HUNK_GLOBAL_ENTRY => HunkType::GlobalEntry(entry_hunk),
                         RawHunkType::HUNK_LOCAL_ENTRY => HunkType::LocalEntry(entry_hunk),
                         _ => {
-                            return Err(format!(
+                            return Err(LibmetroError::Malformed(format!(
                                 "Bad branch selection in {:#?} for tag: {:#?}",
                                 state, tag
-                            ))
+                            )))
                         }
                     };
 
-                    HunkParseState::CommitHunk(Hunk { hunk: hunk })
+                    HunkParseState::CommitHunk(Hunk::from(hunk))
                 }
                 HunkParseState::ParseXRefHunk(tag) => {
+                    check_len(data, 6)?;
                     let name_id = convert_be_u32(&data[0..4].try_into().unwrap());
                     let num_pairs = convert_be_u16(&data[4..6].try_into().unwrap());
 
                     data = &data[6..];
+                    check_pair_count(num_pairs, data.len(), "XRef")?;
 
                     // process pairs
                     let mut pairs: Vec<ObjXRefPair> = vec![];
@@ -862,19 +2069,21 @@ impl TryFrom<&[u8]> for CodeHunks {
                         }
 
                         _ => {
-                            return Err(format!(
+                            return Err(LibmetroError::Malformed(format!(
                                 "Bad branch selection in {:#?} for tag: {:#?}",
                                 state, tag
-                            ))
+                            )))
                         }
                     };
 
-                    HunkParseState::CommitHunk(Hunk { hunk: hunk })
+                    HunkParseState::CommitHunk(Hunk::from(hunk))
                 }
                 HunkParseState::ParseExceptInfoHunk(tag) => {
+                    check_len(data, 4)?;
                     let size = convert_be_u32(&data[0..4].try_into().unwrap());
 
                     data = &data[4..];
+                    check_len(data, size as usize)?;
                     let code = &data[0..size as usize];
                     data = &data[size as usize..];
 
@@ -886,16 +2095,17 @@ impl TryFrom<&[u8]> for CodeHunks {
                         RawHunkType::HUNK_EXCEPTION_INFO => HunkType::ExceptionInfo(exp_hunk),
 
                         _ => {
-                            return Err(format!(
+                            return Err(LibmetroError::Malformed(format!(
                                 "Bad branch selection in {:#?} for tag: {:#?}",
                                 state, tag
-                            ))
+                            )))
                         }
                     };
 
-                    HunkParseState::CommitHunk(Hunk { hunk: hunk })
+                    HunkParseState::CommitHunk(Hunk::from(hunk))
                 }
                 HunkParseState::ParseObjContainerHunk(tag) => {
+                    check_len(data, 16)?;
                     let name_id = convert_be_u32(&data[0..4].try_into().unwrap());
                     let old_def_version = convert_be_u32(&data[4..8].try_into().unwrap());
                     let old_imp_version = convert_be_u32(&data[8..12].try_into().unwrap());
@@ -919,16 +2129,17 @@ impl TryFrom<&[u8]> for CodeHunks {
                         }
 
                         _ => {
-                            return Err(format!(
+                            return Err(LibmetroError::Malformed(format!(
                                 "Bad branch selection in {:#?} for tag: {:#?}",
                                 state, tag
-                            ))
+                            )))
                         }
                     };
 
-                    HunkParseState::CommitHunk(Hunk { hunk: hunk })
+                    HunkParseState::CommitHunk(Hunk::from(hunk))
                 }
                 HunkParseState::ParseObjImportHunk(tag) => {
+                    check_len(data, 4)?;
                     let name_id = convert_be_u32(&data[0..4].try_into().unwrap());
 
                     data = &data[4..];
@@ -939,16 +2150,17 @@ impl TryFrom<&[u8]> for CodeHunks {
                         RawHunkType::HUNK_CFM_IMPORT => HunkType::CFMImport(obj_hunk),
 
                         _ => {
-                            return Err(format!(
+                            return Err(LibmetroError::Malformed(format!(
                                 "Bad branch selection in {:#?} for tag: {:#?}",
                                 state, tag
-                            ))
+                            )))
                         }
                     };
 
-                    HunkParseState::CommitHunk(Hunk { hunk: hunk })
+                    HunkParseState::CommitHunk(Hunk::from(hunk))
                 }
                 HunkParseState::ParseDataPointerHunk(tag) => {
+                    check_len(data, 8)?;
                     let name_id: u32 = convert_be_u32(&data[0..4].try_into().unwrap());
                     let d_name: u32 = convert_be_u32(&data[4..8].try_into().unwrap());
 
@@ -965,16 +2177,17 @@ impl TryFrom<&[u8]> for CodeHunks {
                             HunkType::GlobalDataPointer(dp_hunk)
                         }
                         _ => {
-                            return Err(format!(
+                            return Err(LibmetroError::Malformed(format!(
                                 "Bad branch selection in {:#?} for tag: {:#?}",
                                 state, tag
-                            ))
+                            )))
                         }
                     };
 
-                    HunkParseState::CommitHunk(Hunk { hunk: hunk })
+                    HunkParseState::CommitHunk(Hunk::from(hunk))
                 }
                 HunkParseState::ParseXPointerHunk(tag) => {
+                    check_len(data, 8)?;
                     let xp_name: u32 = convert_be_u32(&data[0..4].try_into().unwrap());
                     let xv_name: u32 = convert_be_u32(&data[4..8].try_into().unwrap());
 
@@ -989,16 +2202,17 @@ impl TryFrom<&[u8]> for CodeHunks {
                         RawHunkType::HUNK_LOCAL_XPOINTER => HunkType::LocalXPointer(xp_hunk),
                         RawHunkType::HUNK_GLOBAL_XPOINTER => HunkType::GlobalXPointer(xp_hunk),
                         _ => {
-                            return Err(format!(
+                            return Err(LibmetroError::Malformed(format!(
                                 "Bad branch selection in {:#?} for tag: {:#?}",
                                 state, tag
-                            ))
+                            )))
                         }
                     };
 
-                    HunkParseState::CommitHunk(Hunk { hunk: hunk })
+                    HunkParseState::CommitHunk(Hunk::from(hunk))
                 }
                 HunkParseState::ParseXVectorHunk(tag) => {
+                    check_len(data, 8)?;
                     let xv_name: u32 = convert_be_u32(&data[0..4].try_into().unwrap());
                     let f_name: u32 = convert_be_u32(&data[4..8].try_into().unwrap());
 
@@ -1013,16 +2227,17 @@ impl TryFrom<&[u8]> for CodeHunks {
                         RawHunkType::HUNK_LOCAL_XVECTOR => HunkType::LocalXVector(xv_hunk),
                         RawHunkType::HUNK_GLOBAL_XVECTOR => HunkType::GlobalXVector(xv_hunk),
                         _ => {
-                            return Err(format!(
+                            return Err(LibmetroError::Malformed(format!(
                                 "Bad branch selection in {:#?} for tag: {:#?}",
                                 state, tag
-                            ))
+                            )))
                         }
                     };
 
-                    HunkParseState::CommitHunk(Hunk { hunk: hunk })
+                    HunkParseState::CommitHunk(Hunk::from(hunk))
                 }
                 HunkParseState::ParseObjSourceHunk(tag) => {
+                    check_len(data, 8)?;
                     let name_id: u32 = convert_be_u32(&data[0..4].try_into().unwrap());
                     let moddate: u32 = convert_be_u32(&data[4..8].try_into().unwrap());
 
@@ -1030,41 +2245,44 @@ impl TryFrom<&[u8]> for CodeHunks {
 
                     let src_hunk = ObjSourceHunk {
                         name_id: name_id,
-                        moddate: from_mac_datetime(moddate).into(),
+                        moddate: MacDateTime::from(moddate),
                     };
 
                     let hunk = match tag {
                         RawHunkType::HUNK_SRC_BREAK => HunkType::SrcBreak(src_hunk),
                         _ => {
-                            return Err(format!(
+                            return Err(LibmetroError::Malformed(format!(
                                 "Bad branch selection in {:#?} for tag: {:#?}",
                                 state, tag
-                            ))
+                            )))
                         }
                     };
 
-                    HunkParseState::CommitHunk(Hunk { hunk: hunk })
+                    HunkParseState::CommitHunk(Hunk::from(hunk))
                 }
                 HunkParseState::ParseObjSegmentHunk(tag) => {
+                    check_len(data, 4)?;
                     let name_id: u32 = convert_be_u32(&data[0..4].try_into().unwrap());
 
                     data = &data[4..];
 
-                    let seg_hunk = ObjSegHunk { name_id: name_id };
-
                     let hunk = match tag {
-                        RawHunkType::HUNK_SEGMENT => HunkType::Segment(seg_hunk),
+                        RawHunkType::HUNK_SEGMENT => HunkType::Segment(ObjSegHunk { name_id }),
+                        RawHunkType::HUNK_FORCE_ACTIVE => {
+                            HunkType::ForceActive(ObjForceActiveHunk { name_id })
+                        }
                         _ => {
-                            return Err(format!(
+                            return Err(LibmetroError::Malformed(format!(
                                 "Bad branch selection in {:#?} for tag: {:#?}",
                                 state, tag
-                            ))
+                            )))
                         }
                     };
 
-                    HunkParseState::CommitHunk(Hunk { hunk: hunk })
+                    HunkParseState::CommitHunk(Hunk::from(hunk))
                 }
                 HunkParseState::ParseObjMethHunk(tag) => {
+                    check_len(data, 8)?;
                     let name_id: u32 = convert_be_u32(&data[0..4].try_into().unwrap());
                     let size: u32 = convert_be_u32(&data[4..8].try_into().unwrap());
 
@@ -1078,21 +2296,23 @@ impl TryFrom<&[u8]> for CodeHunks {
                     let hunk = match tag {
                         RawHunkType::HUNK_METHOD_REF => HunkType::MethodReference(meth_hunk),
                         _ => {
-                            return Err(format!(
+                            return Err(LibmetroError::Malformed(format!(
                                 "Bad branch selection in {:#?} for tag: {:#?}",
                                 state, tag
-                            ))
+                            )))
                         }
                     };
 
-                    HunkParseState::CommitHunk(Hunk { hunk: hunk })
+                    HunkParseState::CommitHunk(Hunk::from(hunk))
                 }
                 HunkParseState::ParseObjClassHunk(tag) => {
+                    check_len(data, 8)?;
                     let name_id = convert_be_u32(&data[0..4].try_into().unwrap());
                     let num_methods = convert_be_u16(&data[4..6].try_into().unwrap());
                     let num_pairs = convert_be_u16(&data[6..8].try_into().unwrap());
 
                     data = &data[8..];
+                    check_pair_count(num_pairs, data.len(), "Class")?;
 
                     // process pairs
                     let mut pairs: Vec<ObjClassPair> = vec![];
@@ -1120,29 +2340,845 @@ impl TryFrom<&[u8]> for CodeHunks {
                         }
 
                         _ => {
-                            return Err(format!(
+                            return Err(LibmetroError::Malformed(format!(
                                 "Bad branch selection in {:#?} for tag: {:#?}",
                                 state, tag
-                            ))
+                            )))
                         }
                     };
 
-                    HunkParseState::CommitHunk(Hunk { hunk: hunk })
+                    HunkParseState::CommitHunk(Hunk::from(hunk))
                 }
 
-                HunkParseState::CommitHunk(hunk) => {
+                HunkParseState::CommitHunk(mut hunk) => {
+                    hunk.span = *hunk_start..value.len() - data.len();
+
+                    if !hunk.hunk.is_valid_for(proc) {
+                        return Err(LibmetroError::Malformed(format!(
+                            "{} hunk is not valid for processor {:?}",
+                            hunk.hunk.kind(),
+                            proc
+                        )));
+                    }
+                    if options.reject_reserved_hunks && hunk.hunk.is_reserved_for(proc) {
+                        return Err(LibmetroError::Malformed(format!(
+                            "{} hunk is reserved",
+                            hunk.hunk.kind()
+                        )));
+                    }
+
+                    let is_end = matches!(hunk.hunk, HunkType::End(_));
                     hunks.push(hunk);
 
-                    if data.len() == 0 {
-                        HunkParseState::End
-                    } else {
-                        HunkParseState::ParseTag
+                    // A well-formed stream stops at its End hunk, running out
+                    // of data anywhere else means the object was cut short
+                    match (is_end, data.is_empty()) {
+                        (true, true) => HunkParseState::End,
+                        // Anything after the End hunk is dropped
+                        (true, false) if !options.require_end_hunk => HunkParseState::End,
+                        (true, false) => {
+                            return Err(LibmetroError::Malformed(format!(
+                                "{} bytes of trailing data after End hunk",
+                                data.len()
+                            )))
+                        }
+                        (false, true) if !options.require_end_hunk => HunkParseState::End,
+                        (false, true) => {
+                            return Err(LibmetroError::Malformed(format!(
+                                "Hunk stream ended after {} hunks without an End hunk",
+                                hunks.len()
+                            )))
+                        }
+                        (false, false) => HunkParseState::ParseTag,
                     }
                 }
-                _ => return Err(format!("Bad branch encountered: {:#?}", state)),
+                _ => {
+                    return Err(LibmetroError::Malformed(format!(
+                        "Bad branch encountered: {:#?}",
+                        state
+                    )))
+                }
             }
         }
 
         Ok(CodeHunks { hunks: hunks })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects_m68k::{MetrowerksObjectBuilder, NameEntry};
+    use crate::test_util::{load_hunk_bytes, load_library};
+
+    #[test]
+    fn test_structural_summary() {
+        let lut = load_library("test/data/set_volume_ex.lib.metro");
+        let summary = lut[0].object().hunks().structural_summary();
+
+        assert_eq!(5, summary.count());
+        assert!(summary.is_well_formed());
+        assert_eq!(Some(&1), summary.kinds().get("GlobalCode"));
+        assert_eq!(Some(&1), summary.kinds().get("XRef32Bit"));
+        assert_eq!(Some(&1), summary.kinds().get("ExceptionInfo"));
+        assert_eq!(None, summary.kinds().get("LocalCode"));
+        assert!(summary.unknown().is_empty());
+
+        // Reserved hunks are reported by the tag they were read with
+        let mut bytes: Vec<u8> = vec![];
+        for tag in [
+            RawHunkType::HUNK_START,
+            RawHunkType::HUNK_LIBRARY_BREAK,
+            RawHunkType::HUNK_DIFF_16BIT,
+            RawHunkType::HUNK_LIBRARY_BREAK,
+        ] {
+            bytes.extend((tag as u16).to_be_bytes());
+        }
+        let options = ParseOptions {
+            require_end_hunk: false,
+            ..ParseOptions::default()
+        };
+        let summary = CodeHunks::try_from_with_options(&bytes, LibraryProcessor::Unknown, &options)
+            .unwrap()
+            .structural_summary();
+        assert_eq!(4, summary.count());
+        assert_eq!(
+            &[
+                RawHunkType::HUNK_LIBRARY_BREAK as u16,
+                RawHunkType::HUNK_DIFF_16BIT as u16,
+                RawHunkType::HUNK_LIBRARY_BREAK as u16
+            ],
+            summary.unknown()
+        );
+        assert_eq!(Some(&2), summary.kinds().get("LibraryBreak"));
+        assert!(summary.has_start());
+        assert!(!summary.has_end());
+        assert!(!summary.is_well_formed());
+    }
+
+    #[test]
+    fn test_trailing_data() {
+        let mut bytes = load_hunk_bytes("test/data/add.lib.metro");
+        let hunks = CodeHunks::try_from((bytes.as_slice(), LibraryProcessor::M68k)).unwrap();
+        bytes.extend([0xde, 0xad]);
+
+        let err = CodeHunks::try_from((bytes.as_slice(), LibraryProcessor::M68k)).unwrap_err();
+        assert!(
+            err.to_string().contains("2 bytes of trailing data"),
+            "{}",
+            err
+        );
+
+        let lenient = CodeHunks::try_from_with_options(
+            &bytes,
+            LibraryProcessor::M68k,
+            &ParseOptions::lenient(),
+        )
+        .unwrap();
+        assert_eq!(hunks, lenient);
+    }
+
+    #[test]
+    fn test_hunks_by_category() {
+        let lut = load_library("test/data/two_funcs.lib.metro");
+        let hunks = lut[0].object().hunks();
+
+        // Both functions are global code, with nothing else between start and end
+        assert_eq!(
+            vec![10, 24],
+            hunks.code_hunks().map(|x| x.len()).collect::<Vec<_>>()
+        );
+        assert_eq!(0, hunks.data_hunks().count());
+        assert_eq!(0, hunks.xref_hunks().count());
+        assert_eq!(0, hunks.entry_hunks().count());
+
+        let lut = load_library("test/data/set_volume_ex.lib.metro");
+        let hunks = lut[0].object().hunks();
+        assert_eq!(1, hunks.code_hunks().count());
+        assert_eq!(1, hunks.xref_hunks().count());
+    }
+
+    #[test]
+    fn test_xref_relocations() {
+        let lut = load_library("test/data/set_volume_ex.lib.metro");
+        let obj = lut[0].object();
+        let xref = obj.hunks().xref_hunks().next().unwrap();
+
+        // pea qd in set_volume, patched at offset 6 of its code
+        let relocs: Vec<(u32, XRefTarget)> = xref.relocations(obj).collect();
+        assert_eq!(1, relocs.len());
+        match relocs[0] {
+            (6, XRefTarget::Symbol(name, 0xca)) => assert_eq!("qd", name.name()),
+            x => panic!("Expected a reference to qd, got: {:?}", x),
+        }
+
+        let unnamed = ObjXRefHunk {
+            name_id: 42,
+            pairs: xref.pairs.clone(),
+        };
+        assert_eq!(
+            vec![(6, XRefTarget::Raw(0xca))],
+            unnamed.relocations(obj).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let lut = load_library("test/data/two_funcs.lib.metro");
+        let hunks = lut[0].object().hunks().clone();
+
+        let kinds: Vec<&str> = hunks.into_iter().map(|h| h.hunk.kind()).collect();
+        assert_eq!(vec!["Start", "GlobalCode", "GlobalCode", "End"], kinds);
+    }
+
+    #[test]
+    fn test_section_lengths() {
+        let lut = load_library("test/data/two_funcs.lib.metro");
+        let code = lut[0].object().hunks()[1].clone();
+        assert!(matches!(code.hunk_type(), HunkType::GlobalCode(_)));
+
+        let idata = ObjDataHunk::new_initialized(1, 0, 0, &[1, 2]);
+        let udata = ObjDataHunk::new_uninitialized(1, 0, 0, 0x10);
+        let hunks = CodeHunks::from(vec![
+            code,
+            Hunk::from(HunkType::InitCode(ObjInitHunk::new(&[0x4e, 0x75]))),
+            Hunk::from(HunkType::LocalInitializedData(idata.clone())),
+            Hunk::from(HunkType::GlobalInitializedData(idata.clone())),
+            Hunk::from(HunkType::LocalFarInitializedData(idata.clone())),
+            Hunk::from(HunkType::GlobalFarInitializedData(idata)),
+            Hunk::from(HunkType::LocalUninitializedData(udata.clone())),
+            Hunk::from(HunkType::GlobalUninitializedData(udata.clone())),
+            Hunk::from(HunkType::LocalFarUninitializedData(udata.clone())),
+            Hunk::from(HunkType::GlobalFarUninitializedData(udata)),
+        ]);
+
+        assert_eq!(10, hunks.code_length());
+        assert_eq!(4 * 2, hunks.idata_length());
+        assert_eq!(4 * 0x10, hunks.udata_length());
+        assert!(hunks.validate_sizes(10, 0x40, 8).is_ok());
+    }
+
+    #[test]
+    fn test_entries() {
+        let lut = load_library("test/data/set_volume_ex.lib.metro");
+        let hunks = lut[0].object().hunks();
+
+        assert_eq!(
+            hunks.iter().collect::<Vec<_>>(),
+            hunks.entries().iter().collect::<Vec<_>>()
+        );
+        assert_eq!(hunks.iter().count(), hunks.len());
+        assert_eq!(Some(&hunks[1]), hunks.get(1));
+        assert!(hunks.get(hunks.len()).is_none());
+
+        let xref = hunks.xref_hunks().next().unwrap();
+        assert_eq!(
+            xref.iter().collect::<Vec<_>>(),
+            xref.entries().iter().collect::<Vec<_>>()
+        );
+        assert_eq!((1, false), (xref.len(), xref.is_empty()));
+        assert_eq!(xref.iter().next(), xref.get(0));
+    }
+
+    #[test]
+    fn test_disassemble_with() {
+        // Reports each 16-bit word it's given as one instruction
+        struct Words;
+
+        impl CodeDecoder for Words {
+            fn decode(&self, code: &[u8], base: u32) -> Vec<DecodedInsn> {
+                code.chunks(2)
+                    .enumerate()
+                    .map(|(i, w)| DecodedInsn {
+                        addr: base + i as u32 * 2,
+                        len: w.len(),
+                        text: format!("dc.w ${:02x}{:02x}", w[0], w[1]),
+                    })
+                    .collect()
+            }
+        }
+
+        let lut = load_library("test/data/two_funcs.lib.metro");
+        let hunks = lut[0].object().hunks();
+        let code = hunks.code_hunks().nth(1).unwrap();
+
+        let insns = code.disassemble_with(&Words);
+        assert_eq!(12, insns.len());
+        assert_eq!(24, insns.iter().map(|x| x.len).sum::<usize>());
+        assert_eq!(0, insns[0].addr);
+        assert_eq!(22, insns[11].addr);
+        assert_eq!(
+            format!("dc.w ${:02x}{:02x}", code[0], code[1]),
+            insns[0].text
+        );
+    }
+
+    #[test]
+    fn test_code_hunk_routine() {
+        let lut = load_library("test/data/add.lib.metro");
+        let obj = lut[0].object();
+        let code = obj.hunks().code_hunks().next().unwrap();
+
+        let routine = code.routine(obj).unwrap();
+        assert!(routine.is_function());
+        assert_eq!(obj.symbols().unwrap().routines()[0], *routine);
+
+        let mut other = code.clone();
+        other.sym_offset = 0x80000000;
+        assert!(!other.has_symtab());
+        assert!(other.routine(obj).is_none());
+
+        // Inside the routine rather than at its start
+        other.sym_offset = code.sym_offset + 2;
+        assert!(other.routine(obj).is_none());
+    }
+
+    #[test]
+    fn test_class_bases() {
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend(HunkType::Start(ObjSimpleHunk {}).tag().to_be_bytes());
+        bytes.extend((RawHunkType::HUNK_METHOD_CLASS_DEF as u16).to_be_bytes());
+        bytes.extend(1u32.to_be_bytes());
+        bytes.extend(3u16.to_be_bytes());
+        bytes.extend(3u16.to_be_bytes());
+        for (base, bias) in [(2u32, 0u32), (3, 8), (9, 12)] {
+            bytes.extend(base.to_be_bytes());
+            bytes.extend(bias.to_be_bytes());
+        }
+        bytes.extend(HunkType::End(ObjSimpleHunk {}).tag().to_be_bytes());
+
+        let obj = MetrowerksObjectBuilder::new()
+            .next_name("Derived")
+            .next_name("Base")
+            .next_name("Mixin")
+            .hunks(CodeHunks::try_from(bytes.as_slice()).unwrap())
+            .build()
+            .unwrap();
+
+        let HunkType::MethodClassDefinition(class) = obj.hunks()[1].hunk_type() else {
+            panic!("{:?}", obj.hunks()[1]);
+        };
+        assert_eq!(3, class.methods());
+
+        // Id 9 isn't in the name table
+        let bases: Vec<_> = class
+            .bases(&obj)
+            .map(|(n, bias)| (n.name().as_str(), bias))
+            .collect();
+        assert_eq!(vec![("Base", 0), ("Mixin", 8)], bases);
+    }
+
+    #[test]
+    fn test_iter_code() {
+        let lut = load_library("test/data/add.lib.metro");
+        let obj = lut[0].object();
+
+        let code: Vec<_> = obj.hunks().iter_code().collect();
+        assert_eq!(1, code.len());
+        assert_eq!("add", obj.name_by_id(code[0].0).unwrap().name());
+        assert_eq!(10, code[0].1.len());
+        assert_eq!(code[0].1, obj.hunks().concatenated_code());
+
+        let lut = load_library("test/data/two_funcs.lib.metro");
+        let hunks = lut[0].object().hunks();
+        let joined = hunks.concatenated_code();
+        assert_eq!(34, joined.len());
+        assert_eq!(hunks.iter_code().nth(1).unwrap().1, &joined[10..]);
+    }
+
+    #[test]
+    fn test_validate_sizes() {
+        let lut = load_library("test/data/two_funcs.lib.metro");
+        let obj = lut[0].object();
+        let header = obj.header();
+
+        assert!(obj
+            .hunks()
+            .validate_sizes(
+                header.code_size() as usize,
+                header.udata_size() as usize,
+                header.idata_size() as usize
+            )
+            .is_ok());
+
+        match obj.hunks().validate_sizes(30, 0, 0) {
+            Err(LibmetroError::SizeMismatch {
+                section,
+                expected,
+                computed,
+            }) => assert_eq!(("code", 30, 34), (section, expected, computed)),
+            x => panic!("Expected a size mismatch, got: {:?}", x),
+        }
+
+        let err = obj.hunks().validate_sizes(34, 0, 8).unwrap_err();
+        assert!(err.to_string().contains("initialized data"), "{}", err);
+    }
+
+    #[test]
+    fn test_aligned_pads_code() {
+        let lut = load_library("test/data/two_funcs.lib.metro");
+        let hunks = lut[0].object().hunks();
+
+        let sizes = |h: &CodeHunks| -> Vec<usize> {
+            h.iter()
+                .filter_map(|x| match x.hunk_type() {
+                    HunkType::GlobalCode(c) => Some(c.len()),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        assert_eq!(vec![10, 24], sizes(hunks));
+        assert_eq!(vec![10, 24], sizes(&hunks.aligned(2)));
+        assert_eq!(vec![12, 24], sizes(&hunks.aligned(4)));
+        assert_eq!(vec![16, 32], sizes(&hunks.aligned(16)));
+    }
+
+    #[test]
+    fn test_pointer_hunk_names() {
+        let lut = load_library("test/data/add.lib.metro");
+        let mut obj = lut[0].object().clone();
+        obj.push_name(NameEntry::new(4, "__ptr_add")).unwrap();
+        obj.push_name(NameEntry::new(5, "__xv_add")).unwrap();
+
+        let dp = DataPointerHunk {
+            name_id: 4,
+            data_name: 1,
+        };
+        assert_eq!(("__ptr_add", "add"), (dp.name(&obj), dp.data_name(&obj)));
+
+        let xp = XPointerHunk {
+            name_id: 4,
+            xvector_name: 5,
+        };
+        assert_eq!("__xv_add", xp.xvector_name(&obj));
+
+        let xv = XVectorHunk {
+            name_id: 5,
+            function_name: 1,
+        };
+        assert_eq!("add", xv.function_name(&obj));
+
+        // Unresolvable ids come back empty rather than panicking
+        let xv = XVectorHunk {
+            name_id: 5,
+            function_name: 42,
+        };
+        assert_eq!(42, xv.function_name_id());
+        assert_eq!("", xv.function_name(&obj));
+    }
+
+    #[test]
+    fn test_resolve_pointer_hunks() {
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend(HunkType::Start(ObjSimpleHunk {}).tag().to_be_bytes());
+        bytes.extend((RawHunkType::HUNK_GLOBAL_CODE as u16).to_be_bytes());
+        for x in [1u32, 2, 0x80000000, 0] {
+            bytes.extend(x.to_be_bytes());
+        }
+        bytes.extend([0x4e, 0x75]);
+        for (tag, name, target) in [
+            (RawHunkType::HUNK_GLOBAL_XVECTOR, 2u32, 1u32),
+            (RawHunkType::HUNK_GLOBAL_XPOINTER, 3, 2),
+            (RawHunkType::HUNK_GLOBAL_DATAPOINTER, 4, 9),
+        ] {
+            bytes.extend((tag as u16).to_be_bytes());
+            bytes.extend(name.to_be_bytes());
+            bytes.extend(target.to_be_bytes());
+        }
+        bytes.extend(HunkType::End(ObjSimpleHunk {}).tag().to_be_bytes());
+
+        let obj = MetrowerksObjectBuilder::new()
+            .next_name("add")
+            .next_name("__xv_add")
+            .next_name("__xp_add")
+            .next_name("__dp_gData")
+            .hunks(CodeHunks::try_from(bytes.as_slice()).unwrap())
+            .build()
+            .unwrap();
+        fn name(n: Option<&NameEntry>) -> Option<&str> {
+            n.map(|x| x.name().as_str())
+        }
+
+        let HunkType::GlobalXVector(xv) = obj.hunks()[2].hunk_type() else {
+            panic!("{:?}", obj.hunks()[2]);
+        };
+        let (vector, function) = xv.resolve(&obj);
+        assert_eq!(
+            (Some("__xv_add"), Some("add")),
+            (name(vector), name(function))
+        );
+
+        let HunkType::GlobalXPointer(xp) = obj.hunks()[3].hunk_type() else {
+            panic!("{:?}", obj.hunks()[3]);
+        };
+        let (pointer, vector) = xp.resolve(&obj);
+        assert_eq!(
+            (Some("__xp_add"), Some("__xv_add")),
+            (name(pointer), name(vector))
+        );
+
+        // Id 9 isn't in the name table
+        let HunkType::GlobalDataPointer(dp) = obj.hunks()[4].hunk_type() else {
+            panic!("{:?}", obj.hunks()[4]);
+        };
+        let (pointer, data) = dp.resolve(&obj);
+        assert_eq!((Some("__dp_gData"), None), (name(pointer), name(data)));
+    }
+
+    #[test]
+    fn test_processor_specific_hunks() {
+        let segment = HunkType::Segment(ObjSegHunk { name_id: 1 });
+        assert!(segment.is_valid_for(LibraryProcessor::M68k));
+        assert!(!segment.is_valid_for(LibraryProcessor::PowerPC));
+
+        let force_active = HunkType::ForceActive(ObjForceActiveHunk::new(1));
+        assert!(!force_active.is_valid_for(LibraryProcessor::M68k));
+        assert!(force_active.is_valid_for(LibraryProcessor::PowerPC));
+
+        let lut = load_library("test/data/set_volume_ex.lib.metro");
+        assert!(lut[0].object().validate_for(LibraryProcessor::M68k).is_ok());
+
+        let stream = |tag: RawHunkType| -> Vec<u8> {
+            let mut bytes: Vec<u8> = vec![];
+            bytes.extend((RawHunkType::HUNK_START as u16).to_be_bytes());
+            bytes.extend((tag as u16).to_be_bytes());
+            if tag == RawHunkType::HUNK_SEGMENT || tag == RawHunkType::HUNK_FORCE_ACTIVE {
+                bytes.extend(1u32.to_be_bytes());
+            }
+            bytes.extend((RawHunkType::HUNK_END as u16).to_be_bytes());
+            bytes
+        };
+
+        let segment = stream(RawHunkType::HUNK_SEGMENT);
+        assert!(CodeHunks::try_from((segment.as_slice(), LibraryProcessor::M68k)).is_ok());
+        let err = CodeHunks::try_from((segment.as_slice(), LibraryProcessor::PowerPC)).unwrap_err();
+        assert!(err.to_string().contains("Segment hunk"), "{}", err);
+
+        let force_active = stream(RawHunkType::HUNK_FORCE_ACTIVE);
+        assert!(CodeHunks::try_from((force_active.as_slice(), LibraryProcessor::PowerPC)).is_ok());
+        assert!(CodeHunks::try_from((force_active.as_slice(), LibraryProcessor::M68k)).is_err());
+        // Without a processor either is accepted
+        assert!(CodeHunks::try_from(segment.as_slice()).is_ok());
+        assert!(CodeHunks::try_from(force_active.as_slice()).is_ok());
+
+        // ForceActive has a layout on PowerPC, and writes back out as read
+        let options = ParseOptions {
+            reject_reserved_hunks: true,
+            ..ParseOptions::default()
+        };
+        let hunks = CodeHunks::try_from_with_options(
+            force_active.as_slice(),
+            LibraryProcessor::PowerPC,
+            &options,
+        )
+        .unwrap();
+        assert!(matches!(&hunks[1].hunk, HunkType::ForceActive(f) if f.name_id() == 1));
+        assert!(!hunks.has_reserved_for(LibraryProcessor::PowerPC));
+        assert!(hunks.structural_summary().unknown().is_empty());
+        let mut out: Vec<u8> = vec![];
+        hunks.serialize_out(&mut out).unwrap();
+        assert_eq!(force_active, out);
+        assert!(hunks.has_reserved_for(LibraryProcessor::M68k));
+    }
+
+    #[test]
+    fn test_data_hunk_round_trip() {
+        let idata = ObjDataHunk::new_initialized(1, 0x20, 0x80, &[0xde, 0xad, 0xbe, 0xef]);
+        let udata = ObjDataHunk::new_uninitialized(2, 0x40, 0x90, 128);
+
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend((RawHunkType::HUNK_START as u16).to_be_bytes());
+        bytes.extend((RawHunkType::HUNK_GLOBAL_IDATA as u16).to_be_bytes());
+        idata.serialize_out(&mut bytes).unwrap();
+        bytes.extend((RawHunkType::HUNK_LOCAL_UDATA as u16).to_be_bytes());
+        udata.serialize_out(&mut bytes).unwrap();
+        bytes.extend((RawHunkType::HUNK_END as u16).to_be_bytes());
+
+        // The uninitialized hunk writes its size but no bytes
+        assert_eq!(2 + 2 + 16 + 4 + 2 + 16 + 2, bytes.len());
+
+        let hunks = CodeHunks::try_from(bytes.as_slice()).unwrap();
+        match hunks[1].hunk_type() {
+            HunkType::GlobalInitializedData(d) => {
+                assert_eq!(
+                    (1, 4, 0x20, 0x80),
+                    (d.name_id(), d.size(), d.sym_offset(), d.sym_decl_offset())
+                );
+                assert_eq!(&[0xde, 0xad, 0xbe, 0xef], d.as_slice());
+            }
+            x => panic!("Expected initialized data, got: {:?}", x),
+        }
+        match hunks[2].hunk_type() {
+            HunkType::LocalUninitializedData(d) => {
+                assert_eq!((2, 128), (d.name_id(), d.size()));
+                assert!(d.is_empty());
+            }
+            x => panic!("Expected uninitialized data, got: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_init_hunk_round_trip() {
+        let init = ObjInitHunk::new(&[0x4e, 0x75]);
+        assert_eq!(&[0x4e, 0x75], init.code());
+        assert_eq!(6, init.raw_length());
+
+        let hunk = Hunk::from(HunkType::InitCode(init.clone()));
+        assert_eq!(2 + 6, hunk.raw_length());
+
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend((RawHunkType::HUNK_START as u16).to_be_bytes());
+        hunk.serialize_out(&mut bytes).unwrap();
+        bytes.extend((RawHunkType::HUNK_END as u16).to_be_bytes());
+
+        let hunks = CodeHunks::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(&HunkType::InitCode(init), hunks[1].hunk_type());
+    }
+
+    #[test]
+    fn test_hunk_type_tags() {
+        let data = ObjDataHunk::new_initialized(1, 0, 0, &[0]);
+        for (raw, hunk) in [
+            (RawHunkType::HUNK_START, HunkType::Start(ObjSimpleHunk {})),
+            (RawHunkType::HUNK_END, HunkType::End(ObjSimpleHunk {})),
+            (
+                RawHunkType::HUNK_GLOBAL_IDATA,
+                HunkType::GlobalInitializedData(data.clone()),
+            ),
+            (
+                RawHunkType::HUNK_LOCAL_FARIDATA,
+                HunkType::LocalFarInitializedData(data),
+            ),
+            (
+                RawHunkType::HUNK_INIT_CODE,
+                HunkType::InitCode(ObjInitHunk::new(&[])),
+            ),
+            (
+                RawHunkType::HUNK_CFM_EXPORT,
+                HunkType::CFMExport(ObjSimpleHunk {}),
+            ),
+            (
+                RawHunkType::HUNK_FORCE_ACTIVE,
+                HunkType::ForceActive(ObjForceActiveHunk::new(1)),
+            ),
+        ] {
+            assert_eq!(raw as u16, hunk.tag(), "{}", hunk.kind());
+        }
+        assert_eq!(0, HunkType::Undefined.tag());
+
+        // Every parsed hunk reports the tag it was read from
+        let lut = load_library("test/data/set_volume_ex.lib.metro");
+        let hunks = lut[0].object().hunks();
+        let mut ve: Vec<u8> = vec![];
+        hunks.serialize_out(&mut ve).unwrap();
+        for h in hunks.iter() {
+            let at = h.span().start;
+            assert_eq!(convert_be_u16(&ve[at..at + 2].try_into().unwrap()), h.tag());
+        }
+        assert!(hunks
+            .iter()
+            .any(|h| h.tag() == RawHunkType::HUNK_GLOBAL_CODE as u16));
+    }
+
+    #[test]
+    fn test_global_udata_size() {
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend((RawHunkType::HUNK_START as u16).to_be_bytes());
+        bytes.extend((RawHunkType::HUNK_GLOBAL_UDATA as u16).to_be_bytes());
+        bytes.extend(
+            [3u32, 0x400, 0x80000000, 0]
+                .iter()
+                .flat_map(|x| x.to_be_bytes()),
+        );
+        bytes.extend((RawHunkType::HUNK_END as u16).to_be_bytes());
+
+        let hunks = CodeHunks::try_from(bytes.as_slice()).unwrap();
+        match hunks[1].hunk_type() {
+            HunkType::GlobalUninitializedData(d) => {
+                assert_eq!((3, 0x400), (d.name_id(), d.size()));
+                assert!(d.is_empty());
+            }
+            x => panic!("Expected uninitialized data, got: {:?}", x),
+        }
+        assert_eq!(0x400, hunks.udata_length());
+    }
+
+    #[test]
+    fn test_pair_count_exceeds_data() {
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend((RawHunkType::HUNK_START as u16).to_be_bytes());
+        bytes.extend((RawHunkType::HUNK_XREF_32BIT as u16).to_be_bytes());
+        bytes.extend(1u32.to_be_bytes());
+        bytes.extend(0xffffu16.to_be_bytes());
+        bytes.extend([0; 8]);
+
+        let err = CodeHunks::try_from(bytes.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("65535 pairs"));
+
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend((RawHunkType::HUNK_START as u16).to_be_bytes());
+        bytes.extend((RawHunkType::HUNK_METHOD_CLASS_DEF as u16).to_be_bytes());
+        bytes.extend(1u32.to_be_bytes());
+        bytes.extend(0u16.to_be_bytes());
+        bytes.extend(2u16.to_be_bytes());
+        bytes.extend([0; 8]);
+
+        assert!(CodeHunks::try_from(bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_missing_end_hunk() {
+        let code = &load_hunk_bytes("test/data/two_funcs.lib.metro")[..];
+        assert!(CodeHunks::try_from(code).is_ok());
+
+        // Drop the End hunk, the stream now stops cleanly on a hunk boundary
+        let err = CodeHunks::try_from(&code[..code.len() - 2]).unwrap_err();
+        assert!(err.to_string().contains("without an End hunk"));
+    }
+
+    #[test]
+    fn test_truncated_hunks() {
+        let code = &load_hunk_bytes("test/data/two_funcs.lib.metro")[..];
+
+        // Every cut is an error, those mid-hunk report what was missing
+        for len in 0..code.len() {
+            match CodeHunks::try_from(&code[..len])
+                .as_ref()
+                .map_err(|e| e.root_cause())
+            {
+                Err(LibmetroError::Truncated { needed, available }) => {
+                    assert!(needed > available, "{} > {}", needed, available)
+                }
+                Err(_) => (),
+                Ok(_) => panic!("Parsed {} of {} bytes", len, code.len()),
+            }
+        }
+
+        // A code hunk declaring more bytes than are left
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend((RawHunkType::HUNK_GLOBAL_CODE as u16).to_be_bytes());
+        bytes.extend([1u32, 0x100, 0, 0].iter().flat_map(|x| x.to_be_bytes()));
+        bytes.extend([0x4e, 0x75]);
+        match CodeHunks::try_from(bytes.as_slice()).map_err(|e| (e.offset(), e)) {
+            Err((Some(0), LibmetroError::AtOffset { error, .. })) => match *error {
+                LibmetroError::Truncated { needed, available } => {
+                    assert_eq!((0x100, 2), (needed, available))
+                }
+                x => panic!("Expected truncated data, got: {:?}", x),
+            },
+            x => panic!("Expected truncated data at 0, got: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_error_offsets() {
+        let code = &load_hunk_bytes("test/data/two_funcs.lib.metro")[..];
+        let hunks = CodeHunks::try_from(code).unwrap();
+
+        // Cutting a hunk short reports where that hunk began
+        let span = hunks
+            .iter()
+            .filter(|h| matches!(h.hunk, HunkType::GlobalCode(_)))
+            .nth(1)
+            .unwrap()
+            .span();
+        let err = CodeHunks::try_from(&code[..span.end - 1]).unwrap_err();
+        assert_eq!(Some(span.start), err.offset());
+        assert!(err
+            .to_string()
+            .starts_with(&format!("At {:#x}: ", span.start)));
+    }
+
+    #[test]
+    fn test_parse_errors_match() {
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend((RawHunkType::HUNK_START as u16).to_be_bytes());
+        bytes.extend(0x4f00u16.to_be_bytes());
+        let err = CodeHunks::try_from(bytes.as_slice()).unwrap_err();
+        match err.root_cause() {
+            LibmetroError::BadHunkTag(0x4f00) => (),
+            x => panic!("Expected a bad hunk tag, got: {:?}", x),
+        }
+        assert_eq!(Some(2), err.offset());
+    }
+
+    #[test]
+    fn test_reserved_hunks_parse() {
+        let mut bytes: Vec<u8> = vec![];
+        for tag in [
+            RawHunkType::HUNK_START,
+            RawHunkType::HUNK_LIBRARY_BREAK,
+            RawHunkType::HUNK_END,
+        ] {
+            bytes.extend((tag as u16).to_be_bytes());
+        }
+
+        let hunks = CodeHunks::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(3, hunks.len());
+        assert!(matches!(hunks[1].hunk, HunkType::LibraryBreak(_)));
+        assert!(hunks.has_reserved());
+
+        let lut = load_library("test/data/two_funcs.lib.metro");
+        assert!(!lut[0].object().hunks().has_reserved());
+    }
+
+    #[test]
+    fn test_hunks_round_trip() {
+        for path in [
+            "test/data/add.lib.metro",
+            "test/data/two_funcs.lib.metro",
+            "test/data/set_volume_ex.lib.metro",
+        ] {
+            let lut = load_library(path);
+            let obj = lut[0].object();
+            let code = &load_hunk_bytes(path)[..];
+
+            let mut out: Vec<u8> = vec![];
+            obj.hunks().serialize_out(&mut out).unwrap();
+            assert_eq!(code, out.as_slice(), "{}", path);
+            assert_eq!(out.len(), obj.hunks().raw_length());
+            assert_eq!(obj.hunks(), &CodeHunks::try_from(out.as_slice()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_hunk_spans() {
+        for path in [
+            "test/data/add.lib.metro",
+            "test/data/two_funcs.lib.metro",
+            "test/data/set_volume_ex.lib.metro",
+        ] {
+            let code = &load_hunk_bytes(path)[..];
+
+            let hunks = CodeHunks::try_from(code).unwrap();
+            let mut next = 0;
+            for h in hunks.iter() {
+                assert_eq!(next, h.span().start, "{}", path);
+                assert_eq!(h.raw_length(), h.span().len(), "{}", path);
+                next = h.span().end;
+            }
+            assert_eq!(code.len(), next, "{}", path);
+        }
+
+        let built = Hunk::from(HunkType::End(ObjSimpleHunk {}));
+        assert!(built.span().is_empty());
+    }
+
+    #[test]
+    fn test_serialize_rejects_bad_hunks() {
+        let mut out: Vec<u8> = vec![];
+
+        let udata = Hunk::from(HunkType::LocalUninitializedData(
+            ObjDataHunk::new_initialized(1, 0, 0, &[1, 2]),
+        ));
+        assert!(udata.serialize_out(&mut out).is_err());
+
+        let reserved = Hunk::from(HunkType::Diff8Bit(ReservedHunk {}));
+        assert!(matches!(
+            reserved.serialize_out(&mut out),
+            Err(LibmetroError::ReservedHunk(tag)) if tag == RawHunkType::HUNK_DIFF_8BIT as u16
+        ));
+        assert!(out.is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+use chrono::{DateTime, Utc};
+use std::fmt::Display;
+
+use crate::util;
+
+/// Everything that can go wrong reading, building, or writing a library.
+#[derive(Debug)]
+pub enum LibmetroError {
+    /// A magic word at the start of a structure didn't match.
+    BadMagic {
+        expected: u32,
+        found: u32,
+    },
+    /// The data ended before the structure being read did.
+    Truncated {
+        needed: usize,
+        available: usize,
+    },
+    /// A hunk tag outside the known range.
+    BadHunkTag(u16),
+    /// A hunk the format reserves, which has no known layout and can't be
+    /// written, with its tag.
+    ReservedHunk(u16),
+    BadRoutineType(u16),
+    BadTypeTag(u16),
+    BadStorageKind(u8),
+    BadStorageClass(u8),
+    /// A name table entry whose stored hash isn't the hash of its name.
+    NameHashMismatch {
+        id: u32,
+        stored: u16,
+        computed: u16,
+    },
+    /// A section size recorded for an object that doesn't match its hunks.
+    SizeMismatch {
+        section: &'static str,
+        expected: usize,
+        computed: usize,
+    },
+    /// A string ran to the end of the bytes it could take up without a NUL.
+    NoNulTerminator,
+    /// A date outside what a classic MacOS timestamp can hold.
+    DateOutOfRange(DateTime<Utc>),
+    /// Anything else wrong with the data being parsed.
+    Malformed(String),
+    /// Parts that don't fit together, or can't be written.
+    Invalid(String),
+    /// An error at a byte offset from the start of the code, symbol table, or
+    /// type table being parsed: the start of the hunk, routine, or type that
+    /// couldn't be read.
+    AtOffset {
+        offset: usize,
+        error: Box<LibmetroError>,
+    },
+    /// An error from one file of a library.
+    InFile {
+        file: String,
+        error: Box<LibmetroError>,
+    },
+    Io(std::io::Error),
+}
+
+impl LibmetroError {
+    /// Ties the error to the library file it came from.
+    pub(crate) fn in_file(self, file: &str) -> LibmetroError {
+        LibmetroError::InFile {
+            file: file.to_owned(),
+            error: Box::new(self),
+        }
+    }
+
+    /// Ties the error to `offset` in the data being parsed. An error already
+    /// tied to an offset within that data is moved along by `offset`.
+    pub(crate) fn at_offset(self, offset: usize) -> LibmetroError {
+        match self {
+            LibmetroError::AtOffset {
+                offset: inner,
+                error,
+            } => LibmetroError::AtOffset {
+                offset: offset + inner,
+                error,
+            },
+            e => LibmetroError::AtOffset {
+                offset,
+                error: Box::new(e),
+            },
+        }
+    }
+
+    /// The offset the error was found at, see `AtOffset`.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            LibmetroError::AtOffset { offset, .. } => Some(*offset),
+            LibmetroError::InFile { error, .. } => error.offset(),
+            _ => None,
+        }
+    }
+
+    /// The error with any `InFile` and `AtOffset` context taken off.
+    pub fn root_cause(&self) -> &LibmetroError {
+        match self {
+            LibmetroError::AtOffset { error, .. } | LibmetroError::InFile { error, .. } => {
+                error.root_cause()
+            }
+            e => e,
+        }
+    }
+}
+
+impl Display for LibmetroError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LibmetroError::BadMagic { expected, found } => {
+                write!(f, "{}", util::magic_mismatch(*expected, *found))
+            }
+            LibmetroError::Truncated { needed, available } => write!(
+                f,
+                "Truncated data: needed {} bytes, have {}",
+                needed, available
+            ),
+            LibmetroError::BadHunkTag(tag) => write!(f, "Bad hunk tag, got: {:#06x}", tag),
+            LibmetroError::ReservedHunk(tag) => {
+                write!(f, "Reserved hunk {:#06x} cannot be serialized", tag)
+            }
+            LibmetroError::BadRoutineType(typ) => write!(f, "Bad Routine Type: got {}", typ),
+            LibmetroError::BadTypeTag(tag) => write!(f, "Bad type tag, got: {}", tag),
+            LibmetroError::BadStorageKind(kind) => write!(f, "Bad Storage Kind, got: {}", kind),
+            LibmetroError::BadStorageClass(class) => {
+                write!(f, "Bad Storage Class, got: {}", class)
+            }
+            LibmetroError::NameHashMismatch {
+                id,
+                stored,
+                computed,
+            } => write!(
+                f,
+                "Hash mismatch for name {}, stored: {:#05x}, computed: {:#05x}",
+                id, stored, computed
+            ),
+            LibmetroError::SizeMismatch {
+                section,
+                expected,
+                computed,
+            } => write!(
+                f,
+                "Size mismatch for {}, expected: {}, computed from hunks: {}",
+                section, expected, computed
+            ),
+            LibmetroError::DateOutOfRange(date) => write!(
+                f,
+                "Date out of range for a MacOS timestamp (1904-01-01 to 2040-02-06), got: {}",
+                date
+            ),
+            LibmetroError::NoNulTerminator => write!(f, "String has no NUL terminator"),
+            LibmetroError::Malformed(msg) => write!(f, "{}", msg),
+            LibmetroError::Invalid(msg) => write!(f, "{}", msg),
+            LibmetroError::AtOffset { offset, error } => write!(f, "At {:#x}: {}", offset, error),
+            LibmetroError::InFile { file, error } => write!(f, "{}: {}", file, error),
+            LibmetroError::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LibmetroError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LibmetroError::AtOffset { error, .. } | LibmetroError::InFile { error, .. } => {
+                Some(error.as_ref())
+            }
+            LibmetroError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for LibmetroError {
+    fn from(value: std::io::Error) -> Self {
+        LibmetroError::Io(value)
+    }
+}
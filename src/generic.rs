@@ -0,0 +1,227 @@
+use crate::code_m68k::HunkType;
+use crate::objects_m68k::MetrowerksObject;
+use crate::util::NameIdFromObject;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionKind {
+    Code,
+    Data,
+    UninitializedData,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolScope {
+    Local,
+    Global,
+}
+
+#[derive(Debug, Clone)]
+pub struct GenericSection {
+    name: String,
+    kind: SectionKind,
+    address: u64,
+    size: u64,
+    data: Vec<u8>,
+}
+
+impl GenericSection {
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn kind(&self) -> SectionKind {
+        self.kind
+    }
+
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Section contents, empty for uninitialized data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GenericSymbol {
+    name: String,
+    address: u64,
+    size: u64,
+    section: usize,
+    scope: SymbolScope,
+}
+
+impl GenericSymbol {
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    /// Size of the symbol, 0 for alternate entry points.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Index into `GenericObject::sections` of the defining section.
+    pub fn section(&self) -> usize {
+        self.section
+    }
+
+    pub fn scope(&self) -> SymbolScope {
+        self.scope
+    }
+}
+
+/// A format-neutral view of an object: its sections and the symbols defined in them.
+///
+/// Every code and data hunk becomes its own section, laid out back-to-back in
+/// file order starting at address 0, so addresses are offsets into that layout.
+#[derive(Debug, Clone)]
+pub struct GenericObject {
+    sections: Vec<GenericSection>,
+    symbols: Vec<GenericSymbol>,
+}
+
+impl GenericObject {
+    pub fn sections(&self) -> &[GenericSection] {
+        &self.sections
+    }
+
+    pub fn symbols(&self) -> &[GenericSymbol] {
+        &self.symbols
+    }
+
+    pub fn symbol_by_name(&self, name: &str) -> Option<&GenericSymbol> {
+        self.symbols.iter().find(|x| x.name == name)
+    }
+}
+
+impl MetrowerksObject {
+    pub fn to_generic(&self) -> GenericObject {
+        let mut sections: Vec<GenericSection> = vec![];
+        let mut symbols: Vec<GenericSymbol> = vec![];
+        let mut address: u64 = 0;
+
+        // Entry hunks are offsets into the code hunk preceding them
+        let mut last_code: Option<usize> = None;
+
+        for h in self.hunks().iter() {
+            let scope = scope_of(h.hunk_type());
+            let (kind, name, bytes, size) = match h.hunk_type() {
+                HunkType::LocalCode(c) | HunkType::GlobalCode(c) => {
+                    (SectionKind::Code, c.name(self), c.to_vec(), c.len())
+                }
+                HunkType::LocalInitializedData(d)
+                | HunkType::GlobalInitializedData(d)
+                | HunkType::LocalFarInitializedData(d)
+                | HunkType::GlobalFarInitializedData(d) => {
+                    (SectionKind::Data, d.name(self), d.to_vec(), d.len())
+                }
+                HunkType::LocalUninitializedData(d)
+                | HunkType::GlobalUninitializedData(d)
+                | HunkType::LocalFarUninitializedData(d)
+                | HunkType::GlobalFarUninitializedData(d) => (
+                    SectionKind::UninitializedData,
+                    d.name(self),
+                    vec![],
+                    d.size() as usize,
+                ),
+                HunkType::LocalEntry(e) | HunkType::GlobalEntry(e) => {
+                    if let Some(idx) = last_code {
+                        symbols.push(GenericSymbol {
+                            name: e.name(self).to_owned(),
+                            address: sections[idx].address + e.offset() as u64,
+                            size: 0,
+                            section: idx,
+                            scope,
+                        });
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+
+            let prefix = match kind {
+                SectionKind::Code => ".text",
+                SectionKind::Data => ".data",
+                SectionKind::UninitializedData => ".bss",
+            };
+
+            if kind == SectionKind::Code {
+                last_code = Some(sections.len());
+            }
+
+            symbols.push(GenericSymbol {
+                name: name.to_owned(),
+                address,
+                size: size as u64,
+                section: sections.len(),
+                scope,
+            });
+
+            sections.push(GenericSection {
+                name: format!("{}.{}", prefix, name),
+                kind,
+                address,
+                size: size as u64,
+                data: bytes,
+            });
+
+            address += size as u64;
+        }
+
+        GenericObject { sections, symbols }
+    }
+}
+
+fn scope_of(hunk: &HunkType) -> SymbolScope {
+    match hunk {
+        HunkType::GlobalCode(_)
+        | HunkType::GlobalEntry(_)
+        | HunkType::GlobalUninitializedData(_)
+        | HunkType::GlobalInitializedData(_)
+        | HunkType::GlobalFarUninitializedData(_)
+        | HunkType::GlobalFarInitializedData(_) => SymbolScope::Global,
+        _ => SymbolScope::Local,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MetroWerksLibrary;
+    use std::fs::File;
+    use std::io::Read;
+
+    #[test]
+    fn test_generic_two_funcs() {
+        let mut lib = File::open("test/data/two_funcs.lib.metro").unwrap();
+        let mut ve: Vec<u8> = vec![];
+        lib.read_to_end(&mut ve).unwrap();
+
+        let lut = MetroWerksLibrary::try_from(ve.as_ref()).unwrap();
+        let generic = lut[0].object().to_generic();
+
+        assert_eq!(2, generic.sections().len());
+        assert_eq!(".text.add", generic.sections()[0].name());
+
+        let add = generic.symbol_by_name("add").unwrap();
+        assert_eq!((0, 10), (add.address(), add.size()));
+        assert_eq!(SymbolScope::Global, add.scope());
+
+        let is_lower = generic.symbol_by_name("is_lower").unwrap();
+        assert_eq!((10, 24), (is_lower.address(), is_lower.size()));
+        assert_eq!(
+            SectionKind::Code,
+            generic.sections()[is_lower.section()].kind()
+        );
+    }
+}
@@ -1,14 +1,24 @@
+#![forbid(unsafe_code)]
+
 #[macro_use]
 extern crate libmetro_proc_macros;
 
+mod c_types;
 pub mod code_m68k;
+pub mod error;
+#[cfg(feature = "generic")]
+pub mod generic;
 pub mod mwob_library;
 pub mod objects_m68k;
+pub mod pef;
 pub mod symtable_m68k;
+#[cfg(test)]
+mod test_util;
 pub mod types_m68k;
 
 pub mod util;
 
+pub use error::LibmetroError;
 pub use mwob_library::*;
 
 pub use code_m68k::*;
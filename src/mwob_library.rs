@@ -1,10 +1,18 @@
+use bitflags::bitflags;
 use chrono::{DateTime, Local};
 
-use crate::objects_m68k::MetrowerksObject;
+use crate::code_m68k::{HunkType, ObjCodeFlag};
+use crate::error::LibmetroError;
+use crate::objects_m68k::{MetrowerksObject, NameEntry};
 
 use super::util;
-use std::ffi::CStr;
+use crate::util::{
+    MacDateTime, NameIdFromObject, ObjectOrder, ParseOptions, Serializable, SerializeOptions,
+};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::ops::Deref;
+use std::path::Path;
 
 #[repr(u32)]
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -13,7 +21,8 @@ pub enum LibraryMagicWord {
 }
 
 #[repr(u32)]
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LibraryProcessor {
     Unknown = 0,
     PowerPC = 0x50504320,
@@ -30,21 +39,53 @@ impl From<u32> for LibraryProcessor {
     }
 }
 
-#[repr(u32)]
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum LibraryFlags {
-    None = 0,
+impl LibraryProcessor {
+    /// The library header version written for this processor, where it is known.
+    pub fn library_version(&self) -> Option<u32> {
+        match self {
+            LibraryProcessor::M68k => Some(2),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+bitflags! {
+    /// Flags from the library header. No bits are known to be used, but any
+    /// that are set are kept as read and written back out.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct LibraryFlags: u32 {
+        const _ = !0;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FileObject {
-    moddate: DateTime<Local>,
+    moddate: MacDateTime,
     file_name: String,
     full_path: String,
     obj: MetrowerksObject,
+    // Position in the file header table this was read from
+    source_index: Option<usize>,
 }
 
 impl FileObject {
+    pub fn new(
+        moddate: DateTime<Local>,
+        file_name: &str,
+        full_path: &str,
+        obj: MetrowerksObject,
+    ) -> FileObject {
+        FileObject {
+            moddate: MacDateTime::from_datetime(moddate),
+            file_name: file_name.to_owned(),
+            full_path: full_path.to_owned(),
+            obj,
+            source_index: None,
+        }
+    }
+
     pub fn object(&self) -> &MetrowerksObject {
         &self.obj
     }
@@ -58,18 +99,204 @@ impl FileObject {
     }
 
     pub fn moddate(&self) -> DateTime<Local> {
+        self.moddate.to_datetime().into()
+    }
+
+    /// The modification date as stored, see `MacDateTime`.
+    pub fn mac_moddate(&self) -> MacDateTime {
         self.moddate
     }
+
+    /// Where this file sat in the library it was read from, `None` if it was
+    /// built rather than parsed.
+    pub fn source_index(&self) -> Option<usize> {
+        self.source_index
+    }
+
+    /// The number of bytes `to_object_bytes` produces.
+    pub fn serialized_len(&self) -> usize {
+        self.obj.serialized_len()
+    }
+
+    /// Writes out just this file's object, as it would appear inside a library.
+    pub fn to_object_bytes(&self) -> Result<Vec<u8>, LibmetroError> {
+        let mut out: Vec<u8> = vec![];
+        self.obj
+            .serialize_out(&mut out)
+            .map_err(|e| e.in_file(&self.file_name))?;
+
+        Ok(out)
+    }
 }
 
+/// A library member as listed in the file header table, without its object.
 #[derive(Debug, Clone)]
+pub struct TocEntry {
+    file_name: String,
+    full_path: String,
+    moddate: MacDateTime,
+    data_size: usize,
+}
+
+impl TocEntry {
+    pub fn file_name(&self) -> &str {
+        self.file_name.as_str()
+    }
+
+    pub fn full_path(&self) -> &str {
+        self.full_path.as_str()
+    }
+
+    pub fn moddate(&self) -> DateTime<Local> {
+        self.moddate.to_datetime().into()
+    }
+
+    /// The modification date as stored, see `MacDateTime`.
+    pub fn mac_moddate(&self) -> MacDateTime {
+        self.moddate
+    }
+
+    pub fn data_size(&self) -> usize {
+        self.data_size
+    }
+}
+
+/// Checks the 28 byte library header and reads the processor, flags, version,
+/// the size totals and the file count from it.
+fn read_library_header(
+    value: &[u8],
+) -> Result<(LibraryProcessor, LibraryFlags, u32, [u8; 8], u32), LibmetroError> {
+    util::check_len(value, 28)?;
+    let magic = util::convert_be_u32(&value[0..4].try_into().unwrap());
+
+    if magic != LibraryMagicWord::LibraryMagicWord as u32 {
+        return Err(LibmetroError::BadMagic {
+            expected: LibraryMagicWord::LibraryMagicWord as u32,
+            found: magic,
+        });
+    }
+
+    let proc_u32 = util::convert_be_u32(&value[4..8].try_into().unwrap());
+    let proc = LibraryProcessor::from(proc_u32);
+
+    let flags =
+        LibraryFlags::from_bits_retain(util::convert_be_u32(&value[8..12].try_into().unwrap()));
+
+    let version = util::convert_be_u32(&value[12..16].try_into().unwrap());
+    let reserved_header: [u8; 8] = value[16..24].try_into().unwrap();
+    let num_files = util::convert_be_u32(&value[24..28].try_into().unwrap());
+
+    Ok((proc, flags, version, reserved_header, num_files))
+}
+
+/// The moddate, name and path locations, data start and data size from a 20
+/// byte file header.
+fn file_header_fields(value: &[u8]) -> [u32; 5] {
+    [0, 4, 8, 12, 16].map(|x| util::convert_be_u32(&value[x..x + 4].try_into().unwrap()))
+}
+
+/// Reads the NUL terminated string at the start of `value`, which sits at
+/// `loc` in the library. `what` names it in errors.
+fn string_at(value: &[u8], loc: usize, what: &str) -> Result<String, LibmetroError> {
+    util::read_c_string(value)
+        .map_err(|e| LibmetroError::Malformed(format!("Bad {} at {}: {}", what, loc, e)))
+}
+
+/// Reads the NUL terminated string at `loc` from the start of a library being
+/// read from `r`.
+fn read_string_at<R: Read + Seek>(
+    r: &mut R,
+    start: u64,
+    loc: u32,
+    what: &str,
+) -> Result<String, LibmetroError> {
+    r.seek(SeekFrom::Start(start + loc as u64))?;
+
+    let mut bytes: Vec<u8> = vec![];
+    let mut chunk = [0u8; 64];
+    while !bytes.contains(&0) {
+        let n = r.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        bytes.extend_from_slice(&chunk[..n]);
+    }
+
+    string_at(&bytes, loc as usize, what)
+}
+
+/// Reads `num_files` file headers following the library header, returning each
+/// entry along with the offset of its object data.
+pub(crate) fn read_file_headers(
+    value: &[u8],
+    num_files: u32,
+) -> Result<Vec<(TocEntry, usize)>, LibmetroError> {
+    let mut obj_bytes = &value[28..];
+    let mut entries = vec![];
+
+    for _ in 0..num_files {
+        util::check_len(obj_bytes, 20)?;
+        let [file_moddate, file_name_loc, full_path_loc, data_start, data_size] =
+            file_header_fields(obj_bytes);
+        let file_name_loc = file_name_loc as usize;
+        let full_path_loc = full_path_loc as usize;
+
+        // The file_name, full_path, and bytes are relative to the LIBRARY Header not the FILE Header
+        let file_name = string_at(
+            value.get(file_name_loc..).unwrap_or_default(),
+            file_name_loc,
+            "file name",
+        )?;
+
+        let full_path: String = if full_path_loc == 0 {
+            String::new()
+        } else {
+            string_at(
+                value.get(full_path_loc..).unwrap_or_default(),
+                full_path_loc,
+                "full path",
+            )?
+        };
+
+        obj_bytes = &obj_bytes[20..];
+
+        entries.push((
+            TocEntry {
+                file_name,
+                full_path,
+                moddate: MacDateTime::from(file_moddate),
+                data_size: data_size as usize,
+            },
+            data_start as usize,
+        ));
+    }
+
+    Ok(entries)
+}
+
+/// What a name listed by `MetroWerksLibrary::symbols` is defined as, taken
+/// from the hunk that names it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    GlobalCode,
+    GlobalData,
+    Entry,
+    /// Code, local or global, marked for export from a CFM fragment.
+    CFMExport,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MetroWerksLibrary {
     proc: LibraryProcessor,
     flags: LibraryFlags,
     version: u32,
+    // Bytes 16..24 of the header as read, the code and data size totals
+    reserved_header: [u8; 8],
     files: Vec<FileObject>,
 }
 
+/// Kept for existing callers, prefer `entries`, `len` and `get`.
 impl Deref for MetroWerksLibrary {
     type Target = Vec<FileObject>;
 
@@ -78,7 +305,222 @@ impl Deref for MetroWerksLibrary {
     }
 }
 
+impl IntoIterator for MetroWerksLibrary {
+    type Item = FileObject;
+    type IntoIter = std::vec::IntoIter<FileObject>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.files.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a MetroWerksLibrary {
+    type Item = &'a FileObject;
+    type IntoIter = std::slice::Iter<'a, FileObject>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.files.iter()
+    }
+}
+
 impl MetroWerksLibrary {
+    /// The files, in the order the library holds them.
+    pub fn entries(&self) -> &[FileObject] {
+        &self.files
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&FileObject> {
+        self.files.get(index)
+    }
+
+    /// Builds a library from already parsed files.
+    ///
+    /// Fails if any object holds hunks that aren't valid for `proc`.
+    pub fn new(proc: LibraryProcessor, files: Vec<FileObject>) -> Result<Self, LibmetroError> {
+        for f in files.iter() {
+            f.object()
+                .validate_for(proc)
+                .map_err(|e| e.in_file(f.filename()))?;
+        }
+
+        Ok(MetroWerksLibrary::from_files(proc, files))
+    }
+
+    /// Builds a library from files it takes ownership of, like `new` but
+    /// without checking their objects against `proc`.
+    pub fn from_files(proc: LibraryProcessor, files: Vec<FileObject>) -> Self {
+        MetroWerksLibrary {
+            proc,
+            flags: LibraryFlags::empty(),
+            // Every library CodeWarrior has been seen to write is version 2
+            version: proc.library_version().unwrap_or(2),
+            reserved_header: [0; 8],
+            files,
+        }
+    }
+
+    /// Adds a file to the end of the library.
+    ///
+    /// Fails if its object holds hunks that aren't valid for the library's processor.
+    pub fn push_file(&mut self, file: FileObject) -> Result<(), LibmetroError> {
+        file.object()
+            .validate_for(self.proc)
+            .map_err(|e| e.in_file(file.filename()))?;
+        self.files.push(file);
+
+        Ok(())
+    }
+
+    /// Appends every file of `other` after this library's own, in their order.
+    ///
+    /// Fails if the libraries are for different processors. Members may share
+    /// names, each object keeps its own name table. The appended files no
+    /// longer count as read from this library, see `FileObject::source_index`.
+    pub fn merge(&mut self, other: MetroWerksLibrary) -> Result<(), LibmetroError> {
+        if self.proc != other.proc {
+            return Err(LibmetroError::Invalid(format!(
+                "Cannot merge a {:?} library into a {:?} one",
+                other.proc, self.proc
+            )));
+        }
+
+        self.files.extend(other.files.into_iter().map(|mut f| {
+            f.source_index = None;
+            f
+        }));
+
+        Ok(())
+    }
+
+    /// Removes the file at `index`, shifting the ones after it down.
+    pub fn remove_file(&mut self, index: usize) -> Option<FileObject> {
+        if index >= self.files.len() {
+            return None;
+        }
+
+        Some(self.files.remove(index))
+    }
+
+    /// Runs `MetrowerksObject::integrity_check` on every file, for catching a
+    /// damaged library before it's written out.
+    ///
+    /// All problems found are returned together, each tied to its file.
+    pub fn integrity_check(&self) -> Result<(), Vec<LibmetroError>> {
+        let problems: Vec<LibmetroError> = self
+            .files
+            .iter()
+            .flat_map(|f| {
+                f.obj
+                    .integrity_check()
+                    .err()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|e| e.in_file(&f.file_name))
+            })
+            .collect();
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Checks the library could be written out and read back: the processor is
+    /// known and matches the version, every file has a name, and every object
+    /// is valid for the processor, holds together (see `MetrowerksObject::assemble`),
+    /// and serializes.
+    ///
+    /// All problems found are returned together.
+    pub fn validate(&self) -> Result<(), Vec<LibmetroError>> {
+        let mut problems: Vec<LibmetroError> = vec![];
+
+        if self.proc == LibraryProcessor::Unknown {
+            problems.push(LibmetroError::Invalid(
+                "Library processor is unknown".to_owned(),
+            ));
+        }
+
+        if let Some(v) = self.proc.library_version() {
+            if v != self.version {
+                problems.push(LibmetroError::Invalid(format!(
+                    "Bad version for {:?} library, Expected: {}, got: {}",
+                    self.proc, v, self.version
+                )));
+            }
+        }
+
+        for (idx, f) in self.files.iter().enumerate() {
+            if f.file_name.is_empty() {
+                problems.push(LibmetroError::Invalid(format!("File {} has no name", idx)));
+            }
+
+            let obj = f.object();
+            if let Err(e) = obj.validate_for(self.proc) {
+                problems.push(e.in_file(&f.file_name));
+            }
+
+            if let Err(errs) = MetrowerksObject::assemble(
+                obj.names().to_vec(),
+                obj.symbols().cloned(),
+                obj.hunks().clone(),
+                obj.header().clone(),
+            ) {
+                problems.extend(errs.into_iter().map(|e| e.in_file(&f.file_name)));
+            }
+
+            if let Err(e) = obj.serialize_out(&mut std::io::sink()) {
+                problems.push(e.in_file(&f.file_name));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Lists the files in a library from its header table alone, without
+    /// parsing any of the objects.
+    pub fn table_of_contents(value: &[u8]) -> Result<Vec<TocEntry>, LibmetroError> {
+        let (_, _, _, _, num_files) = read_library_header(value)?;
+
+        Ok(read_file_headers(value, num_files)?
+            .into_iter()
+            .map(|(entry, _)| entry)
+            .collect())
+    }
+
+    /// The library's files in the order `order` would write them.
+    pub fn ordered_files(&self, order: ObjectOrder) -> Result<Vec<&FileObject>, LibmetroError> {
+        let mut files: Vec<&FileObject> = self.files.iter().collect();
+
+        match order {
+            ObjectOrder::AsGiven => (),
+            ObjectOrder::ByName => files.sort_by(|a, b| a.file_name.cmp(&b.file_name)),
+            ObjectOrder::Original => {
+                if let Some(f) = files.iter().find(|x| x.source_index.is_none()) {
+                    return Err(LibmetroError::Invalid(format!(
+                        "{} was not read from a library, it has no original position",
+                        f.file_name
+                    )));
+                }
+                files.sort_by_key(|x| x.source_index);
+            }
+        }
+
+        Ok(files)
+    }
+
     pub fn proc(&self) -> LibraryProcessor {
         self.proc
     }
@@ -90,91 +532,327 @@ impl MetroWerksLibrary {
     pub fn version(&self) -> u32 {
         self.version
     }
+
+    /// The object of the file at `index` written out on its own, `None` if
+    /// there's no such file or its object can't be written.
+    pub fn extract(&self, index: usize) -> Option<Vec<u8>> {
+        self.files.get(index)?.to_object_bytes().ok()
+    }
+
+    /// Every global code, global data, entry point and CFM exported name in the
+    /// library, with the file defining it, in file then hunk order.
+    ///
+    /// Hunks whose name id isn't in their object's name table are skipped.
+    pub fn symbols(&self) -> impl Iterator<Item = (&FileObject, &NameEntry, SymbolKind)> {
+        self.files.iter().flat_map(|f| {
+            f.obj.hunks().iter().filter_map(move |h| {
+                let (name_id, kind) = match h.hunk_type() {
+                    HunkType::LocalCode(c) | HunkType::GlobalCode(c)
+                        if c.flag() == ObjCodeFlag::CFMExport =>
+                    {
+                        (c.name_id(), SymbolKind::CFMExport)
+                    }
+                    HunkType::GlobalCode(c) => (c.name_id(), SymbolKind::GlobalCode),
+                    HunkType::GlobalInitializedData(d)
+                    | HunkType::GlobalUninitializedData(d)
+                    | HunkType::GlobalFarInitializedData(d)
+                    | HunkType::GlobalFarUninitializedData(d) => {
+                        (d.name_id(), SymbolKind::GlobalData)
+                    }
+                    HunkType::GlobalEntry(e) => (e.name_id(), SymbolKind::Entry),
+                    _ => return None,
+                };
+
+                Some((f, f.obj.name_by_id(name_id)?, kind))
+            })
+        })
+    }
+
+    /// Bytes 16..24 of the header as they were read, zero for a library that
+    /// wasn't parsed.
+    ///
+    /// They're written back as stored, even when the library's files change.
+    pub fn reserved_header(&self) -> [u8; 8] {
+        self.reserved_header
+    }
 }
 
-impl TryFrom<&[u8]> for MetroWerksLibrary {
-    type Error = String;
+impl MetroWerksLibrary {
+    /// The number of bytes `serialize_with` writes for this library.
+    ///
+    /// Every file's name and path are pooled separately, identical paths are
+    /// not shared.
+    pub fn serialized_len_with(&self, options: &SerializeOptions) -> usize {
+        let pool: usize = self
+            .files
+            .iter()
+            .map(|f| {
+                // Stored as MacRoman, one byte per character
+                let path = if f.full_path.is_empty() {
+                    0
+                } else {
+                    f.full_path.chars().count() + 1
+                };
+                f.file_name.chars().count() + 1 + path
+            })
+            .sum();
+        let objects: usize = self
+            .files
+            .iter()
+            .map(|f| f.obj.serialized_len_with(options))
+            .sum();
 
-    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        let magic = util::convert_be_u32(&value[0..4].try_into().unwrap());
+        28 + 20 * self.files.len() + pool.next_multiple_of(2) + objects
+    }
 
-        if magic != LibraryMagicWord::LibraryMagicWord as u32 {
-            return Err(format!(
-                "Bad Magic Word: Expected: {}, got: {}",
-                LibraryMagicWord::LibraryMagicWord as u32,
-                magic
-            ));
+    /// The number of bytes `serialize_out` writes for this library.
+    pub fn serialized_len(&self) -> usize {
+        self.serialized_len_with(&SerializeOptions::default())
+    }
+
+    /// Writes the library: the 28 byte header, a 20 byte header for each file,
+    /// the pooled file names and paths, then each object. All offsets are from
+    /// the start of the library, and the pool is padded so the first object
+    /// starts on an even offset.
+    ///
+    /// Files are written in `options.object_order`, and each object must be
+    /// valid for the library's processor.
+    pub fn serialize_with<W: Write>(
+        &self,
+        w: &mut W,
+        options: &SerializeOptions,
+    ) -> Result<(), LibmetroError> {
+        let files = self.ordered_files(options.object_order)?;
+
+        let mut objects: Vec<Vec<u8>> = vec![];
+        for f in files.iter() {
+            f.object()
+                .validate_for(self.proc)
+                .map_err(|e| e.in_file(&f.file_name))?;
+
+            let mut bytes: Vec<u8> = vec![];
+            f.object()
+                .serialize_with(&mut bytes, options)
+                .map_err(|e| e.in_file(&f.file_name))?;
+            objects.push(bytes);
         }
 
-        let proc_u32 = util::convert_be_u32(&value[4..8].try_into().unwrap());
-        let proc = LibraryProcessor::from(proc_u32);
+        // File names and paths are pooled after the file headers
+        let mut pool: Vec<u8> = vec![];
+        let pool_start = 28 + 20 * files.len();
+        let mut string_locs: Vec<(u32, u32)> = vec![];
+        for f in files.iter() {
+            let name_loc = pool_start + pool.len();
+            pool.extend(util::encode_mac_roman(&f.file_name).map_err(|e| e.in_file(&f.file_name))?);
+            pool.push(0);
+
+            let path_loc = if f.full_path.is_empty() {
+                0
+            } else {
+                let loc = pool_start + pool.len();
+                pool.extend(
+                    util::encode_mac_roman(&f.full_path).map_err(|e| e.in_file(&f.file_name))?,
+                );
+                pool.push(0);
+                loc
+            };
 
-        let flags_u32 = util::convert_be_u32(&value[8..12].try_into().unwrap());
-        if flags_u32 != 0 {
-            return Err(format!("Bad flags for header, got: {}", flags_u32));
+            string_locs.push((name_loc as u32, path_loc as u32));
+        }
+        if pool.len() % 2 != 0 {
+            pool.push(0);
+        }
+
+        let mut out: Vec<u8> = vec![];
+        out.extend((LibraryMagicWord::LibraryMagicWord as u32).to_be_bytes());
+        out.extend((self.proc as u32).to_be_bytes());
+        out.extend(self.flags.bits().to_be_bytes());
+        out.extend(self.version.to_be_bytes());
+        out.extend(self.reserved_header);
+        out.extend((files.len() as u32).to_be_bytes());
+
+        let mut data_start = pool_start + pool.len();
+        for ((f, (name_loc, path_loc)), bytes) in files.iter().zip(string_locs).zip(objects.iter())
+        {
+            out.extend(f.moddate.as_raw().to_be_bytes());
+            out.extend(name_loc.to_be_bytes());
+            out.extend(path_loc.to_be_bytes());
+            out.extend((data_start as u32).to_be_bytes());
+            out.extend((bytes.len() as u32).to_be_bytes());
+            data_start += bytes.len();
+        }
+
+        out.extend(pool);
+        for bytes in objects {
+            out.extend(bytes);
         }
-        let flags = LibraryFlags::None;
 
-        let version = util::convert_be_u32(&value[12..16].try_into().unwrap());
+        Ok(w.write_all(&out)?)
+    }
+}
 
-        let num_files = util::convert_be_u32(&value[24..28].try_into().unwrap());
+impl MetroWerksLibrary {
+    /// Reads and parses the library at `path`.
+    ///
+    /// ```
+    /// use libmetro::MetroWerksLibrary;
+    ///
+    /// let lib = MetroWerksLibrary::from_file("test/data/add.lib.metro")?;
+    ///
+    /// let path = std::env::temp_dir().join("libmetro-doctest-add.lib");
+    /// lib.to_file(&path)?;
+    /// let reread = MetroWerksLibrary::from_file(&path)?;
+    /// assert_eq!(lib[0].filename(), reread[0].filename());
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, LibmetroError> {
+        let bytes = fs::read(path)?;
 
-        let files = if num_files != 0 {
-            let mut obj_bytes = &value[28..];
-            let mut remaining_files = num_files;
-            let mut files = vec![];
+        MetroWerksLibrary::try_from(bytes.as_slice())
+    }
 
-            while remaining_files > 0 {
-                let file_moddate = util::convert_be_u32(&obj_bytes[0..4].try_into().unwrap());
-                let file_name_loc =
-                    util::convert_be_u32(&obj_bytes[4..8].try_into().unwrap()) as usize;
-                let full_path_loc =
-                    util::convert_be_u32(&obj_bytes[8..12].try_into().unwrap()) as usize;
-                let data_start: usize =
-                    util::convert_be_u32(&obj_bytes[12..16].try_into().unwrap()) as usize;
-                let data_size: usize =
-                    util::convert_be_u32(&obj_bytes[16..20].try_into().unwrap()) as usize;
+    /// Reads and parses a library from `r`, starting at its current position.
+    ///
+    /// Only the headers and one object at a time are held in memory: each
+    /// file's name, path and object are read by seeking to the offsets in its
+    /// header, which are taken from where the library starts. Names are read a
+    /// few bytes at a time, so wrap unbuffered readers in a `BufReader`.
+    pub fn from_reader<R: Read + Seek>(mut r: R) -> Result<Self, LibmetroError> {
+        let start = r.stream_position()?;
 
-                // The file_name, full_path, and bytes are relative to the LIBRARY Header not the FILE Header
-                let file_name = CStr::from_bytes_until_nul(&value[file_name_loc..])
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .to_owned();
+        let mut header = [0u8; 28];
+        r.read_exact(&mut header)?;
+        let (proc, flags, version, reserved_header, num_files) = read_library_header(&header)?;
 
-                let full_path: String = if full_path_loc == 0 {
-                    String::new()
-                } else {
-                    CStr::from_bytes_until_nul(&value[full_path_loc as usize..])
-                        .unwrap()
-                        .to_str()
-                        .unwrap()
-                        .to_owned()
-                };
+        let mut file_headers: Vec<u8> = vec![];
+        (&mut r)
+            .take(20 * num_files as u64)
+            .read_to_end(&mut file_headers)?;
+        util::check_len(&file_headers, 20 * num_files as usize)?;
 
-                // The bytes are relative to the LIBRARY Header not the FILE Header
-                let bytes = &value[data_start..(data_start + data_size)];
-                obj_bytes = &obj_bytes[20..];
+        let mut files = vec![];
+        for (index, fields) in file_headers.chunks_exact(20).enumerate() {
+            let [moddate, file_name_loc, full_path_loc, data_start, data_size] =
+                file_header_fields(fields);
 
-                files.push(FileObject {
-                    moddate: util::from_mac_datetime(file_moddate).into(),
-                    file_name: file_name,
-                    full_path: full_path,
-                    obj: MetrowerksObject::try_from(bytes)?,
-                });
+            let file_name = read_string_at(&mut r, start, file_name_loc, "file name")?;
+            let full_path = if full_path_loc == 0 {
+                String::new()
+            } else {
+                read_string_at(&mut r, start, full_path_loc, "full path")?
+            };
 
-                remaining_files -= 1;
+            // Read through `take` so a bad size can't allocate more than the reader holds
+            r.seek(SeekFrom::Start(start + data_start as u64))?;
+            let mut bytes: Vec<u8> = vec![];
+            (&mut r).take(data_size as u64).read_to_end(&mut bytes)?;
+            if bytes.len() < data_size as usize {
+                return Err(LibmetroError::Truncated {
+                    needed: data_start as usize + data_size as usize,
+                    available: data_start as usize + bytes.len(),
+                }
+                .in_file(&file_name));
             }
+            let obj = MetrowerksObject::try_from((bytes.as_slice(), proc))
+                .map_err(|e| e.in_file(&file_name))?;
 
-            files
-        } else {
-            vec![]
-        };
+            files.push(FileObject {
+                moddate: MacDateTime::from(moddate),
+                file_name,
+                full_path,
+                obj,
+                source_index: Some(index),
+            });
+        }
+
+        Ok(MetroWerksLibrary {
+            proc,
+            flags,
+            version,
+            reserved_header,
+            files,
+        })
+    }
+
+    /// Writes the library to `path` with the default `SerializeOptions`,
+    /// replacing anything already there.
+    ///
+    /// The library is laid out in full before the file is created, so a library
+    /// that can't be written leaves `path` untouched.
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), LibmetroError> {
+        let mut bytes: Vec<u8> = vec![];
+        self.serialize_out(&mut bytes)?;
+
+        let mut file = File::create(path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+
+        Ok(())
+    }
+}
+
+/// Writes the library with the default `SerializeOptions`.
+impl Serializable for MetroWerksLibrary {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        self.serialize_with(w, &SerializeOptions::default())
+    }
+}
+
+/// Parses a library with the default `ParseOptions`.
+impl TryFrom<&[u8]> for MetroWerksLibrary {
+    type Error = LibmetroError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        MetroWerksLibrary::try_from_with_options(value, &ParseOptions::default())
+    }
+}
+
+impl MetroWerksLibrary {
+    /// Parses a library and each of its objects, checking them as strictly as
+    /// `options` asks.
+    pub fn try_from_with_options(
+        value: &[u8],
+        options: &ParseOptions,
+    ) -> Result<Self, LibmetroError> {
+        let (proc, flags, version, reserved_header, num_files) = read_library_header(value)?;
+        // None of the library flag bits have a known meaning
+        if !options.allow_unknown_flags && !flags.is_empty() {
+            return Err(LibmetroError::Malformed(format!(
+                "Unknown library flag bits: {:#010x}",
+                flags.bits()
+            )));
+        }
+
+        let mut files = vec![];
+        for (index, (entry, data_start)) in
+            read_file_headers(value, num_files)?.into_iter().enumerate()
+        {
+            // The bytes are relative to the LIBRARY Header not the FILE Header
+            let bytes = value
+                .get(data_start..(data_start + entry.data_size))
+                .ok_or(LibmetroError::Truncated {
+                    needed: data_start + entry.data_size,
+                    available: value.len(),
+                })
+                .map_err(|e| e.in_file(&entry.file_name))?;
+            let obj = MetrowerksObject::try_from_with_options(bytes, proc, options)
+                .map_err(|e| e.in_file(&entry.file_name))?;
+
+            files.push(FileObject {
+                moddate: entry.moddate,
+                file_name: entry.file_name,
+                full_path: entry.full_path,
+                obj,
+                source_index: Some(index),
+            });
+        }
 
         Ok(MetroWerksLibrary {
             proc: proc,
             flags: flags,
             version: version,
+            reserved_header,
             files: files,
         })
     }
@@ -183,6 +861,8 @@ impl TryFrom<&[u8]> for MetroWerksLibrary {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::code_m68k::{CodeHunks, Hunk, ObjForceActiveHunk, ObjSimpleHunk, ReservedHunk};
+    use crate::objects_m68k::MetrowerksObjectBuilder;
     use std::fs::File;
     use std::io::Read;
 
@@ -302,4 +982,658 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_new_library_from_files() {
+        let mut lib = File::open("test/data/set_volume_ex.lib.metro").unwrap();
+        let mut ve: Vec<u8> = vec![];
+        lib.read_to_end(&mut ve).unwrap();
+
+        let lut = MetroWerksLibrary::try_from(ve.as_ref()).unwrap();
+
+        let built = MetroWerksLibrary::new(LibraryProcessor::M68k, lut.to_vec()).unwrap();
+        assert_eq!(LibraryProcessor::M68k, built.proc());
+        assert_eq!(lut.version(), built.version());
+        assert_eq!(lut.len(), built.len());
+
+        // Taking the files by value keeps them as they were
+        let files = lut.to_vec();
+        let owned = MetroWerksLibrary::from_files(lut.proc(), lut.clone().into_iter().collect());
+        assert_eq!(LibraryProcessor::M68k, owned.proc());
+        assert_eq!(files, owned.to_vec());
+        assert_eq!(built, owned);
+    }
+
+    #[test]
+    fn test_table_of_contents() {
+        let mut lib = File::open("test/data/two_funcs.lib.metro").unwrap();
+        let mut ve: Vec<u8> = vec![];
+        lib.read_to_end(&mut ve).unwrap();
+
+        let toc = MetroWerksLibrary::table_of_contents(&ve).unwrap();
+        let lut = MetroWerksLibrary::try_from(ve.as_ref()).unwrap();
+
+        assert_eq!(lut.len(), toc.len());
+        assert_eq!(lut[0].filename(), toc[0].file_name());
+        assert_eq!(lut[0].fullpath(), toc[0].full_path());
+        assert_eq!(lut[0].moddate(), toc[0].moddate());
+        assert_eq!(ve.len() - 0x5c, toc[0].data_size());
+    }
+
+    #[test]
+    fn test_raw_moddate() {
+        let mut ve = fs::read("test/data/add.lib.metro").unwrap();
+        for raw in [0, 1, 0x7fffffff, u32::MAX] {
+            ve[28..32].copy_from_slice(&u32::to_be_bytes(raw));
+
+            let toc = MetroWerksLibrary::table_of_contents(&ve).unwrap();
+            assert_eq!(raw, toc[0].mac_moddate().as_raw());
+
+            for lut in [
+                MetroWerksLibrary::try_from(ve.as_ref()).unwrap(),
+                MetroWerksLibrary::from_reader(std::io::Cursor::new(&ve)).unwrap(),
+            ] {
+                assert_eq!(raw, lut[0].mac_moddate().as_raw());
+
+                let mut out: Vec<u8> = vec![];
+                lut.serialize_out(&mut out).unwrap();
+                assert_eq!(ve, out);
+            }
+        }
+    }
+
+    #[test]
+    fn test_byte_swapped_magic() {
+        let mut lib = File::open("test/data/add.lib.metro").unwrap();
+        let mut ve: Vec<u8> = vec![];
+        lib.read_to_end(&mut ve).unwrap();
+
+        ve[0..4].reverse();
+        let err = MetroWerksLibrary::try_from(ve.as_ref()).unwrap_err();
+        assert!(matches!(err, LibmetroError::BadMagic { .. }));
+        assert!(err.to_string().contains("'MWOB'"));
+        assert!(err.to_string().contains("byte-swapped"));
+    }
+
+    #[test]
+    fn test_ordered_files() {
+        let mut lib = File::open("test/data/add.lib.metro").unwrap();
+        let mut ve: Vec<u8> = vec![];
+        lib.read_to_end(&mut ve).unwrap();
+
+        let lut = MetroWerksLibrary::try_from(ve.as_ref()).unwrap();
+        assert_eq!(Some(0), lut[0].source_index());
+
+        let built_file = FileObject::new(lut[0].moddate(), "add.c", "", lut[0].object().clone());
+        assert_eq!(None, built_file.source_index());
+
+        let files = vec![built_file, lut[0].clone()];
+        let built = MetroWerksLibrary::new(LibraryProcessor::M68k, files).unwrap();
+
+        let as_given = built.ordered_files(ObjectOrder::AsGiven).unwrap();
+        assert_eq!("add.c", as_given[0].filename());
+
+        // "CW11:Desktop Folder:..." sorts ahead of "add.c"
+        let by_name = built.ordered_files(ObjectOrder::ByName).unwrap();
+        assert_eq!(lut[0].filename(), by_name[0].filename());
+        assert_eq!("add.c", by_name[1].filename());
+
+        assert!(built.ordered_files(ObjectOrder::Original).is_err());
+        let original = lut.ordered_files(ObjectOrder::Original).unwrap();
+        assert_eq!(lut[0].filename(), original[0].filename());
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut lib = File::open("test/data/two_funcs.lib.metro").unwrap();
+        let mut ve: Vec<u8> = vec![];
+        lib.read_to_end(&mut ve).unwrap();
+
+        let lut = MetroWerksLibrary::try_from(ve.as_ref()).unwrap();
+        assert!(lut.validate().is_ok());
+
+        let unnamed = FileObject::new(lut[0].moddate(), "", "", lut[0].object().clone());
+        let mut built = MetroWerksLibrary::new(LibraryProcessor::Unknown, vec![unnamed]).unwrap();
+        built.version = 1;
+
+        let problems = built.validate().unwrap_err();
+        assert_eq!(2, problems.len(), "{:?}", problems);
+        assert!(problems[0].to_string().contains("processor is unknown"));
+        assert!(problems[1].to_string().contains("has no name"));
+
+        built.proc = LibraryProcessor::M68k;
+        let problems = built.validate().unwrap_err();
+        assert!(problems[0].to_string().contains("Bad version"));
+
+        // A name with no MacRoman encoding can't be written
+        let mut obj = lut[0].object().clone();
+        obj.rename(1, "関数").unwrap();
+        let file = FileObject::new(lut[0].moddate(), "add.c", "", obj);
+        let built = MetroWerksLibrary::new(LibraryProcessor::M68k, vec![file]).unwrap();
+        let problems = built.validate().unwrap_err();
+        assert_eq!(1, problems.len(), "{:?}", problems);
+        assert!(problems[0].to_string().contains("add.c"));
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let mut lib = File::open("test/data/two_funcs.lib.metro").unwrap();
+        let mut ve: Vec<u8> = vec![];
+        lib.read_to_end(&mut ve).unwrap();
+
+        let lut = MetroWerksLibrary::try_from(ve.as_ref()).unwrap();
+
+        let mut out: Vec<u8> = vec![];
+        lut.serialize_out(&mut out).unwrap();
+        assert_eq!(ve, out);
+        assert_eq!(lut, MetroWerksLibrary::try_from(out.as_ref()).unwrap());
+    }
+
+    #[test]
+    fn test_serialize_ordered_files() {
+        let mut lib = File::open("test/data/add.lib.metro").unwrap();
+        let mut ve: Vec<u8> = vec![];
+        lib.read_to_end(&mut ve).unwrap();
+
+        let lut = MetroWerksLibrary::try_from(ve.as_ref()).unwrap();
+        let files = vec![
+            FileObject::new(lut[0].moddate(), "b.c", "", lut[0].object().clone()),
+            FileObject::new(lut[0].moddate(), "a.c", "HD:a.c", lut[0].object().clone()),
+        ];
+        let built = MetroWerksLibrary::new(LibraryProcessor::M68k, files).unwrap();
+
+        let options = SerializeOptions {
+            object_order: ObjectOrder::ByName,
+            ..Default::default()
+        };
+        let mut out: Vec<u8> = vec![];
+        built.serialize_with(&mut out, &options).unwrap();
+
+        let reread = MetroWerksLibrary::try_from(out.as_ref()).unwrap();
+        assert_eq!(2, reread.len());
+        assert_eq!(
+            ("a.c", "HD:a.c"),
+            (reread[0].filename(), reread[0].fullpath())
+        );
+        assert_eq!(("b.c", ""), (reread[1].filename(), reread[1].fullpath()));
+        assert_eq!(lut[0].moddate(), reread[1].moddate());
+        assert_eq!(lut[0].object().names(), reread[1].object().names());
+    }
+
+    #[test]
+    fn test_from_file_errors() {
+        let lut = MetroWerksLibrary::from_file("test/data/two_funcs.lib.metro").unwrap();
+        assert_eq!(1, lut.len());
+
+        match MetroWerksLibrary::from_file("test/data/missing.lib.metro") {
+            Err(LibmetroError::Io(e)) => assert_eq!(std::io::ErrorKind::NotFound, e.kind()),
+            x => panic!("Expected an I/O error, got: {:?}", x),
+        }
+
+        match MetroWerksLibrary::from_file("Cargo.toml") {
+            Err(LibmetroError::BadMagic { expected, .. }) => {
+                assert_eq!(LibraryMagicWord::LibraryMagicWord as u32, expected)
+            }
+            x => panic!("Expected a parse error, got: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_from_reader() {
+        for path in [
+            "test/data/add.lib.metro",
+            "test/data/two_funcs.lib.metro",
+            "test/data/set_volume_ex.lib.metro",
+        ] {
+            let ve = fs::read(path).unwrap();
+            let lut = MetroWerksLibrary::try_from(ve.as_ref()).unwrap();
+            let streamed = MetroWerksLibrary::from_reader(std::io::Cursor::new(&ve)).unwrap();
+
+            assert_eq!(lut.len(), streamed.len(), "{}", path);
+            for (a, b) in lut.iter().zip(streamed.iter()) {
+                assert_eq!(a.filename(), b.filename());
+                assert_eq!(a.fullpath(), b.fullpath());
+                assert_eq!(a.moddate(), b.moddate());
+                assert_eq!(a.source_index(), b.source_index());
+                assert_eq!(a.object().names(), b.object().names());
+            }
+        }
+
+        // Offsets are from where the library starts, not the start of the reader
+        let mut ve = vec![0xff; 7];
+        ve.extend(fs::read("test/data/add.lib.metro").unwrap());
+        let mut cursor = std::io::Cursor::new(&ve);
+        cursor.seek(SeekFrom::Start(7)).unwrap();
+        let streamed = MetroWerksLibrary::from_reader(cursor).unwrap();
+        assert_eq!(3, streamed[0].object().names().len());
+
+        ve[7..11].reverse();
+        let mut cursor = std::io::Cursor::new(&ve);
+        cursor.seek(SeekFrom::Start(7)).unwrap();
+        let err = MetroWerksLibrary::from_reader(cursor).unwrap_err();
+        assert!(matches!(err, LibmetroError::BadMagic { .. }));
+
+        // Cut off part way through the file header table
+        ve[7..11].reverse();
+        let err = MetroWerksLibrary::from_reader(std::io::Cursor::new(&ve[7..40])).unwrap_err();
+        assert!(matches!(err, LibmetroError::Truncated { .. }), "{:?}", err);
+
+        // An object size past the end of the reader
+        let mut ve = fs::read("test/data/add.lib.metro").unwrap();
+        ve[44..48].copy_from_slice(&u32::MAX.to_be_bytes());
+        let err = MetroWerksLibrary::from_reader(std::io::Cursor::new(&ve)).unwrap_err();
+        assert!(
+            matches!(
+                err.root_cause(),
+                LibmetroError::Truncated { needed, available }
+                    if *needed == 0x5c + u32::MAX as usize && *available == ve.len()
+            ),
+            "{:?}",
+            err
+        );
+        assert!(err.to_string().contains("HelloWorld.c"), "{}", err);
+    }
+
+    #[test]
+    fn test_powerpc_library() {
+        let mut ve = fs::read("test/data/set_volume_ex.lib.metro").unwrap();
+        ve[4..8].copy_from_slice(&(LibraryProcessor::PowerPC as u32).to_be_bytes());
+
+        let lut = MetroWerksLibrary::try_from(ve.as_ref()).unwrap();
+        assert_eq!(LibraryProcessor::PowerPC, lut.proc());
+        assert_eq!(1, lut.len());
+        assert!(lut[0]
+            .object()
+            .validate_for(LibraryProcessor::PowerPC)
+            .is_ok());
+
+        let streamed = MetroWerksLibrary::from_reader(std::io::Cursor::new(&ve)).unwrap();
+        assert_eq!(LibraryProcessor::PowerPC, streamed.proc());
+    }
+
+    #[test]
+    fn test_powerpc_force_active() {
+        let lut = MetroWerksLibrary::from_file("test/data/set_volume_ex.lib.metro").unwrap();
+        let obj = lut[0].object();
+        let mut hunks = obj.hunks().clone();
+        hunks
+            .insert_hunk(
+                1,
+                Hunk::from(HunkType::ForceActive(ObjForceActiveHunk::new(1))),
+            )
+            .unwrap();
+        let obj = MetrowerksObject::assemble(
+            obj.names().to_vec(),
+            obj.symbols().cloned(),
+            hunks,
+            obj.header().clone(),
+        )
+        .unwrap();
+        let file = FileObject::new(lut[0].moddate(), lut[0].filename(), lut[0].fullpath(), obj);
+        let built = MetroWerksLibrary::new(LibraryProcessor::PowerPC, vec![file]).unwrap();
+        let mut ve: Vec<u8> = vec![];
+        built.serialize_out(&mut ve).unwrap();
+
+        let options = ParseOptions {
+            reject_reserved_hunks: true,
+            ..ParseOptions::default()
+        };
+        for lut in [
+            MetroWerksLibrary::try_from(ve.as_ref()).unwrap(),
+            MetroWerksLibrary::try_from_with_options(ve.as_ref(), &options).unwrap(),
+            MetroWerksLibrary::from_reader(std::io::Cursor::new(&ve)).unwrap(),
+        ] {
+            let hunks = lut[0].object().hunks();
+            assert!(matches!(&hunks[1].hunk_type(), HunkType::ForceActive(f) if f.name_id() == 1));
+            assert!(!hunks.has_reserved_for(LibraryProcessor::PowerPC));
+
+            let mut out: Vec<u8> = vec![];
+            lut.serialize_out(&mut out).unwrap();
+            assert_eq!(ve, out);
+        }
+
+        // Still not a legal m68k hunk
+        ve[4..8].copy_from_slice(&(LibraryProcessor::M68k as u32).to_be_bytes());
+        assert!(MetroWerksLibrary::try_from(ve.as_ref()).is_err());
+    }
+
+    #[test]
+    fn test_library_flags() {
+        let mut ve = fs::read("test/data/add.lib.metro").unwrap();
+        assert!(MetroWerksLibrary::try_from(ve.as_ref())
+            .unwrap()
+            .flags()
+            .is_empty());
+
+        ve[8..12].copy_from_slice(&0x00010002u32.to_be_bytes());
+        let lut = MetroWerksLibrary::try_from(ve.as_ref()).unwrap();
+        assert_eq!(0x00010002, lut.flags().bits());
+
+        let mut out: Vec<u8> = vec![];
+        lut.serialize_out(&mut out).unwrap();
+        assert_eq!(ve, out);
+    }
+
+    #[test]
+    fn test_reserved_header() {
+        for path in [
+            "test/data/add.lib.metro",
+            "test/data/two_funcs.lib.metro",
+            "test/data/set_volume_ex.lib.metro",
+        ] {
+            let ve = fs::read(path).unwrap();
+            let lut = MetroWerksLibrary::try_from(ve.as_ref()).unwrap();
+            assert_eq!(&ve[16..24], lut.reserved_header(), "{}", path);
+
+            let mut out: Vec<u8> = vec![];
+            lut.serialize_out(&mut out).unwrap();
+            assert_eq!(&ve[16..24], &out[16..24], "{}", path);
+        }
+
+        let mut ve = fs::read("test/data/add.lib.metro").unwrap();
+        ve[16..24].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef, 0, 1, 2, 3]);
+        let lut = MetroWerksLibrary::try_from(ve.as_ref()).unwrap();
+        assert_eq!([0xde, 0xad, 0xbe, 0xef, 0, 1, 2, 3], lut.reserved_header());
+
+        let mut out: Vec<u8> = vec![];
+        lut.serialize_out(&mut out).unwrap();
+        assert_eq!(ve, out);
+
+        let lut = MetroWerksLibrary::from_file("test/data/two_funcs.lib.metro").unwrap();
+        let built = MetroWerksLibrary::new(LibraryProcessor::M68k, lut.to_vec()).unwrap();
+        assert_eq!([0; 8], built.reserved_header());
+    }
+
+    #[test]
+    fn test_extract() {
+        let ve = fs::read("test/data/add.lib.metro").unwrap();
+        let lut = MetroWerksLibrary::try_from(ve.as_ref()).unwrap();
+
+        let bytes = lut.extract(0).unwrap();
+        assert_eq!(&ve[0x5c..], bytes.as_slice());
+        assert_eq!(bytes, lut[0].to_object_bytes().unwrap());
+        assert_eq!(
+            lut[0].object(),
+            &MetrowerksObject::try_from(bytes.as_slice()).unwrap()
+        );
+
+        assert!(lut.extract(1).is_none());
+    }
+
+    #[test]
+    fn test_serialized_len() {
+        for path in [
+            "test/data/add.lib.metro",
+            "test/data/two_funcs.lib.metro",
+            "test/data/set_volume_ex.lib.metro",
+        ] {
+            let lut = MetroWerksLibrary::from_file(path).unwrap();
+
+            let mut out: Vec<u8> = vec![];
+            lut.serialize_out(&mut out).unwrap();
+            assert_eq!(out.len(), lut.serialized_len(), "{}", path);
+            assert_eq!(
+                lut[0].to_object_bytes().unwrap().len(),
+                lut[0].serialized_len(),
+                "{}",
+                path
+            );
+
+            let options = SerializeOptions {
+                hunk_alignment: 16,
+                ..SerializeOptions::default()
+            };
+            let mut out: Vec<u8> = vec![];
+            lut.serialize_with(&mut out, &options).unwrap();
+            assert_eq!(out.len(), lut.serialized_len_with(&options), "{}", path);
+        }
+
+        // Two files sharing a path, each with its own copy in the pool
+        let lut = MetroWerksLibrary::from_file("test/data/add.lib.metro").unwrap();
+        let mut files = lut.to_vec();
+        files.push(FileObject::new(
+            lut[0].moddate(),
+            "other.c",
+            lut[0].fullpath(),
+            lut[0].object().clone(),
+        ));
+        let built = MetroWerksLibrary::new(LibraryProcessor::M68k, files).unwrap();
+        let mut out: Vec<u8> = vec![];
+        built.serialize_out(&mut out).unwrap();
+        assert_eq!(out.len(), built.serialized_len());
+    }
+
+    #[test]
+    fn test_push_and_remove_files() {
+        let add = MetroWerksLibrary::from_file("test/data/add.lib.metro").unwrap();
+        let two = MetroWerksLibrary::from_file("test/data/two_funcs.lib.metro").unwrap();
+
+        let mut lut = MetroWerksLibrary::new(LibraryProcessor::M68k, vec![]).unwrap();
+        assert!(lut.is_empty());
+        lut.push_file(add[0].clone()).unwrap();
+        lut.push_file(two[0].clone()).unwrap();
+        assert_eq!(2, lut.len());
+        assert_eq!(LibraryProcessor::M68k, lut.proc());
+        assert_eq!(two[0].filename(), lut[1].filename());
+
+        // Segment hunks are m68k only
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend(HunkType::Start(ObjSimpleHunk {}).tag().to_be_bytes());
+        bytes.extend(0x457cu16.to_be_bytes()); // HUNK_SEGMENT
+        bytes.extend(1u32.to_be_bytes());
+        bytes.extend(HunkType::End(ObjSimpleHunk {}).tag().to_be_bytes());
+        let obj = MetrowerksObjectBuilder::new()
+            .next_name("Main")
+            .hunks(CodeHunks::try_from(bytes.as_slice()).unwrap())
+            .build()
+            .unwrap();
+        let segmented = FileObject::new(add[0].moddate(), "seg.c", "", obj);
+        lut.push_file(segmented.clone()).unwrap();
+        assert_eq!(3, lut.len());
+
+        let mut ppc = MetroWerksLibrary::new(LibraryProcessor::PowerPC, vec![]).unwrap();
+        let err = ppc.push_file(segmented).unwrap_err();
+        assert!(err.to_string().contains("seg.c"), "{}", err);
+        assert!(ppc.is_empty());
+
+        assert!(lut.remove_file(2).is_some());
+        let removed = lut.remove_file(0).unwrap();
+        assert_eq!(add[0].filename(), removed.filename());
+        assert_eq!(two[0].filename(), lut[0].filename());
+        assert!(lut.remove_file(1).is_none());
+        assert_eq!(1, lut.len());
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut lut = MetroWerksLibrary::from_file("test/data/add.lib.metro").unwrap();
+        let other = MetroWerksLibrary::from_file("test/data/set_volume_ex.lib.metro").unwrap();
+
+        lut.merge(other.clone()).unwrap();
+        assert_eq!(2, lut.len());
+        assert_eq!(other[0].filename(), lut[1].filename());
+        assert_eq!(other[0].object(), lut[1].object());
+        assert_eq!(Some(0), lut[0].source_index());
+        assert_eq!(None, lut[1].source_index());
+
+        // Names may repeat across members
+        lut.merge(other.clone()).unwrap();
+        assert_eq!(3, lut.len());
+        assert_eq!(lut[1].filename(), lut[2].filename());
+
+        let mut out: Vec<u8> = vec![];
+        lut.serialize_out(&mut out).unwrap();
+        assert_eq!(3, MetroWerksLibrary::try_from(out.as_ref()).unwrap().len());
+
+        let mut ppc = MetroWerksLibrary::new(LibraryProcessor::PowerPC, vec![]).unwrap();
+        assert!(ppc.merge(other).is_err());
+        assert!(ppc.is_empty());
+    }
+
+    #[test]
+    fn test_symbols() {
+        let lut = MetroWerksLibrary::from_file("test/data/two_funcs.lib.metro").unwrap();
+
+        let mut names = vec![];
+        for (file, name, kind) in lut.symbols() {
+            assert_eq!(lut[0].filename(), file.filename());
+            assert_eq!(SymbolKind::GlobalCode, kind);
+            names.push(name.name().as_str());
+        }
+        names.sort();
+        assert_eq!(vec!["add", "is_lower"], names);
+    }
+
+    #[test]
+    fn test_integrity_check() {
+        let mut ve = fs::read("test/data/two_funcs.lib.metro").unwrap();
+        MetroWerksLibrary::try_from(ve.as_ref())
+            .unwrap()
+            .integrity_check()
+            .unwrap();
+
+        // The header's code size, which parsing is told to let through
+        ve[0x5c + 35] += 2;
+        let options = ParseOptions {
+            check_sizes: false,
+            ..ParseOptions::default()
+        };
+        let lut = MetroWerksLibrary::try_from_with_options(ve.as_ref(), &options).unwrap();
+
+        let problems = lut.integrity_check().unwrap_err();
+        assert_eq!(1, problems.len(), "{:?}", problems);
+        match &problems[0] {
+            LibmetroError::InFile { file, error } => {
+                assert_eq!(lut[0].filename(), file);
+                assert!(
+                    matches!(
+                        **error,
+                        LibmetroError::SizeMismatch {
+                            section: "code",
+                            ..
+                        }
+                    ),
+                    "{}",
+                    error
+                );
+            }
+            x => panic!("{}", x),
+        }
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let ve = fs::read("test/data/add.lib.metro").unwrap();
+        let header = MetroWerksLibrary::try_from(ve.as_ref()).unwrap()[0]
+            .object()
+            .header()
+            .clone();
+        let strict = ParseOptions::default();
+        let parse = |bytes: &[u8], options: &ParseOptions| {
+            MetroWerksLibrary::try_from_with_options(bytes, options).map_err(|e| e.to_string())
+        };
+
+        // The first name's stored hash
+        let mut bad_hash = ve.clone();
+        bad_hash[0x5c + header.nametable_start()] ^= 0x01;
+        assert!(parse(&bad_hash, &strict)
+            .unwrap_err()
+            .contains("Hash mismatch"));
+        let options = ParseOptions {
+            check_name_hashes: false,
+            ..ParseOptions::default()
+        };
+        assert!(parse(&bad_hash, &options).is_ok());
+
+        // Swap the Start hunk for a reserved one, only rejected on request
+        let mut break_hunk = ve.clone();
+        let start = 0x5c + 64;
+        break_hunk[start..start + 2]
+            .copy_from_slice(&HunkType::LibraryBreak(ReservedHunk {}).tag().to_be_bytes());
+        assert!(parse(&break_hunk, &strict).is_ok());
+        assert!(MetrowerksObject::try_from(&break_hunk[0x5c..]).is_ok());
+        let options = ParseOptions {
+            reject_reserved_hunks: true,
+            ..ParseOptions::default()
+        };
+        assert!(parse(&break_hunk, &options)
+            .unwrap_err()
+            .contains("reserved"));
+
+        // Swap the End hunk for a reserved one
+        let mut reserved = ve.clone();
+        let end = 0x5c + header.obj_end() - 2;
+        reserved[end..end + 2]
+            .copy_from_slice(&HunkType::Diff8Bit(ReservedHunk {}).tag().to_be_bytes());
+        assert!(parse(&reserved, &strict)
+            .unwrap_err()
+            .contains("without an End hunk"));
+        let mut options = ParseOptions {
+            reject_reserved_hunks: true,
+            ..ParseOptions::default()
+        };
+        assert!(parse(&reserved, &options).unwrap_err().contains("reserved"));
+        options.reject_reserved_hunks = false;
+        options.require_end_hunk = false;
+        assert!(parse(&reserved, &options).is_ok());
+
+        // The header's code size
+        let mut bad_size = ve.clone();
+        bad_size[0x5c + 35] += 2;
+        assert!(parse(&bad_size, &strict).unwrap_err().contains("code"));
+        let options = ParseOptions {
+            check_sizes: false,
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            header.code_size() + 2,
+            parse(&bad_size, &options).unwrap()[0]
+                .object()
+                .header()
+                .code_size()
+        );
+
+        // A library flag bit, then an object flag bit
+        let mut flagged = ve.clone();
+        flagged[11] = 0x01;
+        let mut obj_flagged = ve.clone();
+        obj_flagged[0x5c + 7] |= 0x10;
+        let options = ParseOptions {
+            allow_unknown_flags: false,
+            ..ParseOptions::default()
+        };
+        for bytes in [&flagged, &obj_flagged] {
+            assert!(parse(bytes, &strict).is_ok());
+            assert!(parse(bytes, &options).unwrap_err().contains("flag bits"));
+        }
+
+        let lenient = ParseOptions::lenient();
+        for bytes in [&bad_hash, &reserved, &bad_size, &flagged, &obj_flagged] {
+            assert!(parse(bytes, &lenient).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let lut = MetroWerksLibrary::from_file("test/data/add.lib.metro").unwrap();
+
+        let mut names = vec![];
+        for f in &lut {
+            names.push(f.filename());
+        }
+        assert_eq!(vec![lut[0].filename()], names);
+
+        let files: Vec<FileObject> = lut.clone().into_iter().collect();
+        assert_eq!(1, files.len());
+        let obj = files.into_iter().next().unwrap().obj;
+        assert_eq!(lut[0].object().names(), obj.names());
+
+        assert_eq!(
+            lut.iter().collect::<Vec<_>>(),
+            lut.entries().iter().collect::<Vec<_>>()
+        );
+        assert_eq!((1, false), (lut.len(), lut.is_empty()));
+        assert_eq!(lut.iter().next(), lut.get(0));
+        assert!(lut.get(1).is_none());
+    }
 }
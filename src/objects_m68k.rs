@@ -1,8 +1,15 @@
 use bitflags::bitflags;
 use core::fmt::Display;
-use std::ffi::CStr;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
 
-use crate::util::RawLength;
+use crate::code_m68k::{Hunk, HunkType, ObjCodeFlag};
+use crate::error::LibmetroError;
+use crate::mwob_library::LibraryProcessor;
+use crate::symtable_m68k::Routine;
+use crate::types_m68k::TargetModel;
+use crate::util::{NameIdFromObject, ParseOptions, RawLength, Serializable, SerializeOptions};
 
 use super::{code_m68k::CodeHunks, symtable_m68k::SymbolTable, util};
 
@@ -12,7 +19,8 @@ pub enum ObjectMagicWord {
 }
 
 bitflags! {
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct ObjectFlags: u16 {
        const OBJFLAG_CFM = 0x0001;
        const OBJFLAG_WEAKIMPORT = 0x0004;
@@ -22,9 +30,27 @@ bitflags! {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NameEntry {
     id: u32,
     name: String,
+    stored_hash: Option<u16>,
+}
+
+// Equality is on the id and name only, where the entry came from doesn't matter
+impl PartialEq for NameEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.name == other.name
+    }
+}
+
+impl Eq for NameEntry {}
+
+impl Hash for NameEntry {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+        self.name.hash(state);
+    }
 }
 
 impl Display for NameEntry {
@@ -34,6 +60,41 @@ impl Display for NameEntry {
 }
 
 impl NameEntry {
+    pub fn new(id: u32, name: &str) -> Self {
+        Self {
+            id,
+            name: name.to_owned(),
+            stored_hash: None,
+        }
+    }
+
+    /// An entry read from a name table, keeping the hash from the file only
+    /// when it differs from what `computed_hash` gives.
+    fn parsed(id: u32, name: String, stored_hash: u16) -> Self {
+        let mut entry = Self {
+            id,
+            name,
+            stored_hash: None,
+        };
+
+        if entry.computed_hash() != stored_hash {
+            entry.stored_hash = Some(stored_hash);
+        }
+
+        entry
+    }
+
+    /// The name table hash for this name, as it should be written to disk.
+    pub fn computed_hash(&self) -> u16 {
+        util::nametable_hash(&self.name)
+    }
+
+    /// The hash read from the file, present only when it disagrees with
+    /// `computed_hash`.
+    pub fn stored_hash(&self) -> Option<u16> {
+        self.stored_hash
+    }
+
     pub fn name(&self) -> &String {
         &self.name
     }
@@ -41,9 +102,18 @@ impl NameEntry {
     pub fn id(&self) -> u32 {
         self.id
     }
+
+    /// Bytes the entry takes up in the name table: the u16 hash, the name, and
+    /// its NUL terminator. Entries are packed with no padding between them.
+    ///
+    /// Names are stored as MacRoman, one byte per character.
+    pub fn entry_len(&self) -> usize {
+        2 + self.name.chars().count() + 1
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObjectHeader {
     version: u16, /* always OBJ_VERSION */
     flags: ObjectFlags,
@@ -70,21 +140,23 @@ pub struct ObjectHeader {
 }
 
 impl TryFrom<&[u8]> for ObjectHeader {
-    type Error = String;
+    type Error = LibmetroError;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        util::check_len(value, 64)?;
         let magic = util::convert_be_u32(&value[0..4].try_into().unwrap());
 
         if magic != ObjectMagicWord::ObjectMagicWord as u32 {
-            return Err(format!(
-                "Bad magic word, Expected: {}, got: {}",
-                ObjectMagicWord::ObjectMagicWord as u32,
-                magic
-            ));
+            return Err(LibmetroError::BadMagic {
+                expected: ObjectMagicWord::ObjectMagicWord as u32,
+                found: magic,
+            });
         }
 
         let version = util::convert_be_u16(&value[4..6].try_into().unwrap());
-        let flags = ObjectFlags::from_bits(util::convert_be_u16(&value[6..8].try_into().unwrap()));
+        let flags_u16 = util::convert_be_u16(&value[6..8].try_into().unwrap());
+        // Keep bits we don't model so they survive being written back out
+        let flags = ObjectFlags::from_bits_retain(flags_u16);
         let obj_size = util::convert_be_u32(&value[8..12].try_into().unwrap());
         let nametable_offset = util::convert_be_u32(&value[12..16].try_into().unwrap());
         let nametable_count = util::convert_be_u32(&value[16..20].try_into().unwrap());
@@ -93,7 +165,10 @@ impl TryFrom<&[u8]> for ObjectHeader {
         let reserved1 = util::convert_be_u32(&value[28..32].try_into().unwrap());
 
         if reserved1 != 0 {
-            return Err(format!("Reserved1 is not 0L, got: {}", reserved1));
+            return Err(LibmetroError::Malformed(format!(
+                "Reserved1 is not 0L, got: {}",
+                reserved1
+            )));
         }
 
         let code_size = util::convert_be_u32(&value[32..36].try_into().unwrap());
@@ -113,20 +188,26 @@ impl TryFrom<&[u8]> for ObjectHeader {
 
         let reserved3 = value[62];
         if reserved3 != 0 {
-            return Err(format!("Reserved is not 0L, got: {}", reserved3));
+            return Err(LibmetroError::Malformed(format!(
+                "Reserved is not 0L, got: {}",
+                reserved3
+            )));
         }
 
         let reserved4 = value[63];
         if reserved4 != 0 {
-            return Err(format!("Reserved4 is not 0L, got: {}", reserved4));
+            return Err(LibmetroError::Malformed(format!(
+                "Reserved4 is not 0L, got: {}",
+                reserved4
+            )));
         }
 
         Ok(ObjectHeader {
             version: version,
-            flags: flags.unwrap(),
+            flags,
             obj_size: obj_size,
             nametable_offset: nametable_offset,
-            nametable_names: nametable_count - 1,
+            nametable_names: nametable_count.saturating_sub(1),
             symtable_offset: symtab_offset,
             symtable_size: symtable_size,
             reserved1: reserved1,
@@ -154,7 +235,44 @@ impl RawLength for ObjectHeader {
     }
 }
 
+/// Writes the fields as held; `MetrowerksObject` is what keeps them in step
+/// with the rest of the object.
+impl Serializable for ObjectHeader {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        let mut out: Vec<u8> = vec![];
+        out.extend((ObjectMagicWord::ObjectMagicWord as u32).to_be_bytes());
+        out.extend(self.version.to_be_bytes());
+        out.extend(self.flags.bits().to_be_bytes());
+        out.extend(self.obj_size.to_be_bytes());
+        out.extend(self.nametable_offset.to_be_bytes());
+        // The count on disk includes the null name at id 0
+        out.extend((self.nametable_names + 1).to_be_bytes());
+        out.extend(self.symtable_offset.to_be_bytes());
+        out.extend(self.symtable_size.to_be_bytes());
+        out.extend(self.reserved1.to_be_bytes());
+        out.extend(self.code_size.to_be_bytes());
+        out.extend(self.udata_size.to_be_bytes());
+        out.extend(self.idata_size.to_be_bytes());
+        out.extend(self.old_def_version.to_be_bytes());
+        out.extend(self.old_imp_version.to_be_bytes());
+        out.extend(self.current_version.to_be_bytes());
+        out.extend([
+            self.has_flags,
+            self.is_pascal,
+            self.is_fourbyteint,
+            self.is_eightdouble,
+            self.is_mc68881,
+            self.basereg,
+            self.reserved3,
+            self.reserved4,
+        ]);
+
+        Ok(w.write_all(&out)?)
+    }
+}
+
 impl ObjectHeader {
+    /// The code always directly follows the header, there is no field locating it.
     pub fn obj_start(&self) -> usize {
         64
     }
@@ -187,6 +305,75 @@ impl ObjectHeader {
         self.nametable_names as usize
     }
 
+    /// Checks the sections are laid out the way the parser reads them: code
+    /// right after the header, with the symbol table and name table both
+    /// placed after it and not overlapping each other.
+    pub fn check_layout(&self) -> Result<(), LibmetroError> {
+        let has_symtab = self.symtable_start() != 0;
+        let has_names = self.nametable_start() != 0;
+
+        if has_symtab && self.symtable_start() < self.obj_end() {
+            return Err(LibmetroError::Malformed(format!(
+                "Symbol table at {:#x} overlaps code at {:#x}..{:#x}",
+                self.symtable_start(),
+                self.obj_start(),
+                self.obj_end()
+            )));
+        }
+
+        if has_names && self.nametable_start() < self.obj_end() {
+            return Err(LibmetroError::Malformed(format!(
+                "Name table at {:#x} overlaps code at {:#x}..{:#x}",
+                self.nametable_start(),
+                self.obj_start(),
+                self.obj_end()
+            )));
+        }
+
+        if has_symtab
+            && has_names
+            && (self.symtable_start()..self.symtable_end()).contains(&self.nametable_start())
+        {
+            return Err(LibmetroError::Malformed(format!(
+                "Name table at {:#x} overlaps symbol table at {:#x}..{:#x}",
+                self.nametable_start(),
+                self.symtable_start(),
+                self.symtable_end()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Checks the code and symbol table fit within an object of `len` bytes,
+    /// and the name table starts within it.
+    pub fn check_bounds(&self, len: usize) -> Result<(), LibmetroError> {
+        let mut sections = vec![("Code", self.obj_start(), self.obj_end())];
+        if self.symtable_start() != 0 {
+            sections.push(("Symbol table", self.symtable_start(), self.symtable_end()));
+        }
+
+        for (section, start, end) in sections {
+            if end > len {
+                return Err(LibmetroError::Malformed(format!(
+                    "{} at {:#x}..{:#x} runs past the end of the object, {:#x} bytes",
+                    section, start, end, len
+                )));
+            }
+        }
+
+        // The name table's length is only known once its entries are read
+        if self.nametable_start() > len {
+            return Err(LibmetroError::Malformed(format!(
+                "Name table at {:#x} starts past the end of the object, {:#x} bytes",
+                self.nametable_start(),
+                len
+            )));
+        }
+
+        Ok(())
+    }
+
     pub fn reserved1(&self) -> u32 {
         self.reserved1
     }
@@ -219,6 +406,8 @@ impl ObjectHeader {
         self.version
     }
 
+    /// The object's flags, including any bits outside the known `OBJFLAG_`
+    /// constants exactly as they were read.
     pub fn flags(&self) -> ObjectFlags {
         self.flags
     }
@@ -254,9 +443,38 @@ impl ObjectHeader {
     pub fn reserved4(&self) -> u8 {
         self.reserved4
     }
+
+    /// Recomputes the sizes, offsets, and name count for the given sections.
+    ///
+    /// Code follows the header, then the symbol table, then the names.
+    fn lay_out(&mut self, hunks: &CodeHunks, symtab: Option<&SymbolTable>, names: &[NameEntry]) {
+        let (code_size, udata_size, idata_size) = hunks.section_sizes();
+
+        self.obj_size = hunks.raw_length() as u32;
+        self.code_size = code_size as u32;
+        self.idata_size = idata_size as u32;
+        self.udata_size = udata_size as u32;
+
+        let mut end = self.obj_end();
+        match symtab {
+            Some(s) => {
+                self.symtable_offset = end as u32;
+                self.symtable_size = s.raw_length() as u32;
+                end += s.raw_length();
+            }
+            None => {
+                self.symtable_offset = 0;
+                self.symtable_size = 0;
+            }
+        }
+
+        self.nametable_offset = if names.is_empty() { 0 } else { end as u32 };
+        self.nametable_names = names.len() as u32;
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MetrowerksObject {
     header: ObjectHeader,
     names: Vec<NameEntry>,
@@ -269,6 +487,25 @@ impl MetrowerksObject {
         &self.names
     }
 
+    /// The name with the given id.
+    ///
+    /// Ids are handed out in table order when parsing, so the entry at
+    /// `id - 1` is checked first and the table is only searched when names
+    /// have been added or removed out of order.
+    pub fn name_by_id(&self, id: u32) -> Option<&NameEntry> {
+        let at = (id as usize).checked_sub(1).and_then(|x| self.names.get(x));
+        match at {
+            Some(entry) if entry.id() == id => Some(entry),
+            _ => self.names.iter().find(|x| x.id() == id),
+        }
+    }
+
+    /// Every name keyed by its id, for resolving many ids at once without
+    /// relying on the table's order.
+    pub fn build_name_index(&self) -> HashMap<u32, &NameEntry> {
+        self.names.iter().map(|x| (x.id(), x)).collect()
+    }
+
     pub fn symbols(&self) -> Option<&SymbolTable> {
         self.symtab.as_ref()
     }
@@ -280,13 +517,705 @@ impl MetrowerksObject {
     pub fn header(&self) -> &ObjectHeader {
         &self.header
     }
+
+    pub fn symbols_mut(&mut self) -> Option<&mut SymbolTable> {
+        self.symtab.as_mut()
+    }
+
+    /// Inserts a hunk with `CodeHunks::insert_hunk`, keeping the header's
+    /// sizes and offsets in step.
+    pub fn insert_hunk(&mut self, index: usize, hunk: Hunk) -> Result<(), LibmetroError> {
+        self.hunks.insert_hunk(index, hunk)?;
+        self.header
+            .lay_out(&self.hunks, self.symtab.as_ref(), &self.names);
+
+        Ok(())
+    }
+
+    /// Removes a hunk with `CodeHunks::remove_hunk`, keeping the header's
+    /// sizes and offsets in step.
+    pub fn remove_hunk(&mut self, index: usize) -> Result<Hunk, LibmetroError> {
+        let hunk = self.hunks.remove_hunk(index)?;
+        self.header
+            .lay_out(&self.hunks, self.symtab.as_ref(), &self.names);
+
+        Ok(hunk)
+    }
+
+    /// Adds a name to the name table, keeping the header's name count in step.
+    ///
+    /// Fails if an entry with the same id is already present.
+    pub fn push_name(&mut self, entry: NameEntry) -> Result<(), LibmetroError> {
+        if self.names.iter().any(|x| x.id() == entry.id()) {
+            return Err(LibmetroError::Invalid(format!(
+                "Duplicate name id: {}",
+                entry.id()
+            )));
+        }
+
+        self.names.push(entry);
+        self.header.nametable_names = self.names.len() as u32;
+
+        Ok(())
+    }
+
+    /// The id of `name` in the name table, adding it with the id after the
+    /// highest one present if it isn't there yet. Names match case-sensitively.
+    pub fn intern_name(&mut self, name: &str) -> u32 {
+        if let Some(entry) = self.names.iter().find(|x| x.name() == name) {
+            return entry.id();
+        }
+
+        let id = self.names.iter().map(|x| x.id()).max().unwrap_or(0) + 1;
+        self.names.push(NameEntry::new(id, name));
+        self.header.nametable_names = self.names.len() as u32;
+
+        id
+    }
+
+    /// Removes the name with the given id, keeping the header's name count in step.
+    pub fn remove_name_by_id(&mut self, id: u32) -> Option<NameEntry> {
+        let idx = self.names.iter().position(|x| x.id() == id)?;
+        let entry = self.names.remove(idx);
+        self.header.nametable_names = self.names.len() as u32;
+
+        Some(entry)
+    }
+
+    /// Changes the name with the given id, leaving every hunk that refers to
+    /// it pointing at the new name.
+    ///
+    /// Any hash kept from the file belonged to the old name and is dropped.
+    pub fn rename(&mut self, id: u32, new_name: &str) -> Result<(), LibmetroError> {
+        let entry = self
+            .names
+            .iter_mut()
+            .find(|x| x.id() == id)
+            .ok_or_else(|| LibmetroError::Invalid(format!("No name with id: {}", id)))?;
+        *entry = NameEntry::new(id, new_name);
+
+        Ok(())
+    }
+
+    /// Each routine paired with the source file it was compiled from, taken
+    /// from the nearest `SrcBreak` hunk before its code hunk.
+    ///
+    /// Routines whose code comes before any `SrcBreak`, or whose break names
+    /// nothing in the name table, get `None`.
+    pub fn routines_by_source(&self) -> Vec<(Option<&str>, &Routine)> {
+        let Some(symtab) = self.symbols() else {
+            return vec![];
+        };
+        let offsets = symtab.routine_offsets();
+
+        let mut src: Option<&str> = None;
+        let mut routines = vec![];
+        for h in self.hunks.iter() {
+            match h.hunk_type() {
+                HunkType::SrcBreak(s) => src = Some(s.name(self)).filter(|x| !x.is_empty()),
+                HunkType::LocalCode(c) | HunkType::GlobalCode(c) if c.has_symtab() => {
+                    if let Some((_, r)) = offsets
+                        .iter()
+                        .find(|(off, _)| *off == c.sym_offset() as usize)
+                    {
+                        routines.push((src, *r));
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        routines
+    }
+
+    /// The names this fragment imports, from `CFMImport` hunks and from
+    /// import containers, strong or weak, in hunk order.
+    ///
+    /// Containers come with their `(old_def_version, old_imp_version,
+    /// current_version)`, single imports with `None`. Hunks naming nothing in
+    /// the name table are skipped.
+    pub fn cfm_imports(&self) -> impl Iterator<Item = (&NameEntry, Option<(u32, u32, u32)>)> {
+        self.hunks.iter().filter_map(|h| {
+            let (name_id, versions) = match h.hunk_type() {
+                HunkType::CFMImport(i) => (i.name_id(), None),
+                HunkType::CFMImportContainer(c) | HunkType::WeakImportContainer(c) => {
+                    (c.name_id(), Some(c.versions()))
+                }
+                _ => return None,
+            };
+
+            Some((self.name_by_id(name_id)?, versions))
+        })
+    }
+
+    /// The names of code hunks marked for export by a `CFMExport` hunk.
+    pub fn cfm_exports(&self) -> impl Iterator<Item = &NameEntry> {
+        self.hunks
+            .code_hunks()
+            .filter(|c| c.flag() == ObjCodeFlag::CFMExport)
+            .filter_map(|c| self.name_by_id(c.name_id()))
+    }
+
+    /// The int and double sizes and FPU use recorded in the header.
+    ///
+    /// Without 8-byte doubles a `double` is an extended: 12 bytes when built
+    /// for the MC68881, 10 otherwise.
+    pub fn target_model(&self) -> TargetModel {
+        let mc68881 = self.header.is_mc68881 != 0;
+
+        TargetModel {
+            int_size: if self.header.is_fourbyteint != 0 {
+                4
+            } else {
+                2
+            },
+            double_size: match (self.header.is_eightdouble != 0, mc68881) {
+                (true, _) => 8,
+                (false, true) => 12,
+                (false, false) => 10,
+            },
+            mc68881,
+        }
+    }
+
+    /// Checks that every hunk in the object is allowed for `proc`.
+    pub fn validate_for(&self, proc: LibraryProcessor) -> Result<(), LibmetroError> {
+        match self
+            .hunks
+            .iter()
+            .find(|h| !h.hunk_type().is_valid_for(proc))
+        {
+            Some(h) => Err(LibmetroError::Invalid(format!(
+                "{} hunk is not valid for processor {:?}",
+                h.hunk_type().kind(),
+                proc
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that every name id used by a code, data, or entry hunk has an
+    /// entry in the name table.
+    ///
+    /// The file doesn't store ids, so the parser numbers names in table
+    /// order; a hunk that refers past the end of the table or to a name that
+    /// has since been removed is reported here.
+    pub fn validate_names(&self) -> Result<(), LibmetroError> {
+        match self.missing_names().next() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the object still agrees with itself before it's written out:
+    /// the header's section sizes and name count match the hunks and name
+    /// table, every name's hash is right, and every name id a hunk uses is in
+    /// the name table.
+    ///
+    /// Magic words aren't kept once parsed and are always written correctly,
+    /// so there are none to check. All problems found are returned together.
+    pub fn integrity_check(&self) -> Result<(), Vec<LibmetroError>> {
+        let mut problems: Vec<LibmetroError> = vec![];
+
+        let header = &self.header;
+        if let Err(e) = self.hunks.validate_sizes(
+            header.code_size() as usize,
+            header.udata_size() as usize,
+            header.idata_size() as usize,
+        ) {
+            problems.push(e);
+        }
+
+        if header.nametable_count() != self.names.len() {
+            problems.push(LibmetroError::Invalid(format!(
+                "Header counts {} names, but the name table has {}",
+                header.nametable_count(),
+                self.names.len()
+            )));
+        }
+
+        for n in self.names.iter() {
+            if let Some(stored) = n.stored_hash() {
+                problems.push(LibmetroError::NameHashMismatch {
+                    id: n.id(),
+                    stored,
+                    computed: n.computed_hash(),
+                });
+            }
+        }
+
+        problems.extend(self.missing_names());
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    fn missing_names(&self) -> impl Iterator<Item = LibmetroError> + '_ {
+        self.hunks.iter().filter_map(|h| {
+            let id = h.hunk_type().name_id()?;
+            match self.name_by_id(id) {
+                Some(_) => None,
+                None => Some(LibmetroError::Invalid(format!(
+                    "{} hunk refers to missing name id: {}",
+                    h.hunk_type().kind(),
+                    id
+                ))),
+            }
+        })
+    }
+}
+
+impl MetrowerksObject {
+    /// Builds an object from its parts, checking they fit together and filling
+    /// in everything in the header that follows from them.
+    ///
+    /// Duplicate names are merged, and must then run from id 1 without gaps.
+    /// Code hunks with symbols are linked, in order, to the symbol table's
+    /// routines. The sizes, offsets, and name count in `header` are
+    /// recomputed; its flags and versions are kept. The result must then pass
+    /// `validate`, so every code, data, and entry hunk must name an entry in
+    /// `names`.
+    ///
+    /// All problems found are returned together.
+    pub fn assemble(
+        names: Vec<NameEntry>,
+        symtab: Option<SymbolTable>,
+        hunks: CodeHunks,
+        header: ObjectHeader,
+    ) -> Result<MetrowerksObject, Vec<LibmetroError>> {
+        let mut problems: Vec<LibmetroError> = vec![];
+
+        let mut interned: Vec<NameEntry> = vec![];
+        for n in names {
+            match interned.iter().find(|x| x.id == n.id) {
+                Some(x) if x.name == n.name => (),
+                Some(x) => problems.push(LibmetroError::Invalid(format!(
+                    "Name id {} is both {:?} and {:?}",
+                    n.id, x.name, n.name
+                ))),
+                None => interned.push(n),
+            }
+        }
+        interned.sort_by_key(|x| x.id);
+
+        // Ids are positions in the table, so they can't skip
+        if let Some((idx, n)) = interned
+            .iter()
+            .enumerate()
+            .find(|(idx, n)| n.id != *idx as u32 + 1)
+        {
+            problems.push(LibmetroError::Invalid(format!(
+                "Name ids must run from 1 without gaps, expected: {}, got: {}",
+                idx + 1,
+                n.id
+            )));
+        }
+
+        let mut hunks = hunks;
+        let offsets: Vec<usize> = match &symtab {
+            Some(s) => s.routine_offsets().iter().map(|(off, _)| *off).collect(),
+            None => vec![],
+        };
+        if let Err(e) = hunks.link_routines(&offsets) {
+            problems.push(e);
+        }
+
+        let mut header = header;
+        header.lay_out(&hunks, symtab.as_ref(), &interned);
+
+        let obj = MetrowerksObject {
+            header,
+            names: interned,
+            symtab,
+            hunks,
+        };
+        if let Err(errs) = obj.validate() {
+            problems.extend(errs);
+        }
+
+        if problems.is_empty() {
+            Ok(obj)
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Checks the object is ready to be written out: the hunks run from
+    /// `Start` to `End` and each can be serialized, and it passes
+    /// `integrity_check`.
+    ///
+    /// All problems found are returned together.
+    pub fn validate(&self) -> Result<(), Vec<LibmetroError>> {
+        let mut problems: Vec<LibmetroError> = vec![];
+
+        let summary = self.hunks.structural_summary();
+        if !summary.has_start() || !summary.has_end() {
+            problems.push(LibmetroError::Invalid(
+                "Hunks must start with a Start hunk and finish with an End hunk".to_owned(),
+            ));
+        }
+
+        for h in self.hunks.iter() {
+            let kind = h.hunk_type();
+            if kind.is_reserved() {
+                problems.push(LibmetroError::Invalid(format!(
+                    "{} hunks can't be written",
+                    kind.kind()
+                )));
+            } else if let Err(e) = h.serialize_out(&mut std::io::sink()) {
+                problems.push(e);
+            }
+        }
+
+        if let Err(errs) = self.integrity_check() {
+            problems.extend(errs);
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Builds an object from parts it takes ownership of, as they are.
+    ///
+    /// Nothing is checked, linked, or laid out, so parts taken from an object
+    /// give back an equal one. Use `assemble` for parts that need that.
+    pub fn from_parts(
+        header: ObjectHeader,
+        names: Vec<NameEntry>,
+        symtab: Option<SymbolTable>,
+        hunks: CodeHunks,
+    ) -> MetrowerksObject {
+        MetrowerksObject {
+            header,
+            names,
+            symtab,
+            hunks,
+        }
+    }
+
+    /// Recovers whatever code and initialized data can be found in a possibly
+    /// damaged object, along with the hunk's name when it can be resolved.
+    ///
+    /// Unlike `try_from` this never fails: it reads as much of the header and
+    /// name table as it can, walks the hunks until the first point it can't
+    /// make sense of, and returns what it found up to there.
+    pub fn salvage_code(bytes: &[u8]) -> Vec<(Option<String>, Vec<u8>)> {
+        let header = if bytes.len() >= 64 {
+            ObjectHeader::try_from(bytes).ok()
+        } else {
+            None
+        };
+
+        let names: Vec<NameEntry> = match &header {
+            Some(h) if h.nametable_start() != 0 => {
+                let mut names = vec![];
+                let mut name_bytes = bytes.get(h.nametable_start()..).unwrap_or_default();
+                for name_id in 1..=h.nametable_count() as u32 {
+                    let Some(s) = name_bytes
+                        .get(2..usize::min(258, name_bytes.len()))
+                        .and_then(|x| util::read_c_string(x).ok())
+                    else {
+                        break;
+                    };
+                    let stored = util::convert_be_u16(&name_bytes[0..2].try_into().unwrap());
+                    let entry = NameEntry::parsed(name_id, s, stored);
+                    name_bytes = &name_bytes[entry.entry_len()..];
+                    names.push(entry);
+                }
+                names
+            }
+            _ => vec![],
+        };
+
+        // Without a header assume the hunks run from the end of it to the end of the data
+        let start = 64;
+        let end = match &header {
+            Some(h) => usize::min(h.obj_end(), bytes.len()),
+            None => bytes.len(),
+        };
+
+        crate::code_m68k::salvage_hunks(bytes.get(start..end).unwrap_or_default())
+            .into_iter()
+            .map(|(name_id, code)| {
+                let name = name_id
+                    .and_then(|id| names.iter().find(|x| x.id() == id))
+                    .map(|x| x.name().to_owned());
+                (name, code)
+            })
+            .collect()
+    }
 }
 
+impl MetrowerksObject {
+    /// The header as `serialize_with` writes it: sizes, offsets, and name count
+    /// recomputed for the aligned hunks, with the version and reserved fields zeroed.
+    pub(crate) fn written_header(&self, options: &SerializeOptions) -> ObjectHeader {
+        let mut header = self.header.clone();
+        header.version = 0;
+        header.reserved1 = 0;
+        header.reserved3 = 0;
+        header.reserved4 = 0;
+        header.lay_out(
+            &self.hunks.aligned(options.hunk_alignment),
+            self.symtab.as_ref(),
+            &self.names,
+        );
+
+        header
+    }
+
+    /// The number of bytes `serialize_with` writes for this object.
+    pub fn serialized_len_with(&self, options: &SerializeOptions) -> usize {
+        let names: usize = self.names.iter().map(|x| x.entry_len()).sum();
+
+        (64 + self.hunks.aligned(options.hunk_alignment).raw_length()
+            + self.symtab.as_ref().map_or(0, |x| x.raw_length())
+            + names)
+            .next_multiple_of(2)
+    }
+
+    /// The number of bytes `serialize_out` writes for this object.
+    pub fn serialized_len(&self) -> usize {
+        self.serialized_len_with(&SerializeOptions::default())
+    }
+
+    /// Writes the object laid out as header, code, symbol table, then names,
+    /// with the end padded to an even length.
+    ///
+    /// Code and initialized data are padded to `options.hunk_alignment`, and the
+    /// header's sizes and offsets are recomputed to match. Flags, CFM versions,
+    /// and the compiler option bytes are kept; the version and reserved fields
+    /// are written as zero.
+    pub fn serialize_with<W: Write>(
+        &self,
+        w: &mut W,
+        options: &SerializeOptions,
+    ) -> Result<(), LibmetroError> {
+        let mut out: Vec<u8> = vec![];
+        self.written_header(options).serialize_out(&mut out)?;
+        self.hunks
+            .aligned(options.hunk_alignment)
+            .serialize_out(&mut out)?;
+        if let Some(symtab) = &self.symtab {
+            symtab.serialize_out(&mut out)?;
+        }
+        self.serialize_name_table(&mut out)?;
+
+        // Objects are padded out to a whole number of 16-bit words
+        if out.len() % 2 != 0 {
+            out.push(0);
+        }
+        Ok(w.write_all(&out)?)
+    }
+
+    /// Writes the name table: for each name in id order, its hash then the
+    /// name and a NUL. An object without names writes nothing.
+    ///
+    /// Hashes are always recomputed, a stored hash that disagrees is not kept.
+    /// Ids are positions in the table, so they must run from 1 without gaps,
+    /// and names may be at most 255 bytes.
+    pub fn serialize_name_table<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        let mut names: Vec<&NameEntry> = self.names.iter().collect();
+        names.sort_by_key(|x| x.id);
+
+        let mut out: Vec<u8> = vec![];
+        for (idx, n) in names.iter().enumerate() {
+            if n.id != idx as u32 + 1 {
+                return Err(LibmetroError::Invalid(format!(
+                    "Name ids must run from 1 without gaps, expected: {}, got: {}",
+                    idx + 1,
+                    n.id
+                )));
+            }
+            let bytes = util::encode_mac_roman(&n.name)?;
+            if bytes.len() > 255 {
+                return Err(LibmetroError::Invalid(format!(
+                    "Name {} is {} bytes long, at most 255 fit",
+                    n.id,
+                    bytes.len()
+                )));
+            }
+
+            out.extend(n.computed_hash().to_be_bytes());
+            out.extend(bytes);
+            out.push(0);
+        }
+
+        Ok(w.write_all(&out)?)
+    }
+}
+
+/// The address register globals are reached through, as stored in the header's
+/// `basereg`.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaseRegister {
+    /// Code resources and other standalone code.
+    A4 = 4,
+    /// Applications, the default.
+    A5 = 5,
+}
+
+/// Builds a `MetrowerksObject` a piece at a time, see `MetrowerksObject::assemble`
+/// for how the parts are checked.
+#[derive(Debug, Clone)]
+pub struct MetrowerksObjectBuilder {
+    names: Vec<NameEntry>,
+    header: ObjectHeader,
+    symtab: Option<SymbolTable>,
+    hunks: CodeHunks,
+}
+
+impl Default for MetrowerksObjectBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetrowerksObjectBuilder {
+    pub fn new() -> Self {
+        Self {
+            names: vec![],
+            header: ObjectHeader::default(),
+            symtab: None,
+            hunks: CodeHunks::from(vec![]),
+        }
+    }
+
+    /// Adds a name with the given id.
+    pub fn name(mut self, id: u32, name: &str) -> Self {
+        self.names.push(NameEntry::new(id, name));
+        self
+    }
+
+    /// Adds a name with the id after the highest one given so far.
+    pub fn next_name(self, name: &str) -> Self {
+        let id = self.names.iter().map(|x| x.id).max().unwrap_or(0) + 1;
+        self.name(id, name)
+    }
+
+    pub fn flag(mut self, flag: ObjectFlags) -> Self {
+        self.header.flags |= flag;
+        self
+    }
+
+    pub fn fourbyteint(mut self, on: bool) -> Self {
+        self.header.is_fourbyteint = on as u8;
+        self
+    }
+
+    pub fn eightdouble(mut self, on: bool) -> Self {
+        self.header.is_eightdouble = on as u8;
+        self
+    }
+
+    pub fn mc68881(mut self, on: bool) -> Self {
+        self.header.is_mc68881 = on as u8;
+        self
+    }
+
+    pub fn basereg(mut self, reg: BaseRegister) -> Self {
+        self.header.basereg = reg as u8;
+        self
+    }
+
+    pub fn hunks(mut self, hunks: CodeHunks) -> Self {
+        self.hunks = hunks;
+        self
+    }
+
+    pub fn symbols(mut self, symtab: SymbolTable) -> Self {
+        self.symtab = Some(symtab);
+        self
+    }
+
+    /// Assembles the object, failing with every problem found.
+    ///
+    /// Unlike `assemble`, a name id given twice is an error even if both
+    /// names match.
+    pub fn build(self) -> Result<MetrowerksObject, Vec<LibmetroError>> {
+        let mut problems: Vec<LibmetroError> = vec![];
+        for (idx, n) in self.names.iter().enumerate() {
+            if self.names[..idx].iter().any(|x| x.id == n.id) {
+                problems.push(LibmetroError::Invalid(format!(
+                    "Duplicate name id: {}",
+                    n.id
+                )));
+            }
+        }
+
+        if !problems.is_empty() {
+            return Err(problems);
+        }
+
+        MetrowerksObject::assemble(self.names, self.symtab, self.hunks, self.header)
+    }
+}
+
+/// Writes the object with the default `SerializeOptions`.
+impl Serializable for MetrowerksObject {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        self.serialize_with(w, &SerializeOptions::default())
+    }
+}
+
+/// Parses an object without regard to processor, see `CodeHunks`.
 impl TryFrom<&[u8]> for MetrowerksObject {
-    type Error = String;
+    type Error = LibmetroError;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        MetrowerksObject::try_from((value, LibraryProcessor::Unknown))
+    }
+}
+
+/// Parses an object built for the given processor, rejecting hunks that
+/// aren't legal for it, with the default `ParseOptions`.
+///
+/// Fails with `NameHashMismatch` if a name's stored hash is wrong, see
+/// `MetrowerksObject::try_from_lenient`.
+impl TryFrom<(&[u8], LibraryProcessor)> for MetrowerksObject {
+    type Error = LibmetroError;
+
+    fn try_from(value: (&[u8], LibraryProcessor)) -> Result<Self, Self::Error> {
+        MetrowerksObject::try_from_with_options(value.0, value.1, &ParseOptions::default())
+    }
+}
+
+impl MetrowerksObject {
+    /// Parses an object like `try_from`, but trusts the names in the name table
+    /// over their stored hashes. Wrong hashes are kept, see `NameEntry::stored_hash`.
+    pub fn try_from_lenient(value: &[u8]) -> Result<Self, LibmetroError> {
+        let options = ParseOptions {
+            check_name_hashes: false,
+            ..ParseOptions::default()
+        };
+
+        MetrowerksObject::try_from_with_options(value, LibraryProcessor::Unknown, &options)
+    }
+
+    /// Parses an object built for the given processor, checking it as strictly
+    /// as `options` asks.
+    pub fn try_from_with_options(
+        value: &[u8],
+        proc: LibraryProcessor,
+        options: &ParseOptions,
+    ) -> Result<Self, LibmetroError> {
         let header = ObjectHeader::try_from(value)?;
+        header.check_bounds(value.len())?;
+        header.check_layout()?;
+
+        if !options.allow_unknown_flags && !ObjectFlags::all().contains(header.flags()) {
+            return Err(LibmetroError::Malformed(format!(
+                "Unknown object flag bits: {:#06x}",
+                header.flags().bits() & !ObjectFlags::all().bits()
+            )));
+        }
 
         let name_table = if header.nametable_start() != 0 {
             let mut names: Vec<NameEntry> = vec![];
@@ -294,18 +1223,20 @@ impl TryFrom<&[u8]> for MetrowerksObject {
             let mut remaining_names = header.nametable_count();
             let mut name_id = 1;
             while remaining_names > 0 {
-                let s =
-                    CStr::from_bytes_until_nul(&name_bytes[2..usize::min(257, name_bytes.len())])
-                        .unwrap()
-                        .to_str()
-                        .unwrap()
-                        .to_owned();
-                let end_of_entry = 2 + s.as_bytes().len() + 1;
-                name_bytes = &name_bytes[end_of_entry..];
-                names.push(NameEntry {
-                    id: name_id,
-                    name: s,
-                });
+                util::check_len(name_bytes, 3)?;
+                // Names are at most 255 bytes before the NUL
+                let s = util::read_c_string(&name_bytes[2..usize::min(258, name_bytes.len())])?;
+                let stored = util::convert_be_u16(&name_bytes[0..2].try_into().unwrap());
+                let entry = NameEntry::parsed(name_id, s, stored);
+                if options.check_name_hashes && entry.stored_hash.is_some() {
+                    return Err(LibmetroError::NameHashMismatch {
+                        id: name_id,
+                        stored,
+                        computed: entry.computed_hash(),
+                    });
+                }
+                name_bytes = &name_bytes[entry.entry_len()..];
+                names.push(entry);
 
                 remaining_names -= 1;
                 name_id += 1;
@@ -319,10 +1250,11 @@ impl TryFrom<&[u8]> for MetrowerksObject {
         let sym_tab_start = header.symtable_start();
         let sym_tab_end = header.symtable_end();
 
-        let symtab = if sym_tab_start != 0 {
+        // Stripped builds can leave the offset set with nothing behind it
+        let symtab = if sym_tab_start != 0 && sym_tab_end > sym_tab_start {
             let symbol_bytes = &value[sym_tab_start..sym_tab_end];
 
-            Option::Some(SymbolTable::try_from(symbol_bytes).unwrap())
+            Option::Some(SymbolTable::try_from(symbol_bytes)?)
         } else {
             Option::None
         };
@@ -334,9 +1266,17 @@ impl TryFrom<&[u8]> for MetrowerksObject {
 
             let object_bytes = &value[start..end];
 
-            CodeHunks::try_from(object_bytes).unwrap()
+            CodeHunks::try_from_with_options(object_bytes, proc, options)?
         };
 
+        if options.check_sizes {
+            code_objects.validate_sizes(
+                header.code_size() as usize,
+                header.udata_size() as usize,
+                header.idata_size() as usize,
+            )?;
+        }
+
         Ok(MetrowerksObject {
             header: header,
             names: name_table,
@@ -345,3 +1285,722 @@ impl TryFrom<&[u8]> for MetrowerksObject {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{load_library, load_object_bytes};
+
+    fn load_object(path: &str) -> MetrowerksObject {
+        load_library(path)[0].object().clone()
+    }
+
+    #[test]
+    fn test_salvage_code() {
+        let bytes = load_object_bytes("test/data/two_funcs.lib.metro");
+
+        let blobs = MetrowerksObject::salvage_code(&bytes);
+        assert_eq!(2, blobs.len());
+        assert_eq!(Some("add".to_owned()), blobs[0].0);
+        assert_eq!(10, blobs[0].1.len());
+        assert_eq!(Some("is_lower".to_owned()), blobs[1].0);
+        assert_eq!(24, blobs[1].1.len());
+
+        // Cut the object off part way through the second function
+        let blobs = MetrowerksObject::salvage_code(&bytes[..64 + 56]);
+        assert_eq!(2, blobs.len());
+        assert_eq!(None, blobs[0].0);
+        assert_eq!(10, blobs[0].1.len());
+        assert_eq!(8, blobs[1].1.len());
+
+        assert!(MetrowerksObject::salvage_code(&bytes[..10]).is_empty());
+    }
+
+    #[test]
+    fn test_name_mutators_keep_count() {
+        let mut ob = load_object("test/data/add.lib.metro");
+
+        assert!(ob.push_name(NameEntry::new(1, "dup")).is_err());
+
+        ob.push_name(NameEntry::new(4, "sum")).unwrap();
+        assert_eq!(4, ob.names().len());
+        assert_eq!(4, ob.header().nametable_count());
+
+        let removed = ob.remove_name_by_id(2).unwrap();
+        assert_eq!("a", removed.name());
+        assert_eq!(3, ob.header().nametable_count());
+        assert!(ob.remove_name_by_id(2).is_none());
+    }
+
+    #[test]
+    fn test_intern_name() {
+        let mut ob = MetrowerksObject {
+            header: ObjectHeader::default(),
+            names: vec![],
+            symtab: None,
+            hunks: CodeHunks::from(vec![]),
+        };
+
+        let id = ob.intern_name("add");
+        assert_eq!(1, id);
+        assert_eq!(id, ob.intern_name("add"));
+        assert_eq!(1, ob.names().len());
+        assert_eq!(1, ob.header().nametable_count());
+        assert_eq!(2, ob.intern_name("Add"));
+
+        let mut ob = load_object("test/data/add.lib.metro");
+        assert_eq!(2, ob.intern_name("a"));
+        assert_eq!(4, ob.intern_name("sum"));
+        assert_eq!(4, ob.names().len());
+    }
+
+    #[test]
+    fn test_validate_names() {
+        let mut ob = load_object("test/data/add.lib.metro");
+        ob.validate_names().unwrap();
+
+        // The code hunk for "add" is left without a name
+        ob.remove_name_by_id(1);
+        let err = ob.validate_names().unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("GlobalCode hunk refers to missing name id: 1"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut ob = load_object("test/data/add.lib.metro");
+        let id = ob.hunks().code_hunks().next().unwrap().name_id();
+        assert_eq!("add", ob.name_by_id(id).unwrap().name());
+
+        ob.rename(id, "sum").unwrap();
+        let code = ob.hunks().code_hunks().next().unwrap();
+        assert_eq!("sum", ob.name_by_id(code.name_id()).unwrap().name());
+        assert_eq!(3, ob.names().len());
+        ob.validate_names().unwrap();
+
+        let mut out: Vec<u8> = vec![];
+        ob.serialize_out(&mut out).unwrap();
+        assert_eq!(ob, MetrowerksObject::try_from(out.as_slice()).unwrap());
+
+        assert!(ob.rename(9, "sum").is_err());
+    }
+
+    #[test]
+    fn test_cfm_imports_and_exports() {
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend(0x4567u16.to_be_bytes()); // HUNK_START
+        for (tag, id) in [(0x458cu16, 1u32), (0x4596, 2)] {
+            // HUNK_CFM_IMPORT_CONTAINER, HUNK_WEAK_IMPORT_CONTAINER
+            bytes.extend(tag.to_be_bytes());
+            for x in [id, 0x0100, 0x0200, 0x0300] {
+                bytes.extend(x.to_be_bytes());
+            }
+        }
+        bytes.extend(0x458bu16.to_be_bytes()); // HUNK_CFM_IMPORT
+        bytes.extend(3u32.to_be_bytes());
+        bytes.extend(0x458au16.to_be_bytes()); // HUNK_CFM_EXPORT
+        bytes.extend(0x456au16.to_be_bytes()); // HUNK_GLOBAL_CODE
+        for x in [4u32, 2, 0x80000000, 0] {
+            bytes.extend(x.to_be_bytes());
+        }
+        bytes.extend([0x4e, 0x75]);
+        bytes.extend(0x4568u16.to_be_bytes()); // HUNK_END
+
+        let obj = MetrowerksObjectBuilder::new()
+            .next_name("InterfaceLib")
+            .next_name("NameRegistryLib")
+            .next_name("GetSharedLibrary")
+            .next_name("main")
+            .hunks(CodeHunks::try_from(bytes.as_slice()).unwrap())
+            .build()
+            .unwrap();
+
+        let imports: Vec<_> = obj
+            .cfm_imports()
+            .map(|(n, v)| (n.name().as_str(), v))
+            .collect();
+        assert_eq!(
+            vec![
+                ("InterfaceLib", Some((0x0100, 0x0200, 0x0300))),
+                ("NameRegistryLib", Some((0x0100, 0x0200, 0x0300))),
+                ("GetSharedLibrary", None),
+            ],
+            imports
+        );
+
+        let exports: Vec<_> = obj.cfm_exports().map(|n| n.name().as_str()).collect();
+        assert_eq!(vec!["main"], exports);
+
+        let add = load_object("test/data/add.lib.metro");
+        assert_eq!(0, add.cfm_imports().count());
+        assert_eq!(0, add.cfm_exports().count());
+    }
+
+    #[test]
+    fn test_name_lookup_out_of_order() {
+        let mut ob = load_object("test/data/add.lib.metro");
+        assert_eq!("b", ob.name_by_id(3).unwrap().name());
+        assert!(ob.name_by_id(0).is_none());
+
+        // Shift every entry after id 1 down a slot
+        ob.remove_name_by_id(1);
+        ob.push_name(NameEntry::new(9, "sum")).unwrap();
+        assert_eq!("b", ob.name_by_id(3).unwrap().name());
+        assert_eq!("sum", ob.name_by_id(9).unwrap().name());
+        assert!(ob.name_by_id(1).is_none());
+
+        let index = ob.build_name_index();
+        assert_eq!(3, index.len());
+        for entry in ob.names() {
+            assert_eq!(Some(&entry), index.get(&entry.id()));
+        }
+    }
+
+    #[test]
+    fn test_name_entry_len() {
+        let obj = load_object("test/data/add.lib.metro");
+
+        // add, a, b
+        let lens: Vec<usize> = obj.names().iter().map(|x| x.entry_len()).collect();
+        assert_eq!(vec![6, 4, 4], lens);
+
+        // The name table runs from its offset to the end of the object
+        let table_len = lens.iter().sum::<usize>();
+        let obj_bytes = load_object_bytes("test/data/add.lib.metro");
+        assert_eq!(
+            obj_bytes.len() - obj.header().nametable_start(),
+            table_len + table_len % 2
+        );
+    }
+
+    #[test]
+    fn test_mac_roman_names() {
+        let mut obj = load_object("test/data/add.lib.metro");
+        let id = obj.intern_name("Größe");
+        let entry = obj.name_by_id(id).unwrap().clone();
+        assert_eq!(8, entry.entry_len());
+
+        let mut out: Vec<u8> = vec![];
+        obj.serialize_out(&mut out).unwrap();
+        let start = obj
+            .written_header(&SerializeOptions::default())
+            .nametable_start();
+        let table = &out[start..];
+        assert!(table.windows(6).any(|x| x == b"Gr\x9a\xa7e\0"));
+
+        // Read back with the hash checked
+        let reread = MetrowerksObject::try_from(out.as_slice()).unwrap();
+        assert_eq!(Some(&entry), reread.name_by_id(id));
+
+        obj.intern_name("日本");
+        assert!(obj.serialize_out(&mut vec![]).is_err());
+    }
+
+    #[test]
+    fn test_empty_symtab_is_absent() {
+        let mut bytes = load_object_bytes("test/data/add.lib.metro");
+        bytes[24..28].copy_from_slice(&0u32.to_be_bytes());
+
+        let obj = MetrowerksObject::try_from(bytes.as_slice()).unwrap();
+        assert!(obj.header().symtable_start() != 0);
+        assert!(obj.symbols().is_none());
+        assert!(obj.routines_by_source().is_empty());
+        assert_eq!(3, obj.names().len());
+        assert_eq!(3, obj.hunks().len());
+    }
+
+    #[test]
+    fn test_symtab_before_code_is_rejected() {
+        let mut bytes = load_object_bytes("test/data/add.lib.metro");
+        assert!(MetrowerksObject::try_from(bytes.as_slice()).is_ok());
+
+        // Point the symbol table back into the code region
+        bytes[20..24].copy_from_slice(&64u32.to_be_bytes());
+        let err = MetrowerksObject::try_from(bytes.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("overlaps code"));
+    }
+
+    #[test]
+    fn test_out_of_range_offsets() {
+        let bytes = load_object_bytes("test/data/add.lib.metro");
+        let past_end = (bytes.len() as u32 + 0x10).to_be_bytes();
+
+        // obj_size, nametable_offset, symtab_offset and symtable_size
+        for (field, message) in [
+            (8..12, "Code at"),
+            (12..16, "Name table at"),
+            (20..24, "Symbol table at"),
+            (24..28, "Symbol table at"),
+        ] {
+            let mut bad = bytes.clone();
+            bad[field].copy_from_slice(&past_end);
+            let err = MetrowerksObject::try_from(bad.as_slice()).unwrap_err();
+            assert!(err.to_string().contains(message), "{}", err);
+        }
+
+        // More names than the table holds
+        let mut bad = bytes.clone();
+        bad[16..20].copy_from_slice(&0x100u32.to_be_bytes());
+        assert!(MetrowerksObject::try_from(bad.as_slice()).is_err());
+
+        assert!(matches!(
+            MetrowerksObject::try_from(&bytes[..40]),
+            Err(LibmetroError::Truncated { needed: 64, .. })
+        ));
+    }
+
+    #[test]
+    fn test_unknown_object_flags() {
+        let mut bytes = load_object_bytes("test/data/add.lib.metro");
+        bytes[6..8].copy_from_slice(&0x0011u16.to_be_bytes());
+
+        let obj = MetrowerksObject::try_from(bytes.as_slice()).unwrap();
+        assert!(obj.header().flags().contains(ObjectFlags::OBJFLAG_CFM));
+        assert_eq!(0x0011, obj.header().flags().bits());
+
+        let mut out: Vec<u8> = vec![];
+        obj.serialize_out(&mut out).unwrap();
+        assert_eq!(bytes, out);
+    }
+
+    #[test]
+    fn test_routines_by_source() {
+        let obj = load_object("test/data/two_funcs.lib.metro");
+        let routines = obj.routines_by_source();
+        assert_eq!(2, routines.len());
+        assert!(routines.iter().all(|(src, _)| src.is_none()));
+
+        // Splice a SrcBreak (0x458d) naming "add" in right after the Start hunk
+        let mut bytes = load_object_bytes("test/data/add.lib.metro");
+        let src_break: [u8; 10] = [0x45, 0x8d, 0, 0, 0, 1, 0, 0, 0, 0];
+        bytes.splice(66..66, src_break);
+        for field in [8..12, 12..16, 20..24] {
+            let v = util::convert_be_u32(&bytes[field.clone()].try_into().unwrap());
+            bytes[field].copy_from_slice(&(v + 10).to_be_bytes());
+        }
+
+        let obj = MetrowerksObject::try_from(bytes.as_slice()).unwrap();
+        let routines = obj.routines_by_source();
+        assert_eq!(1, routines.len());
+        assert_eq!(Some("add"), routines[0].0);
+    }
+
+    #[test]
+    fn test_stored_name_hash() {
+        for path in [
+            "test/data/add.lib.metro",
+            "test/data/two_funcs.lib.metro",
+            "test/data/set_volume_ex.lib.metro",
+        ] {
+            let obj = load_object(path);
+            assert!(obj.names().iter().all(|x| x.stored_hash().is_none()));
+        }
+
+        let mut bytes = load_object_bytes("test/data/add.lib.metro");
+        let obj = MetrowerksObject::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(0x376, obj.names()[0].computed_hash());
+
+        // Corrupt the hash of the first entry, "add"
+        let start = obj.header().nametable_start();
+        bytes[start..start + 2].copy_from_slice(&0x123u16.to_be_bytes());
+
+        match MetrowerksObject::try_from(bytes.as_slice()) {
+            Err(LibmetroError::NameHashMismatch {
+                id,
+                stored,
+                computed,
+            }) => assert_eq!((1, 0x123, 0x376), (id, stored, computed)),
+            x => panic!("Expected a hash mismatch, got: {:?}", x),
+        }
+
+        let obj = MetrowerksObject::try_from_lenient(bytes.as_slice()).unwrap();
+        assert_eq!(Some(0x123), obj.names()[0].stored_hash());
+        assert_eq!(0x376, obj.names()[0].computed_hash());
+        assert_eq!(None, obj.names()[1].stored_hash());
+    }
+
+    #[test]
+    fn test_name_entry_sets() {
+        use std::collections::HashSet;
+
+        let add = load_object("test/data/add.lib.metro");
+        let two_funcs = load_object("test/data/two_funcs.lib.metro");
+
+        let a: HashSet<&NameEntry> = add.names().iter().collect();
+        let b: HashSet<&NameEntry> = two_funcs.names().iter().collect();
+
+        // Both objects start with "add" as name 1
+        assert!(a.contains(&NameEntry::new(1, "add")));
+        assert!(a.intersection(&b).any(|x| x.name() == "add"));
+        assert_ne!(NameEntry::new(1, "add"), NameEntry::new(2, "add"));
+    }
+
+    #[test]
+    fn test_assemble_from_parts() {
+        for path in [
+            "test/data/add.lib.metro",
+            "test/data/two_funcs.lib.metro",
+            "test/data/set_volume_ex.lib.metro",
+        ] {
+            let obj = load_object(path);
+
+            // Duplicate names are merged rather than rejected
+            let mut names = obj.names().to_vec();
+            names.push(obj.names()[0].clone());
+
+            let built = MetrowerksObject::assemble(
+                names,
+                obj.symbols().cloned(),
+                obj.hunks().clone(),
+                ObjectHeader::default(),
+            )
+            .unwrap();
+
+            let (a, b) = (obj.header(), built.header());
+            assert_eq!(a.obj_length(), b.obj_length(), "{}", path);
+            assert_eq!(a.symtable_start(), b.symtable_start(), "{}", path);
+            assert_eq!(a.symtable_length(), b.symtable_length(), "{}", path);
+            assert_eq!(a.nametable_start(), b.nametable_start(), "{}", path);
+            assert_eq!(a.nametable_count(), b.nametable_count(), "{}", path);
+            assert_eq!(
+                (a.code_size(), a.idata_size(), a.udata_size()),
+                (b.code_size(), b.idata_size(), b.udata_size()),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_odd_idata_round_trip() {
+        use crate::code_m68k::{ObjDataHunk, ObjSimpleHunk};
+
+        let hunks = CodeHunks::from(vec![
+            Hunk::from(HunkType::Start(ObjSimpleHunk {})),
+            Hunk::from(HunkType::GlobalInitializedData(
+                ObjDataHunk::new_initialized(1, 0, 0, b"hi\0"),
+            )),
+            Hunk::from(HunkType::End(ObjSimpleHunk {})),
+        ]);
+        let obj = MetrowerksObject::assemble(
+            vec![NameEntry::new(1, "greeting")],
+            None,
+            hunks,
+            ObjectHeader::default(),
+        )
+        .unwrap();
+        assert_eq!(3, obj.header().idata_size());
+
+        let mut out: Vec<u8> = vec![];
+        obj.serialize_out(&mut out).unwrap();
+        assert_eq!(out.len(), obj.serialized_len());
+        // The data and name leave the object odd, the whole of it is padded
+        assert_eq!(0, obj.serialized_len_with(&SerializeOptions::default()) % 2);
+        assert_eq!(0, out.len() % 2);
+        let parsed = MetrowerksObject::try_from(out.as_slice()).unwrap();
+        assert_eq!(3, parsed.header().idata_size());
+        assert_eq!(obj.hunks(), parsed.hunks());
+
+        let mut again: Vec<u8> = vec![];
+        parsed.serialize_out(&mut again).unwrap();
+        assert_eq!(out, again);
+
+        // Padding is still there to ask for
+        let options = SerializeOptions {
+            hunk_alignment: 2,
+            ..SerializeOptions::default()
+        };
+        let mut padded: Vec<u8> = vec![];
+        obj.serialize_with(&mut padded, &options).unwrap();
+        let parsed = MetrowerksObject::try_from(padded.as_slice()).unwrap();
+        assert_eq!(4, parsed.header().idata_size());
+    }
+
+    #[test]
+    fn test_from_parts() {
+        let obj = load_object("test/data/two_funcs.lib.metro");
+        let MetrowerksObject {
+            header,
+            names,
+            symtab,
+            hunks,
+        } = obj.clone();
+
+        let built = MetrowerksObject::from_parts(header, names, symtab, hunks);
+        assert_eq!(obj, built);
+
+        let mut out: Vec<u8> = vec![];
+        built.serialize_out(&mut out).unwrap();
+        let mut expected: Vec<u8> = vec![];
+        obj.serialize_out(&mut expected).unwrap();
+        assert_eq!(expected, out);
+    }
+
+    #[test]
+    fn test_builder() {
+        let obj = load_object("test/data/add.lib.metro");
+        let names = obj.names();
+
+        let header = ObjectHeader {
+            is_fourbyteint: 1,
+            is_eightdouble: 1,
+            basereg: 5,
+            ..Default::default()
+        };
+        let manual = MetrowerksObject::assemble(
+            names.to_vec(),
+            obj.symbols().cloned(),
+            obj.hunks().clone(),
+            header,
+        )
+        .unwrap();
+
+        let built = MetrowerksObjectBuilder::new()
+            .name(1, names[0].name())
+            .next_name(names[1].name())
+            .next_name(names[2].name())
+            .fourbyteint(true)
+            .eightdouble(true)
+            .basereg(BaseRegister::A5)
+            .hunks(obj.hunks().clone())
+            .symbols(obj.symbols().unwrap().clone())
+            .build()
+            .unwrap();
+
+        assert_eq!(names, built.names());
+        let (mut a, mut b): (Vec<u8>, Vec<u8>) = (vec![], vec![]);
+        manual.serialize_out(&mut a).unwrap();
+        built.serialize_out(&mut b).unwrap();
+        assert_eq!(a, b);
+
+        let problems = MetrowerksObjectBuilder::new()
+            .name(1, "add")
+            .name(1, "add")
+            .hunks(obj.hunks().clone())
+            .build()
+            .unwrap_err();
+        assert_eq!(1, problems.len());
+        assert!(problems[0].to_string().contains("Duplicate name id: 1"));
+
+        // Nothing to start or end the hunks
+        assert!(MetrowerksObjectBuilder::default().build().is_err());
+    }
+
+    #[test]
+    fn test_insert_and_remove_hunks() {
+        let original = load_object("test/data/add.lib.metro");
+        let mut obj = original.clone();
+        let code = obj.hunks()[1].clone();
+        let code_len = obj.hunks().code_length();
+
+        obj.insert_hunk(2, code.clone()).unwrap();
+        assert_eq!(4, obj.hunks().len());
+        assert_eq!(2 * code_len, obj.header().code_size() as usize);
+        obj.integrity_check().unwrap();
+
+        let mut out: Vec<u8> = vec![];
+        obj.serialize_out(&mut out).unwrap();
+        let reread = MetrowerksObject::try_from(out.as_slice()).unwrap();
+        assert_eq!(2 * code_len, reread.hunks().code_length());
+
+        // Nothing goes before Start or after End, and they stay put
+        assert!(obj.insert_hunk(0, code.clone()).is_err());
+        assert!(obj.insert_hunk(5, code.clone()).is_err());
+        let start = obj.hunks()[0].clone();
+        assert!(obj.insert_hunk(1, start).is_err());
+        assert!(obj.remove_hunk(0).is_err());
+        assert!(obj.remove_hunk(3).is_err());
+        assert_eq!(4, obj.hunks().len());
+
+        assert_eq!(code, obj.remove_hunk(2).unwrap());
+        assert_eq!(original, obj);
+    }
+
+    #[test]
+    fn test_target_model() {
+        // Just a Start and an End hunk
+        let empty = CodeHunks::try_from([0x45, 0x67, 0x45, 0x68].as_slice()).unwrap();
+        let build = |fourbyteint: bool, eightdouble: bool, mc68881: bool| {
+            MetrowerksObjectBuilder::new()
+                .hunks(empty.clone())
+                .fourbyteint(fourbyteint)
+                .eightdouble(eightdouble)
+                .mc68881(mc68881)
+                .build()
+                .unwrap()
+                .target_model()
+        };
+
+        let model = build(true, true, false);
+        assert_eq!(TargetModel::default(), model);
+
+        let model = build(false, false, true);
+        assert_eq!(2, model.int_size);
+        assert_eq!(12, model.double_size);
+        assert!(model.mc68881);
+        assert_eq!(10, build(false, false, false).double_size);
+
+        let long = crate::types_m68k::BasicDataType::BasicTypeLong;
+        assert_eq!(Some(4), long.size_in(&build(true, true, false)));
+        assert_eq!(Some(2), long.size_in(&model));
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        for path in [
+            "test/data/add.lib.metro",
+            "test/data/two_funcs.lib.metro",
+            "test/data/set_volume_ex.lib.metro",
+        ] {
+            let bytes = load_object_bytes(path);
+            let obj = MetrowerksObject::try_from(bytes.as_slice()).unwrap();
+
+            let mut out: Vec<u8> = vec![];
+            obj.serialize_out(&mut out).unwrap();
+            assert_eq!(bytes, out, "{}", path);
+
+            let reparsed = MetrowerksObject::try_from(out.as_slice()).unwrap();
+            assert_eq!(obj, reparsed, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_serialize_recomputes_header() {
+        let mut obj = load_object("test/data/two_funcs.lib.metro");
+        obj.header.is_fourbyteint = 1;
+        obj.header.basereg = 2;
+        obj.push_name(NameEntry::new(5, "extra")).unwrap();
+
+        let mut out: Vec<u8> = vec![];
+        obj.serialize_out(&mut out).unwrap();
+        let reparsed = MetrowerksObject::try_from(out.as_slice()).unwrap();
+
+        let h = reparsed.header();
+        assert_eq!((1, 2), (h.is_fourbyteint(), h.basereg()));
+        assert_eq!(5, h.nametable_count());
+        let names_len: usize = reparsed.names().iter().map(|x| x.entry_len()).sum();
+        assert_eq!(out.len(), (h.nametable_start() + names_len + 1) & !1);
+        assert_eq!(
+            Some("extra"),
+            reparsed.name_by_id(5).map(|x| x.name().as_str())
+        );
+    }
+
+    #[test]
+    fn test_serialize_name_table() {
+        let mut ob = load_object("test/data/two_funcs.lib.metro");
+        // Leave the names out of id order
+        let removed = ob.remove_name_by_id(2).unwrap();
+        ob.push_name(NameEntry::new(5, "sum")).unwrap();
+        ob.push_name(removed).unwrap();
+
+        let mut table: Vec<u8> = vec![];
+        ob.serialize_name_table(&mut table).unwrap();
+        let mut expected = ob.names().to_vec();
+        expected.sort_by_key(|x| x.id());
+        assert_eq!(
+            expected.iter().map(|x| x.entry_len()).sum::<usize>(),
+            table.len()
+        );
+        assert_eq!(&expected[0].computed_hash().to_be_bytes(), &table[0..2]);
+
+        let mut out: Vec<u8> = vec![];
+        ob.serialize_out(&mut out).unwrap();
+        let reparsed = MetrowerksObject::try_from(out.as_slice()).unwrap();
+        assert_eq!(expected, reparsed.names());
+
+        ob.push_name(NameEntry::new(6, &"x".repeat(256))).unwrap();
+        assert!(ob.serialize_name_table(&mut vec![]).is_err());
+
+        ob.remove_name_by_id(6);
+        ob.remove_name_by_id(3);
+        assert!(ob.serialize_name_table(&mut vec![]).is_err());
+
+        for id in [1, 2, 4, 5] {
+            ob.remove_name_by_id(id);
+        }
+        let mut table: Vec<u8> = vec![];
+        ob.serialize_name_table(&mut table).unwrap();
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn test_assemble_reports_problems() {
+        let obj = load_object("test/data/two_funcs.lib.metro");
+
+        // Drop "add" and give one of the routines away
+        let mut symtab = obj.symbols().unwrap().clone();
+        symtab.remove_routine(1);
+
+        let problems = MetrowerksObject::assemble(
+            obj.names()[1..].to_vec(),
+            Some(symtab),
+            obj.hunks().clone(),
+            ObjectHeader::default(),
+        )
+        .unwrap_err();
+
+        assert_eq!(3, problems.len(), "{:?}", problems);
+        assert!(problems[0].to_string().contains("without gaps"));
+        assert!(problems[1]
+            .to_string()
+            .contains("2 code hunks have symbols"));
+        assert!(problems[2].to_string().contains("missing name id: 1"));
+    }
+
+    #[test]
+    fn test_assemble_validates() {
+        use crate::code_m68k::{ObjDataHunk, ObjSimpleHunk};
+
+        // Uninitialized data holding bytes, and no End hunk
+        let hunks = CodeHunks::from(vec![
+            Hunk::from(HunkType::Start(ObjSimpleHunk {})),
+            Hunk::from(HunkType::LocalUninitializedData(
+                ObjDataHunk::new_initialized(1, 0, 0, &[1, 2]),
+            )),
+        ]);
+        let names = vec![NameEntry::new(1, "buffer")];
+
+        let problems =
+            MetrowerksObject::assemble(names.clone(), None, hunks.clone(), ObjectHeader::default())
+                .unwrap_err();
+        assert_eq!(2, problems.len(), "{:?}", problems);
+        assert!(problems[0].to_string().contains("End hunk"));
+
+        // The same checks, on an object that skipped them, whose header
+        // also disagrees with its sizes and names
+        let obj = MetrowerksObject::from_parts(ObjectHeader::default(), names, None, hunks);
+        let unchecked = obj.validate().unwrap_err();
+        assert_eq!(4, unchecked.len(), "{:?}", unchecked);
+        assert_eq!(problems[0].to_string(), unchecked[0].to_string());
+        assert!(load_object("test/data/two_funcs.lib.metro")
+            .validate()
+            .is_ok());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        for path in [
+            "test/data/add.lib.metro",
+            "test/data/two_funcs.lib.metro",
+            "test/data/set_volume_ex.lib.metro",
+        ] {
+            let bytes = load_object_bytes(path);
+            let obj = MetrowerksObject::try_from(bytes.as_slice()).unwrap();
+
+            let json = serde_json::to_string(&obj).unwrap();
+            let back: MetrowerksObject = serde_json::from_str(&json).unwrap();
+            assert_eq!(obj, back, "{}", path);
+            assert_eq!(json, serde_json::to_string(&back).unwrap(), "{}", path);
+
+            let mut out: Vec<u8> = vec![];
+            back.serialize_out(&mut out).unwrap();
+            assert_eq!(bytes, out, "{}", path);
+        }
+    }
+}
@@ -0,0 +1,619 @@
+use crate::error::LibmetroError;
+
+use super::util::{convert_be_u16, convert_be_u32, decode_mac_roman, read_c_string};
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PefMagicWord {
+    Tag1 = 0x4a6f7921, // 'Joy!'
+    Tag2 = 0x70656666, // 'peff'
+}
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PefArchitecture {
+    Unknown = 0,
+    PowerPC = 0x70777063, // 'pwpc'
+    M68k = 0x6d36386b,    // 'm68k'
+}
+
+impl From<u32> for PefArchitecture {
+    fn from(value: u32) -> Self {
+        match value {
+            x if x == PefArchitecture::PowerPC as u32 => PefArchitecture::PowerPC,
+            x if x == PefArchitecture::M68k as u32 => PefArchitecture::M68k,
+            _ => PefArchitecture::Unknown,
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PefSectionKind {
+    Code = 0,
+    UnpackedData,
+    PatternInitData,
+    ConstData,
+    Loader,
+    DebugReserved,
+    ExecutableData,
+    Exception,
+    Traceback,
+    Unknown = 0xff,
+}
+
+impl From<u8> for PefSectionKind {
+    fn from(value: u8) -> Self {
+        match value {
+            x if x == PefSectionKind::Code as u8 => PefSectionKind::Code,
+            x if x == PefSectionKind::UnpackedData as u8 => PefSectionKind::UnpackedData,
+            x if x == PefSectionKind::PatternInitData as u8 => PefSectionKind::PatternInitData,
+            x if x == PefSectionKind::ConstData as u8 => PefSectionKind::ConstData,
+            x if x == PefSectionKind::Loader as u8 => PefSectionKind::Loader,
+            x if x == PefSectionKind::DebugReserved as u8 => PefSectionKind::DebugReserved,
+            x if x == PefSectionKind::ExecutableData as u8 => PefSectionKind::ExecutableData,
+            x if x == PefSectionKind::Exception as u8 => PefSectionKind::Exception,
+            x if x == PefSectionKind::Traceback as u8 => PefSectionKind::Traceback,
+            _ => PefSectionKind::Unknown,
+        }
+    }
+}
+
+fn slice(data: &[u8], start: usize, len: usize) -> Result<&[u8], LibmetroError> {
+    let end = start.saturating_add(len);
+    data.get(start..end).ok_or(LibmetroError::Truncated {
+        needed: end,
+        available: data.len(),
+    })
+}
+
+fn read_u16(data: &[u8], at: usize) -> Result<u16, LibmetroError> {
+    Ok(convert_be_u16(&slice(data, at, 2)?.try_into().unwrap()))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<u32, LibmetroError> {
+    Ok(convert_be_u32(&slice(data, at, 4)?.try_into().unwrap()))
+}
+
+fn read_string(data: &[u8], at: usize) -> Result<String, LibmetroError> {
+    let bytes = data.get(at..).ok_or_else(|| {
+        LibmetroError::Malformed(format!("String offset out of range, got: {}", at))
+    })?;
+
+    read_c_string(bytes)
+        .map_err(|_| LibmetroError::Malformed(format!("Unterminated string at {}", at)))
+}
+
+#[derive(Debug, Clone)]
+pub struct PefSection {
+    name: Option<String>,
+    kind: PefSectionKind,
+    default_address: u32,
+    total_size: u32,
+    unpacked_size: u32,
+    share_kind: u8,
+    alignment: u8,
+    data: Vec<u8>,
+}
+
+impl PefSection {
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn kind(&self) -> PefSectionKind {
+        self.kind
+    }
+
+    pub fn default_address(&self) -> u32 {
+        self.default_address
+    }
+
+    pub fn total_size(&self) -> u32 {
+        self.total_size
+    }
+
+    pub fn unpacked_size(&self) -> u32 {
+        self.unpacked_size
+    }
+
+    pub fn share_kind(&self) -> u8 {
+        self.share_kind
+    }
+
+    /// Alignment as a power of two.
+    pub fn alignment(&self) -> u8 {
+        self.alignment
+    }
+
+    /// The section's bytes as stored in the container.
+    ///
+    /// Pattern-initialized data is not expanded.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PefImportedLibrary {
+    name: String,
+    old_imp_version: u32,
+    current_version: u32,
+    first_symbol: u32,
+    symbol_count: u32,
+    options: u8,
+}
+
+impl PefImportedLibrary {
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn old_imp_version(&self) -> u32 {
+        self.old_imp_version
+    }
+
+    pub fn current_version(&self) -> u32 {
+        self.current_version
+    }
+
+    /// Index of this library's first entry in `PefLoader::imported_symbols`.
+    pub fn first_symbol(&self) -> u32 {
+        self.first_symbol
+    }
+
+    pub fn symbol_count(&self) -> u32 {
+        self.symbol_count
+    }
+
+    pub fn is_weak(&self) -> bool {
+        self.options & 0x40 != 0
+    }
+
+    pub fn is_init_before(&self) -> bool {
+        self.options & 0x80 != 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PefSymbol {
+    name: String,
+    class: u8,
+}
+
+impl PefSymbol {
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Symbol class: 0 code, 1 data, 2 transition vector, 3 TOC, 4 glue.
+    pub fn class(&self) -> u8 {
+        self.class & 0x0f
+    }
+
+    pub fn is_weak(&self) -> bool {
+        self.class & 0x80 != 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PefExportedSymbol {
+    symbol: PefSymbol,
+    value: u32,
+    section: i16,
+}
+
+impl PefExportedSymbol {
+    pub fn name(&self) -> &str {
+        self.symbol.name()
+    }
+
+    pub fn class(&self) -> u8 {
+        self.symbol.class()
+    }
+
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+
+    /// Section index the value is relative to, or negative for absolute/re-exported symbols.
+    pub fn section(&self) -> i16 {
+        self.section
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PefLoader {
+    main_section: i32,
+    main_offset: u32,
+    init_section: i32,
+    init_offset: u32,
+    term_section: i32,
+    term_offset: u32,
+    imported_libraries: Vec<PefImportedLibrary>,
+    imported_symbols: Vec<PefSymbol>,
+    exported_symbols: Vec<PefExportedSymbol>,
+}
+
+impl PefLoader {
+    pub fn main(&self) -> Option<(i32, u32)> {
+        (self.main_section >= 0).then_some((self.main_section, self.main_offset))
+    }
+
+    pub fn init(&self) -> Option<(i32, u32)> {
+        (self.init_section >= 0).then_some((self.init_section, self.init_offset))
+    }
+
+    pub fn term(&self) -> Option<(i32, u32)> {
+        (self.term_section >= 0).then_some((self.term_section, self.term_offset))
+    }
+
+    pub fn imported_libraries(&self) -> &[PefImportedLibrary] {
+        &self.imported_libraries
+    }
+
+    pub fn imported_symbols(&self) -> &[PefSymbol] {
+        &self.imported_symbols
+    }
+
+    /// Symbols imported from the given library.
+    pub fn symbols_for(&self, library: &PefImportedLibrary) -> &[PefSymbol] {
+        let start = library.first_symbol as usize;
+        let end = start + library.symbol_count as usize;
+
+        self.imported_symbols.get(start..end).unwrap_or_default()
+    }
+
+    pub fn exported_symbols(&self) -> &[PefExportedSymbol] {
+        &self.exported_symbols
+    }
+}
+
+impl TryFrom<&[u8]> for PefLoader {
+    type Error = LibmetroError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let main_section = read_u32(value, 0)? as i32;
+        let main_offset = read_u32(value, 4)?;
+        let init_section = read_u32(value, 8)? as i32;
+        let init_offset = read_u32(value, 12)?;
+        let term_section = read_u32(value, 16)? as i32;
+        let term_offset = read_u32(value, 20)?;
+        let library_count = read_u32(value, 24)? as usize;
+        let import_count = read_u32(value, 28)? as usize;
+        let reloc_section_count = read_u32(value, 32)? as usize;
+        let strings_offset = read_u32(value, 40)? as usize;
+        let hash_offset = read_u32(value, 44)? as usize;
+        let hash_power = read_u32(value, 48)?;
+        let export_count = read_u32(value, 52)? as usize;
+
+        if hash_power > 31 {
+            return Err(LibmetroError::Malformed(format!(
+                "Bad export hash table power, got: {}",
+                hash_power
+            )));
+        }
+
+        let strings = value.get(strings_offset..).ok_or_else(|| {
+            LibmetroError::Malformed(format!("Bad loader string offset, got: {}", strings_offset))
+        })?;
+
+        // Imported libraries directly follow the 56 byte loader header
+        let mut imported_libraries: Vec<PefImportedLibrary> = vec![];
+        let mut at = 56;
+        for _idx in 0..library_count {
+            let entry = slice(value, at, 24)?;
+
+            imported_libraries.push(PefImportedLibrary {
+                name: read_string(strings, read_u32(entry, 0)? as usize)?,
+                old_imp_version: read_u32(entry, 4)?,
+                current_version: read_u32(entry, 8)?,
+                symbol_count: read_u32(entry, 12)?,
+                first_symbol: read_u32(entry, 16)?,
+                options: entry[20],
+            });
+
+            at += 24;
+        }
+
+        let mut imported_symbols: Vec<PefSymbol> = vec![];
+        for _idx in 0..import_count {
+            let class_and_name = read_u32(value, at)?;
+
+            imported_symbols.push(PefSymbol {
+                name: read_string(strings, (class_and_name & 0x00ff_ffff) as usize)?,
+                class: (class_and_name >> 24) as u8,
+            });
+
+            at += 4;
+        }
+
+        // Skip the relocation headers, they aren't needed to name anything
+        slice(value, at, reloc_section_count * 12)?;
+
+        // The export hash table is followed by the key table then the symbol table
+        let keys_offset = hash_offset + (4usize << hash_power);
+        let symbols_offset = keys_offset + (export_count * 4);
+
+        let mut exported_symbols: Vec<PefExportedSymbol> = vec![];
+        for idx in 0..export_count {
+            let name_len = read_u16(value, keys_offset + (idx * 4))? as usize;
+            let entry = slice(value, symbols_offset + (idx * 10), 10)?;
+            let class_and_name = read_u32(entry, 0)?;
+            let name_offset = (class_and_name & 0x00ff_ffff) as usize;
+
+            let name = slice(strings, name_offset, name_len)?;
+
+            exported_symbols.push(PefExportedSymbol {
+                symbol: PefSymbol {
+                    name: decode_mac_roman(name),
+                    class: (class_and_name >> 24) as u8,
+                },
+                value: read_u32(entry, 4)?,
+                section: read_u16(entry, 8)? as i16,
+            });
+        }
+
+        Ok(PefLoader {
+            main_section,
+            main_offset,
+            init_section,
+            init_offset,
+            term_section,
+            term_offset,
+            imported_libraries,
+            imported_symbols,
+            exported_symbols,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PefContainer {
+    architecture: PefArchitecture,
+    format_version: u32,
+    date_time_stamp: u32,
+    old_def_version: u32,
+    old_imp_version: u32,
+    current_version: u32,
+    instantiated_sections: u16,
+    sections: Vec<PefSection>,
+    loader: Option<PefLoader>,
+}
+
+impl PefContainer {
+    pub fn architecture(&self) -> PefArchitecture {
+        self.architecture
+    }
+
+    pub fn format_version(&self) -> u32 {
+        self.format_version
+    }
+
+    pub fn date_time_stamp(&self) -> u32 {
+        self.date_time_stamp
+    }
+
+    pub fn old_def_version(&self) -> u32 {
+        self.old_def_version
+    }
+
+    pub fn old_imp_version(&self) -> u32 {
+        self.old_imp_version
+    }
+
+    pub fn current_version(&self) -> u32 {
+        self.current_version
+    }
+
+    pub fn instantiated_sections(&self) -> u16 {
+        self.instantiated_sections
+    }
+
+    pub fn sections(&self) -> &[PefSection] {
+        &self.sections
+    }
+
+    pub fn loader(&self) -> Option<&PefLoader> {
+        self.loader.as_ref()
+    }
+
+    /// Finds the imported library a CFM import container hunk refers to.
+    pub fn imported_library(&self, name: &str) -> Option<&PefImportedLibrary> {
+        self.loader
+            .as_ref()?
+            .imported_libraries
+            .iter()
+            .find(|x| x.name == name)
+    }
+
+    /// Finds the exported symbol a CFM export refers to.
+    pub fn exported_symbol(&self, name: &str) -> Option<&PefExportedSymbol> {
+        self.loader
+            .as_ref()?
+            .exported_symbols
+            .iter()
+            .find(|x| x.name() == name)
+    }
+}
+
+impl TryFrom<&[u8]> for PefContainer {
+    type Error = LibmetroError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let tag1 = read_u32(value, 0)?;
+        let tag2 = read_u32(value, 4)?;
+
+        // Reports whichever of the two magic words is wrong, 'Joy!' first
+        for (expected, found) in [
+            (PefMagicWord::Tag1 as u32, tag1),
+            (PefMagicWord::Tag2 as u32, tag2),
+        ] {
+            if found != expected {
+                return Err(LibmetroError::BadMagic { expected, found });
+            }
+        }
+
+        let architecture = PefArchitecture::from(read_u32(value, 8)?);
+        let format_version = read_u32(value, 12)?;
+        let date_time_stamp = read_u32(value, 16)?;
+        let old_def_version = read_u32(value, 20)?;
+        let old_imp_version = read_u32(value, 24)?;
+        let current_version = read_u32(value, 28)?;
+        let section_count = read_u16(value, 32)? as usize;
+        let instantiated_sections = read_u16(value, 34)?;
+
+        // Section names are stored after the last section header
+        let names_offset = 40 + (section_count * 28);
+
+        let mut sections: Vec<PefSection> = vec![];
+        let mut loader: Option<PefLoader> = None;
+        for idx in 0..section_count {
+            let header = slice(value, 40 + (idx * 28), 28)?;
+
+            let name_offset = read_u32(header, 0)? as i32;
+            let container_length = read_u32(header, 16)? as usize;
+            let container_offset = read_u32(header, 20)? as usize;
+            let kind = PefSectionKind::from(header[24]);
+
+            let name = if name_offset < 0 {
+                None
+            } else {
+                Some(read_string(value, names_offset + name_offset as usize)?)
+            };
+
+            let data = slice(value, container_offset, container_length)?;
+
+            if kind == PefSectionKind::Loader {
+                loader = Some(PefLoader::try_from(data)?);
+            }
+
+            sections.push(PefSection {
+                name,
+                kind,
+                default_address: read_u32(header, 4)?,
+                total_size: read_u32(header, 8)?,
+                unpacked_size: read_u32(header, 12)?,
+                share_kind: header[25],
+                alignment: header[26],
+                data: data.to_vec(),
+            });
+        }
+
+        Ok(PefContainer {
+            architecture,
+            format_version,
+            date_time_stamp,
+            old_def_version,
+            old_imp_version,
+            current_version,
+            instantiated_sections,
+            sections,
+            loader,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A container with one code section and a loader importing `NewPtr` from
+    // `InterfaceLib` and exporting `main`.
+    fn sample_container() -> Vec<u8> {
+        let code: Vec<u8> = vec![0x4e, 0x80, 0x00, 0x20];
+
+        let strings = b"InterfaceLib\0NewPtr\0main".to_vec();
+        let mut loader: Vec<u8> = vec![];
+        for v in [0xffffffffu32, 0, 0xffffffff, 0, 0xffffffff, 0, 1, 1, 0, 0] {
+            loader.extend(v.to_be_bytes());
+        }
+        let strings_offset = 56 + 24 + 4;
+        let hash_offset = strings_offset + strings.len() as u32;
+        for v in [strings_offset, hash_offset, 0, 1] {
+            loader.extend(v.to_be_bytes());
+        }
+        // Imported library and symbol
+        for v in [0u32, 0, 0, 1, 0, 0] {
+            loader.extend(v.to_be_bytes());
+        }
+        loader.extend((0x02000000u32 | 13).to_be_bytes());
+        loader.extend(&strings);
+        // Hash table (1 slot), key table, and symbol table
+        loader.extend(0u32.to_be_bytes());
+        loader.extend((4u32 << 16).to_be_bytes());
+        loader.extend((0x02000000u32 | 20).to_be_bytes());
+        loader.extend(0x10u32.to_be_bytes());
+        loader.extend(0u16.to_be_bytes());
+
+        let names = b"code\0".to_vec();
+        let code_offset = 40 + (2 * 28) + names.len() as u32;
+        let loader_offset = code_offset + code.len() as u32;
+
+        let mut data: Vec<u8> = vec![];
+        for v in [0x4a6f7921u32, 0x70656666, 0x70777063, 1, 0, 0, 0, 0] {
+            data.extend(v.to_be_bytes());
+        }
+        data.extend(2u16.to_be_bytes());
+        data.extend(1u16.to_be_bytes());
+        data.extend(0u32.to_be_bytes());
+
+        for (name, len, offset, kind) in [
+            (0u32, code.len() as u32, code_offset, 0u8),
+            (0xffffffff, loader.len() as u32, loader_offset, 4),
+        ] {
+            for v in [name, 0, len, len, len, offset] {
+                data.extend(v.to_be_bytes());
+            }
+            data.extend([kind, 0, 4, 0]);
+        }
+
+        data.extend(&names);
+        data.extend(&code);
+        data.extend(&loader);
+        data
+    }
+
+    #[test]
+    fn test_pef_container() {
+        let data = sample_container();
+        let pef = PefContainer::try_from(data.as_ref()).unwrap();
+
+        assert_eq!(PefArchitecture::PowerPC, pef.architecture());
+        assert_eq!(2, pef.sections().len());
+        assert_eq!(Some("code"), pef.sections()[0].name());
+        assert_eq!(PefSectionKind::Code, pef.sections()[0].kind());
+        assert_eq!(&[0x4e, 0x80, 0x00, 0x20], pef.sections()[0].data());
+
+        let loader = pef.loader().unwrap();
+        assert_eq!(None, loader.main());
+
+        let lib = pef.imported_library("InterfaceLib").unwrap();
+        let syms = loader.symbols_for(lib);
+        assert_eq!(1, syms.len());
+        assert_eq!("NewPtr", syms[0].name());
+        assert_eq!(2, syms[0].class());
+
+        let main = pef.exported_symbol("main").unwrap();
+        assert_eq!(0x10, main.value());
+        assert_eq!(0, main.section());
+    }
+
+    #[test]
+    fn test_pef_mac_roman_names() {
+        let mut data = sample_container();
+        // 0x8a is ä in MacRoman, and not valid UTF-8 on its own
+        let at = data.windows(4).rposition(|x| x == b"main").unwrap();
+        data[at + 1] = 0x8a;
+
+        let pef = PefContainer::try_from(data.as_ref()).unwrap();
+        assert_eq!(0x10, pef.exported_symbol("mäin").unwrap().value());
+    }
+
+    #[test]
+    fn test_pef_truncated() {
+        let data = sample_container();
+        assert!(PefContainer::try_from(&data[..data.len() - 4]).is_err());
+        assert!(PefContainer::try_from(&data[..20]).is_err());
+    }
+}
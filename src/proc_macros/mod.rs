@@ -22,8 +22,14 @@ fn impl_name_macro(ast: &syn::DeriveInput) -> TokenStream {
     let name = &ast.ident;
     let gen = quote! {
         impl<'a> NameIdFromObject<'a> for #name {
-            fn name(&'a self, obj: &'a crate::objects_m68k::MetrowerksObject) -> &str {
-                obj.names()[(self.name_id + 1) as usize].name().as_str()
+            fn name_id(&self) -> u32 {
+                self.name_id
+            }
+
+            fn name(&'a self, obj: &'a crate::objects_m68k::MetrowerksObject) -> &'a str {
+                obj.name_by_id(self.name_id)
+                    .map(|x| x.name().as_str())
+                    .unwrap_or_default()
             }
         }
     };
@@ -1,7 +1,9 @@
 use std::fmt::Debug;
+use std::io::Write;
 
+use crate::error::LibmetroError;
 use crate::types_m68k::TypeTable;
-use crate::util::{convert_be_i32, RawLength};
+use crate::util::{check_len, convert_be_i32, RawLength, Serializable};
 
 use super::types_m68k::{DataType, TypeDefinition};
 
@@ -12,7 +14,8 @@ pub enum SymTableMagicWord {
     SymTableMagicWord = 0x53594D48,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StatementLocation {
     offset: i32,
     source_offset: u32,
@@ -48,8 +51,19 @@ impl From<&[u8]> for StatementLocation {
     }
 }
 
+impl Serializable for StatementLocation {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        let mut out: Vec<u8> = vec![];
+        out.extend(self.offset.to_be_bytes());
+        out.extend(self.source_offset.to_be_bytes());
+
+        Ok(w.write_all(&out)?)
+    }
+}
+
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StorageKind {
     Local = 0,
     Value,
@@ -57,7 +71,7 @@ pub enum StorageKind {
 }
 
 impl TryFrom<u8> for StorageKind {
-    type Error = &'static str;
+    type Error = LibmetroError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         Ok(match value {
@@ -65,7 +79,7 @@ impl TryFrom<u8> for StorageKind {
             x if x == StorageKind::Value as u8 => StorageKind::Value,
             x if x == StorageKind::Reference as u8 => StorageKind::Reference,
             _ => {
-                return Err("Bad Storage Kind");
+                return Err(LibmetroError::BadStorageKind(value));
             }
         })
     }
@@ -73,6 +87,7 @@ impl TryFrom<u8> for StorageKind {
 
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StorageClass {
     Register = 0,
     A5,
@@ -81,7 +96,7 @@ pub enum StorageClass {
 }
 
 impl TryFrom<u8> for StorageClass {
-    type Error = &'static str;
+    type Error = LibmetroError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         Ok(match value {
@@ -91,13 +106,14 @@ impl TryFrom<u8> for StorageClass {
             x if x == StorageClass::A7 as u8 => StorageClass::A7,
 
             _ => {
-                return Err("Bad Storage Kind");
+                return Err(LibmetroError::BadStorageClass(value));
             }
         })
     }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LocalVar {
     name_id: u32,
     var_type: DataType,
@@ -106,21 +122,37 @@ pub struct LocalVar {
     wher: u32, // TODO: Integrate this into the sclass
 }
 
-impl From<&[u8]> for LocalVar {
-    fn from(value: &[u8]) -> Self {
+impl TryFrom<&[u8]> for LocalVar {
+    type Error = LibmetroError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        check_len(value, 14)?;
         let name_id = convert_be_u32(value[0..4].try_into().unwrap());
         let var_type = convert_be_u32(value[4..8].try_into().unwrap());
-        let kind = StorageKind::try_from(value[8]).unwrap();
-        let sclass = StorageClass::try_from(value[9]).unwrap();
+        let kind = StorageKind::try_from(value[8])?;
+        let sclass = StorageClass::try_from(value[9])?;
         let wher = convert_be_u32(value[10..14].try_into().unwrap());
 
-        Self {
+        Ok(Self {
             name_id: name_id,
             var_type: DataType::from(var_type),
             kind: kind,
             sclass: sclass,
             wher: wher,
-        }
+        })
+    }
+}
+
+impl Serializable for LocalVar {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        let mut out: Vec<u8> = vec![];
+        out.extend(self.name_id.to_be_bytes());
+        out.extend(u32::try_from(&self.var_type)?.to_be_bytes());
+        out.push(self.kind as u8);
+        out.push(self.sclass as u8);
+        out.extend(self.wher.to_be_bytes());
+
+        Ok(w.write_all(&out)?)
     }
 }
 
@@ -147,13 +179,15 @@ impl LocalVar {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RoutineType {
     Procedure = 0,
     Function = 1,
     Unknown = 0xffff,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Routine {
     typ: RoutineType,
     statement_locations: Vec<StatementLocation>,
@@ -161,7 +195,7 @@ pub struct Routine {
 }
 
 impl TryFrom<&[u8]> for Routine {
-    type Error = String;
+    type Error = LibmetroError;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
         let mut data = value;
@@ -169,29 +203,34 @@ impl TryFrom<&[u8]> for Routine {
         let mut local_vars: Vec<LocalVar> = vec![];
 
         // Get routine type
+        check_len(data, 2)?;
         let routine_type = convert_be_u16(&data[0..2].try_into().unwrap());
         let typ = match routine_type {
             x if x == RoutineType::Procedure as u16 => RoutineType::Procedure,
             x if x == RoutineType::Function as u16 => RoutineType::Function,
+            // Some compiler versions write the sentinel for routines of no known kind
+            x if x == RoutineType::Unknown as u16 => RoutineType::Unknown,
             _ => {
-                return Err(format!("Bad Routine Type: got {}", routine_type));
+                return Err(LibmetroError::BadRoutineType(routine_type));
             }
         };
 
         data = &data[2..];
         let mut eol = false;
         while !eol {
+            check_len(data, 8)?;
             let statement_loc = StatementLocation::from(data);
             data = &data[statement_loc.raw_length()..];
             eol = statement_loc.is_end_of_list();
             statement_locs.push(statement_loc);
         }
 
+        check_len(data, 2)?;
         let mut remaining_local_vars = convert_be_u16(&data[0..2].try_into().unwrap());
         data = &data[2..];
 
         while remaining_local_vars != 0 {
-            let local = LocalVar::from(data);
+            let local = LocalVar::try_from(data)?;
             data = &data[local.raw_length()..];
 
             local_vars.push(local);
@@ -207,6 +246,44 @@ impl TryFrom<&[u8]> for Routine {
     }
 }
 
+/// Statement locations are written as held, so the list must already end with
+/// the `-1` terminator the parser stops on.
+impl Serializable for Routine {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        let typ = match self.typ {
+            RoutineType::Procedure => RoutineType::Procedure as u16,
+            RoutineType::Function => RoutineType::Function as u16,
+            RoutineType::Unknown => RoutineType::Unknown as u16,
+        };
+        if !self
+            .statement_locations
+            .last()
+            .is_some_and(|x| x.is_end_of_list())
+        {
+            return Err(LibmetroError::Invalid(
+                "Routine statement locations must end with -1".to_owned(),
+            ));
+        }
+        let num_locals = u16::try_from(self.local_vars.len()).map_err(|_| {
+            LibmetroError::Invalid(format!(
+                "Routine has too many locals: {}",
+                self.local_vars.len()
+            ))
+        })?;
+
+        w.write_all(&typ.to_be_bytes())?;
+        for loc in self.statement_locations.iter() {
+            loc.serialize_out(w)?;
+        }
+        w.write_all(&num_locals.to_be_bytes())?;
+        for local in self.local_vars.iter() {
+            local.serialize_out(w)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl Routine {
     pub fn statement_locations(&self) -> &[StatementLocation] {
         self.statement_locations.as_slice()
@@ -224,6 +301,51 @@ impl Routine {
         self.typ == RoutineType::Function
     }
 
+    /// The statement covering `obj_offset` into the routine's code: the last
+    /// location whose offset is at or before it.
+    ///
+    /// The `-1` end-of-list terminator is never returned, so an offset past the
+    /// last statement still maps to it, and one before the first maps to `None`.
+    pub fn locate(&self, obj_offset: i32) -> Option<&StatementLocation> {
+        self.statement_locations
+            .iter()
+            .rev()
+            .find(|x| !x.is_end_of_list() && x.offset <= obj_offset)
+    }
+
+    /// Compares what two routines describe rather than how they were laid out.
+    ///
+    /// The routine types must match. Statement locations are compared as a set,
+    /// ignoring their order and the `-1` end-of-list terminator. Local variables
+    /// are compared as a set of (name id, type, storage kind, storage class);
+    /// their `wher` location is ignored.
+    pub fn structurally_eq(&self, other: &Self) -> bool {
+        let statements = |r: &Routine| {
+            let mut v: Vec<(i32, u32)> = r
+                .statement_locations
+                .iter()
+                .filter(|x| !x.is_end_of_list())
+                .map(|x| (x.offset, x.source_offset))
+                .collect();
+            v.sort();
+            v
+        };
+
+        let locals = |r: &Routine| {
+            let mut v: Vec<(u32, DataType, StorageKind, StorageClass)> = r
+                .local_vars
+                .iter()
+                .map(|x| (x.name_id, x.var_type.clone(), x.kind, x.sclass))
+                .collect();
+            v.sort();
+            v
+        };
+
+        self.typ == other.typ
+            && statements(self) == statements(other)
+            && locals(self) == locals(other)
+    }
+
     fn raw_length(&self) -> usize {
         4 + self
             .statement_locations
@@ -239,11 +361,11 @@ impl Routine {
 }
 
 fn convert_reserved(data: &[u8; 16]) -> [u32; 4] {
-    let res: [u32; 4] = unsafe { std::mem::transmute(*data) };
-    res.map(|v| u32::from_be(v))
+    [0, 4, 8, 12].map(|x| convert_be_u32(&data[x..x + 4].try_into().unwrap()))
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SymbolTable {
     unnamed: u32, // CVW: This may be resolvable where 'name_id == 0' in type table entries.
     reserved: [u32; 4],
@@ -266,21 +388,48 @@ impl SymbolTable {
         &self.types
     }
 
-    pub fn routine_at_offset(&self, offset: usize) -> &Routine {
-        let mut i = 0;
-        let mut off = offset;
+    pub fn type_table(&self) -> &TypeTable {
+        &self.types
+    }
 
-        // Remove the Symtab header
-        off -= 32;
+    pub fn push_routine(&mut self, routine: Routine) {
+        self.routines.push(routine);
+    }
 
-        let mut iter = self.routines.iter();
-        while off > 0 {
-            let r = iter.next().unwrap();
-            off -= r.raw_length();
-            i += 1;
+    pub fn remove_routine(&mut self, index: usize) -> Option<Routine> {
+        if index < self.routines.len() {
+            Some(self.routines.remove(index))
+        } else {
+            None
         }
+    }
+
+    /// Each routine paired with its byte offset from the start of the symbol table.
+    ///
+    /// These are the values code hunks store in their `sym_offset`.
+    pub fn routine_offsets(&self) -> Vec<(usize, &Routine)> {
+        // Routines follow the 32 byte Symtab header back to back
+        let mut off = 32;
 
-        &self.routines[i]
+        self.routines
+            .iter()
+            .map(|r| {
+                let entry = (off, r);
+                off += r.raw_length();
+                entry
+            })
+            .collect()
+    }
+
+    /// The routine starting at `offset` from the start of the symbol table, as
+    /// stored in a code hunk's `sym_offset`.
+    ///
+    /// `None` if no routine starts exactly there.
+    pub fn routine_at_offset(&self, offset: usize) -> Option<&Routine> {
+        self.routine_offsets()
+            .into_iter()
+            .find(|(off, _)| *off == offset)
+            .map(|(_, r)| r)
     }
 
     pub fn reserved(&self) -> [u32; 4] {
@@ -293,30 +442,46 @@ impl SymbolTable {
 }
 
 impl TryFrom<&[u8]> for SymbolTable {
-    type Error = String;
+    type Error = LibmetroError;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        // Process header
+        // Process header, errors in it are at the start of the table
+        check_len(value, 32).map_err(|e| e.at_offset(0))?;
         let magic = convert_be_u32(&value[0..4].try_into().unwrap());
 
         if magic != SymTableMagicWord::SymTableMagicWord as u32 {
-            return Err(format!(
-                "Bad magic word, Expected: {}, got: {}",
-                SymTableMagicWord::SymTableMagicWord as u32,
-                magic
-            ));
+            return Err(LibmetroError::BadMagic {
+                expected: SymTableMagicWord::SymTableMagicWord as u32,
+                found: magic,
+            }
+            .at_offset(0));
         }
         let type_offset = convert_be_u32(&value[4..8].try_into().unwrap()) as usize;
         let num_types = convert_be_u32(&value[8..12].try_into().unwrap());
         let num_unnamed = convert_be_u32(&value[12..16].try_into().unwrap());
         let reserved = convert_reserved(&value[16..32].try_into().unwrap());
 
-        // Process Routines
+        if type_offset != 0 && !(32..=value.len()).contains(&type_offset) {
+            return Err(LibmetroError::Malformed(format!(
+                "Type table at {:#x} is outside the symbol table, {:#x} bytes",
+                type_offset,
+                value.len()
+            ))
+            .at_offset(0));
+        }
+
+        // Process Routines, which run up to the type table when there is one
+        let routines_end = if type_offset != 0 {
+            type_offset
+        } else {
+            value.len()
+        };
         let routines = if value.len() > 0 {
-            let mut routine_bytes = &value[32..];
+            let mut routine_bytes = &value[32..routines_end];
             let mut rs: Vec<Routine> = vec![];
             while routine_bytes.len() != 0 {
-                let r: Routine = Routine::try_from(routine_bytes).unwrap();
+                let at = routines_end - routine_bytes.len();
+                let r: Routine = Routine::try_from(routine_bytes).map_err(|e| e.at_offset(at))?;
                 routine_bytes = &routine_bytes[r.raw_length()..];
 
                 rs.push(r);
@@ -329,7 +494,7 @@ impl TryFrom<&[u8]> for SymbolTable {
         // Process Type Table
         let type_table = if type_offset != 0 {
             let tbl = &value[type_offset..];
-            TypeTable::try_from((tbl, num_types)).unwrap()
+            TypeTable::try_from((tbl, num_types)).map_err(|e| e.at_offset(type_offset))?
         } else {
             TypeTable::default()
         };
@@ -342,3 +507,267 @@ impl TryFrom<&[u8]> for SymbolTable {
         })
     }
 }
+
+/// The type table, when there is one, directly follows the routines.
+impl Serializable for SymbolTable {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        let type_offset = if self.types.is_empty() {
+            0
+        } else {
+            32 + self.routines.iter().map(|x| x.raw_length()).sum::<usize>()
+        };
+
+        let mut out: Vec<u8> = vec![];
+        out.extend((SymTableMagicWord::SymTableMagicWord as u32).to_be_bytes());
+        out.extend((type_offset as u32).to_be_bytes());
+        out.extend((self.types.len() as u32).to_be_bytes());
+        out.extend(self.unnamed.to_be_bytes());
+        for r in self.reserved.iter() {
+            out.extend(r.to_be_bytes());
+        }
+        w.write_all(&out)?;
+
+        for r in self.routines.iter() {
+            r.serialize_out(w)?;
+        }
+        self.types.serialize_out(w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_m68k::HunkType;
+    use crate::test_util::{load_library, load_object_bytes};
+    use crate::types_m68k::BasicDataType;
+
+    #[test]
+    fn test_routine_offsets_match_code_hunks() {
+        let lut = load_library("test/data/two_funcs.lib.metro");
+        let ob = lut[0].object();
+
+        let offsets: Vec<usize> = ob
+            .symbols()
+            .unwrap()
+            .routine_offsets()
+            .iter()
+            .map(|(off, _)| *off)
+            .collect();
+        assert_eq!(vec![0x20, 0x58], offsets);
+
+        let sym_offsets: Vec<usize> = ob
+            .hunks()
+            .iter()
+            .filter_map(|h| match h.hunk_type() {
+                HunkType::GlobalCode(c) => Some(c.sym_offset() as usize),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(offsets, sym_offsets);
+
+        let symtab = ob.symbols().unwrap();
+        assert_eq!(Some(&symtab.routines()[1]), symtab.routine_at_offset(0x58));
+        // Inside the header, inside the first routine, and past the end
+        for off in [0, 0x1f, 0x21, 0x1000] {
+            assert_eq!(None, symtab.routine_at_offset(off), "{:#x}", off);
+        }
+    }
+
+    #[test]
+    fn test_routine_structurally_eq() {
+        let lut = load_library("test/data/add.lib.metro");
+        let add = &lut[0].object().symbols().unwrap().routines()[0];
+
+        let mut shuffled = add.clone();
+        shuffled.statement_locations.reverse();
+        shuffled.local_vars.reverse();
+        shuffled.local_vars[0].wher += 4;
+        assert!(add.structurally_eq(&shuffled));
+
+        let mut retyped = add.clone();
+        retyped.local_vars[0].var_type = DataType::from(BasicDataType::BasicTypeWord as u32);
+        assert!(!add.structurally_eq(&retyped));
+
+        let mut moved = add.clone();
+        moved.statement_locations[1].offset += 2;
+        assert!(!add.structurally_eq(&moved));
+    }
+
+    #[test]
+    fn test_locate_statement() {
+        let lut = load_library("test/data/add.lib.metro");
+        let routine = &lut[0].object().symbols().unwrap().routines()[0];
+        assert_eq!(3, routine.statement_locations().len());
+
+        let source = |offset| routine.locate(offset).map(|x| x.sourcecode_offset());
+        assert_eq!(Some(198), source(0));
+        assert_eq!(Some(198), source(6));
+        assert_eq!(Some(211), source(8));
+        assert_eq!(Some(211), source(0x100));
+        assert_eq!(None, source(-1));
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let mut routine: Vec<u8> = vec![];
+        routine.extend((RoutineType::Function as u16).to_be_bytes());
+        routine.extend([0u32, 0x10].iter().flat_map(|x| x.to_be_bytes()));
+        routine.extend([-1i32].iter().flat_map(|x| x.to_be_bytes()));
+        routine.extend(0x1cu32.to_be_bytes());
+        routine.extend(1u16.to_be_bytes());
+        routine.extend(2u32.to_be_bytes());
+        routine.extend((BasicDataType::BasicTypeLong as u32).to_be_bytes());
+        routine.extend([StorageKind::Value as u8, StorageClass::A6 as u8]);
+        routine.extend(8u32.to_be_bytes());
+
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend((SymTableMagicWord::SymTableMagicWord as u32).to_be_bytes());
+        bytes.extend((32 + routine.len() as u32).to_be_bytes()); // type offset
+        bytes.extend(1u32.to_be_bytes()); // type count
+        bytes.extend([0; 20]);
+        bytes.extend(&routine);
+        // A pointer to long
+        bytes.extend(0u16.to_be_bytes());
+        bytes.extend(200u32.to_be_bytes());
+        bytes.extend(1u16.to_be_bytes());
+        bytes.extend((BasicDataType::BasicTypeLong as u32).to_be_bytes());
+
+        let symtab = SymbolTable::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(1, symtab.routines().len());
+        assert_eq!(1, symtab.types().len());
+
+        let mut out: Vec<u8> = vec![];
+        symtab.serialize_out(&mut out).unwrap();
+        assert_eq!(bytes, out);
+        assert_eq!(symtab, SymbolTable::try_from(out.as_slice()).unwrap());
+
+        // Without types there is no type table to point at
+        let mut bare = symtab.clone();
+        bare.types = TypeTable::default();
+        let mut out: Vec<u8> = vec![];
+        bare.serialize_out(&mut out).unwrap();
+        assert_eq!([0; 8], out[4..12]);
+        assert_eq!(bare, SymbolTable::try_from(out.as_slice()).unwrap());
+    }
+
+    #[test]
+    fn test_routine_bytes_match_library() {
+        let lut = load_library("test/data/add.lib.metro");
+        let ve = load_object_bytes("test/data/add.lib.metro");
+        let ob = lut[0].object();
+        let symtab = ob.symbols().unwrap();
+
+        // Routine offsets are from the start of the symtab
+        let symtab_start = ob.header().symtable_start();
+        for (off, r) in symtab.routine_offsets() {
+            let start = symtab_start + off;
+
+            let mut out: Vec<u8> = vec![];
+            r.serialize_out(&mut out).unwrap();
+            assert_eq!(&ve[start..start + r.raw_length()], out.as_slice());
+        }
+
+        // The first routine's type is followed by its statement locations
+        let start = symtab_start + 32;
+        let r = &symtab.routines()[0];
+        let mut out: Vec<u8> = vec![];
+        r.statement_locations()[0].serialize_out(&mut out).unwrap();
+        assert_eq!(&ve[start + 2..start + 10], out.as_slice());
+
+        let locals_start = start + 2 + 8 * r.statement_locations().len() + 2;
+        let mut out: Vec<u8> = vec![];
+        r.local_vars()[0].serialize_out(&mut out).unwrap();
+        assert_eq!(&ve[locals_start..locals_start + 14], out.as_slice());
+    }
+
+    #[test]
+    fn test_routine_needs_terminator() {
+        let lut = load_library("test/data/add.lib.metro");
+        let mut r = lut[0].object().symbols().unwrap().routines()[0].clone();
+        r.statement_locations.pop();
+
+        let mut out: Vec<u8> = vec![];
+        assert!(r.serialize_out(&mut out).is_err());
+    }
+
+    #[test]
+    fn test_unknown_routine_type() {
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend((RoutineType::Unknown as u16).to_be_bytes());
+        bytes.extend([0u32, 0x10].iter().flat_map(|x| x.to_be_bytes()));
+        bytes.extend([-1i32].iter().flat_map(|x| x.to_be_bytes()));
+        bytes.extend(0x08u32.to_be_bytes());
+        bytes.extend(0u16.to_be_bytes());
+
+        let routine = Routine::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(RoutineType::Unknown, routine.typ);
+        assert!(!routine.is_procedure() && !routine.is_function());
+
+        let mut out: Vec<u8> = vec![];
+        routine.serialize_out(&mut out).unwrap();
+        assert_eq!(bytes, out);
+    }
+
+    #[test]
+    fn test_malformed_symtab_errors() {
+        let lut = load_library("test/data/add.lib.metro");
+        let symtab = lut[0].object().symbols().unwrap().clone();
+        assert!(!symtab.routines()[0].local_vars.is_empty());
+
+        let mut bytes: Vec<u8> = vec![];
+        symtab.serialize_out(&mut bytes).unwrap();
+        assert!(SymbolTable::try_from(bytes.as_slice()).is_ok());
+
+        let mut bad = bytes.clone();
+        bad[32..34].copy_from_slice(&7u16.to_be_bytes());
+        assert!(matches!(
+            SymbolTable::try_from(bad.as_slice())
+                .unwrap_err()
+                .root_cause(),
+            LibmetroError::BadRoutineType(7)
+        ));
+
+        // The last local's storage kind
+        let mut bad = bytes.clone();
+        let kind = bad.len() - 6;
+        bad[kind] = 9;
+        assert!(matches!(
+            SymbolTable::try_from(bad.as_slice())
+                .unwrap_err()
+                .root_cause(),
+            LibmetroError::BadStorageKind(9)
+        ));
+
+        assert!(matches!(
+            SymbolTable::try_from(&bytes[..bytes.len() - 4])
+                .unwrap_err()
+                .root_cause(),
+            LibmetroError::Truncated { .. }
+        ));
+        assert!(matches!(
+            SymbolTable::try_from(&bytes[..20])
+                .unwrap_err()
+                .root_cause(),
+            LibmetroError::Truncated { needed: 32, .. }
+        ));
+
+        let mut bad = bytes.clone();
+        bad[4..8].copy_from_slice(&(bytes.len() as u32 + 1).to_be_bytes());
+        bad[8..12].copy_from_slice(&1u32.to_be_bytes());
+        let err = SymbolTable::try_from(bad.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("outside the symbol table"));
+
+        // A type entry with an unknown tag
+        let mut bad = bytes.clone();
+        bad[4..8].copy_from_slice(&(bytes.len() as u32).to_be_bytes());
+        bad[8..12].copy_from_slice(&1u32.to_be_bytes());
+        bad.extend(0x40u16.to_be_bytes());
+        bad.extend(200u32.to_be_bytes());
+        assert!(matches!(
+            SymbolTable::try_from(bad.as_slice())
+                .unwrap_err()
+                .root_cause(),
+            LibmetroError::BadTypeTag(0x40)
+        ));
+    }
+}
@@ -0,0 +1,27 @@
+//! Fixture loading shared by the unit tests.
+
+use std::fs;
+
+use crate::mwob_library::read_file_headers;
+use crate::objects_m68k::ObjectHeader;
+use crate::MetroWerksLibrary;
+
+pub(crate) fn load_library(path: &str) -> MetroWerksLibrary {
+    MetroWerksLibrary::from_file(path).unwrap()
+}
+
+/// The first object in a library, exactly as stored in the file.
+pub(crate) fn load_object_bytes(path: &str) -> Vec<u8> {
+    let ve = fs::read(path).unwrap();
+    let (toc, start) = &read_file_headers(&ve, 1).unwrap()[0];
+
+    ve[*start..*start + toc.data_size()].to_vec()
+}
+
+/// The hunk stream of the first object in a library, as stored in the file.
+pub(crate) fn load_hunk_bytes(path: &str) -> Vec<u8> {
+    let bytes = load_object_bytes(path);
+    let header = ObjectHeader::try_from(bytes.as_slice()).unwrap();
+
+    bytes[header.obj_start()..header.obj_end()].to_vec()
+}
@@ -1,10 +1,14 @@
-use std::ops::{Deref, Range};
+use std::fmt::Display;
+use std::io::Write;
+use std::ops::{Deref, Range, RangeInclusive};
 
-use crate::util::RawLength;
+use crate::error::LibmetroError;
+use crate::util::{check_len, RawLength, Serializable};
 
 use super::util::{convert_be_u16, convert_be_u32, NameIdFromObject};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DataType {
     Undefined(()),
     BasicDataType(BasicDataType),
@@ -13,6 +17,7 @@ pub enum DataType {
 
 #[repr(u16)]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BasicDataType {
     BasicTypeVoid = 0,
     BasicTypePstring,
@@ -44,6 +49,95 @@ pub enum BasicDataType {
     MyBasicTypePstringPtr, /* Pascal str. pointer */
 }
 
+impl BasicDataType {
+    /// How many bytes a value of this type takes up, `None` for `void` and the
+    /// variable length strings.
+    pub fn size_in_bytes(&self) -> Option<u32> {
+        match self {
+            BasicDataType::BasicTypeVoid => None,
+            BasicDataType::BasicTypePstring => None,
+            BasicDataType::BasicTypeCstring => None,
+            BasicDataType::BasicTypeAIstring => None,
+
+            BasicDataType::BasicTypeBoolean => Some(1),
+            BasicDataType::BasicTypeUbyte => Some(1),
+            BasicDataType::BasicTypeByte => Some(1),
+            BasicDataType::BasicTypeChar => Some(1),
+            BasicDataType::BasicTypeWchar => Some(2),
+            BasicDataType::BasicTypeUword => Some(2),
+            BasicDataType::BasicTypeWord => Some(2),
+            BasicDataType::BasicTypeUlong => Some(4),
+            BasicDataType::BasicTypeLong => Some(4),
+            BasicDataType::BasicTypeFloat4 => Some(4),
+            BasicDataType::BasicTypeFloat8 => Some(8),
+            BasicDataType::BasicTypeComp => Some(8),
+            BasicDataType::BasicTypeFloat10 => Some(10),
+            BasicDataType::BasicTypeFloat12 => Some(12),
+
+            // Pointers and handles are all 32-bit on m68k
+            BasicDataType::MyBasicTypeVoidPtr
+            | BasicDataType::MyBasicTypeVoidHdl
+            | BasicDataType::MyBasicTypeCharPtr
+            | BasicDataType::MyBasicTypeCharHdl
+            | BasicDataType::MyBasicTypeUcharPtr
+            | BasicDataType::MyBasicTypeUcharHdl
+            | BasicDataType::MyBasicTypeFunc
+            | BasicDataType::MyBasicTypeStringPtr
+            | BasicDataType::MyBasicTypePstringPtr => Some(4),
+        }
+    }
+}
+
+/// The compiler settings an object was built with that decide how big some
+/// basic types are, see `MetrowerksObject::target_model`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TargetModel {
+    /// Bytes in an `int`, 2 or 4.
+    pub int_size: u32,
+    /// Bytes in a `double`, 8, or the size of an extended when 8-byte doubles
+    /// are off.
+    pub double_size: u32,
+    /// Whether floating point is done on an MC68881.
+    pub mc68881: bool,
+}
+
+/// Four-byte ints and 8-byte doubles without an FPU, the sizes
+/// `BasicDataType::size_in_bytes` assumes.
+impl Default for TargetModel {
+    fn default() -> Self {
+        Self {
+            int_size: 4,
+            double_size: 8,
+            mc68881: false,
+        }
+    }
+}
+
+impl BasicDataType {
+    /// Like `size_in_bytes`, but with `Long` and `Ulong` taking the size of an
+    /// `int` in `model`. The float types name their width and don't change.
+    pub fn size_in(&self, model: &TargetModel) -> Option<u32> {
+        match self {
+            BasicDataType::BasicTypeLong | BasicDataType::BasicTypeUlong => Some(model.int_size),
+            x => x.size_in_bytes(),
+        }
+    }
+}
+
+impl DataType {
+    /// How many bytes a value of this type takes up, looking `Other` ids up in
+    /// `types`.
+    ///
+    /// `None` when the size isn't fixed or the id isn't in the table.
+    pub fn size_in_bytes(&self, types: &TypeTable) -> Option<u32> {
+        match self {
+            DataType::Undefined(_) => None,
+            DataType::BasicDataType(x) => x.size_in_bytes(),
+            DataType::Other(_) => types.resolve(self)?.definition().size_in_bytes(types),
+        }
+    }
+}
+
 impl From<u32> for DataType {
     fn from(value: u32) -> Self {
         match value {
@@ -133,7 +227,40 @@ impl From<u32> for DataType {
     }
 }
 
-#[derive(Debug, Clone)]
+/// The raw id of a type as stored on disk.
+///
+/// `Undefined` has no id and always fails.
+impl TryFrom<&DataType> for u32 {
+    type Error = LibmetroError;
+
+    fn try_from(value: &DataType) -> Result<Self, Self::Error> {
+        match value {
+            DataType::BasicDataType(x) => Ok(x.clone() as u32),
+            DataType::Other(id) => Ok(*id),
+            DataType::Undefined(_) => Err(LibmetroError::Invalid(
+                "Undefined data type has no type id".to_owned(),
+            )),
+        }
+    }
+}
+
+/// The raw id of a type for the 16-bit fields, such as an enum's base type.
+///
+/// Behaves like the `u32` conversion, and additionally fails when an `Other`
+/// id doesn't fit in 16 bits.
+impl TryFrom<&DataType> for u16 {
+    type Error = LibmetroError;
+
+    fn try_from(value: &DataType) -> Result<Self, Self::Error> {
+        let id = u32::try_from(value)?;
+        u16::try_from(id).map_err(|_| {
+            LibmetroError::Invalid(format!("Type id does not fit in 16 bits, got: {}", id))
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Pointer {
     number: u16,
     typ: DataType,
@@ -166,7 +293,18 @@ impl RawLength for Pointer {
     }
 }
 
-#[derive(Debug, Clone)]
+impl Serializable for Pointer {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        let mut out: Vec<u8> = vec![];
+        out.extend(self.number.to_be_bytes());
+        out.extend(u32::try_from(&self.typ)?.to_be_bytes());
+
+        Ok(w.write_all(&out)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Array {
     size: u32,
     esize: u32,
@@ -207,7 +345,19 @@ impl RawLength for Array {
     }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+impl Serializable for Array {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        let mut out: Vec<u8> = vec![];
+        out.extend(self.size.to_be_bytes());
+        out.extend(self.esize.to_be_bytes());
+        out.extend(u32::try_from(&self.typ)?.to_be_bytes());
+
+        Ok(w.write_all(&out)?)
+    }
+}
+
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StructMember {
     name_id: u32,
     typ: DataType,
@@ -222,6 +372,11 @@ impl StructMember {
     pub fn offset(&self) -> u32 {
         self.offset
     }
+
+    /// The definition of this member's type, see `TypeTable::resolve`.
+    pub fn resolve_type<'a>(&self, types: &'a TypeTable) -> Option<&'a TypeDefinition> {
+        types.resolve(&self.typ)
+    }
 }
 
 impl RawLength for StructMember {
@@ -230,13 +385,15 @@ impl RawLength for StructMember {
     }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Struct {
     name_id: u32,
     size: u32,
     members: Vec<StructMember>,
 }
 
+/// Kept for existing callers, prefer `entries`, `len` and `get`.
 impl Deref for Struct {
     type Target = Vec<StructMember>;
 
@@ -245,14 +402,19 @@ impl Deref for Struct {
     }
 }
 
-impl From<&[u8]> for Struct {
-    fn from(value: &[u8]) -> Self {
+/// Fails if there aren't enough bytes for the members the count declares.
+impl TryFrom<&[u8]> for Struct {
+    type Error = LibmetroError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
         let mut data = value;
 
+        check_len(data, 10)?;
         let name = convert_be_u32(&data[0..4].try_into().unwrap());
         let size = convert_be_u32(&data[4..8].try_into().unwrap());
         let num_members = convert_be_u16(&data[8..10].try_into().unwrap());
         data = &data[10..];
+        check_len(data, num_members as usize * 12)?;
 
         let mut members: Vec<StructMember> = vec![];
         for _idx in 0..num_members {
@@ -269,15 +431,32 @@ impl From<&[u8]> for Struct {
             data = &data[12..]
         }
 
-        Struct {
+        Ok(Struct {
             name_id: name,
             size: size,
             members: members,
-        }
+        })
     }
 }
 
 impl Struct {
+    /// The members, in declaration order.
+    pub fn entries(&self) -> &[StructMember] {
+        &self.members
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&StructMember> {
+        self.members.get(index)
+    }
+
     pub fn size(&self) -> u32 {
         self.size
     }
@@ -289,7 +468,31 @@ impl RawLength for Struct {
     }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+impl Serializable for Struct {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        let count = u16::try_from(self.members.len()).map_err(|_| {
+            LibmetroError::Invalid(format!(
+                "Struct has too many members: {}",
+                self.members.len()
+            ))
+        })?;
+
+        let mut out: Vec<u8> = vec![];
+        out.extend(self.name_id.to_be_bytes());
+        out.extend(self.size.to_be_bytes());
+        out.extend(count.to_be_bytes());
+        for m in self.members.iter() {
+            out.extend(m.name_id.to_be_bytes());
+            out.extend(u32::try_from(&m.typ)?.to_be_bytes());
+            out.extend(m.offset.to_be_bytes());
+        }
+
+        Ok(w.write_all(&out)?)
+    }
+}
+
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnumMember {
     name_id: u32,
     value: u32,
@@ -307,13 +510,15 @@ impl RawLength for EnumMember {
     }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Enum {
     name_id: u32,
     typ: DataType,
     members: Vec<EnumMember>,
 }
 
+/// Kept for existing callers, prefer `entries`, `len` and `get`.
 impl Deref for Enum {
     type Target = Vec<EnumMember>;
 
@@ -323,6 +528,25 @@ impl Deref for Enum {
 }
 
 impl Enum {
+    /// The members, in declaration order.
+    pub fn entries(&self) -> &[EnumMember] {
+        &self.members
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&EnumMember> {
+        self.members.get(index)
+    }
+
+    /// The enum's base type, always a `DataType::BasicDataType`: parsing rejects
+    /// any other base.
     pub fn data_type(&self) -> &DataType {
         &self.typ
     }
@@ -334,15 +558,38 @@ impl RawLength for Enum {
     }
 }
 
+impl Serializable for Enum {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        let count = u16::try_from(self.members.len()).map_err(|_| {
+            LibmetroError::Invalid(format!("Enum has too many members: {}", self.members.len()))
+        })?;
+
+        let mut out: Vec<u8> = vec![];
+        out.extend(self.name_id.to_be_bytes());
+        out.extend(u16::try_from(&self.typ)?.to_be_bytes());
+        out.extend(count.to_be_bytes());
+        for m in self.members.iter() {
+            out.extend(m.name_id.to_be_bytes());
+            out.extend(m.value.to_be_bytes());
+        }
+
+        Ok(w.write_all(&out)?)
+    }
+}
+
+/// Fails if there aren't enough bytes for the members the count declares, or
+/// the base type isn't a basic type.
 impl TryFrom<&[u8]> for Enum {
-    type Error = String;
+    type Error = LibmetroError;
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
         let mut data = value;
 
+        check_len(data, 8)?;
         let name = convert_be_u32(&data[0..4].try_into().unwrap());
         let baseid = convert_be_u16(&data[4..6].try_into().unwrap());
         let num_members = convert_be_u16(&data[6..8].try_into().unwrap());
         data = &data[8..];
+        check_len(data, num_members as usize * 8)?;
 
         let mut members: Vec<EnumMember> = vec![];
         for _idx in 0..num_members {
@@ -359,7 +606,12 @@ impl TryFrom<&[u8]> for Enum {
 
         let typ: BasicDataType = match DataType::from(baseid as u32) {
             DataType::BasicDataType(x) => x,
-            _ => return Err(format!("Bad Type for Enum, got: {}", baseid)),
+            _ => {
+                return Err(LibmetroError::Malformed(format!(
+                    "Bad Type for Enum, got: {}",
+                    baseid
+                )))
+            }
         };
 
         Ok(Enum {
@@ -370,7 +622,8 @@ impl TryFrom<&[u8]> for Enum {
     }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PascalArray {
     packed: bool,
     size: u32,
@@ -421,7 +674,21 @@ impl RawLength for PascalArray {
     }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+impl Serializable for PascalArray {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        let mut out: Vec<u8> = vec![];
+        out.extend((self.packed as u32).to_be_bytes());
+        out.extend(self.size.to_be_bytes());
+        out.extend(self.iid.to_be_bytes());
+        out.extend(u32::try_from(&self.eid)?.to_be_bytes());
+        out.extend(self.name_id.to_be_bytes());
+
+        Ok(w.write_all(&out)?)
+    }
+}
+
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PascalRange {
     name_id: u32,
     typ: DataType,
@@ -464,6 +731,22 @@ impl PascalRange {
     pub fn data_type(&self) -> &DataType {
         &self.typ
     }
+
+    /// Whether `value` is in the range, counting `lower` but not `upper` as
+    /// `Range` does.
+    pub fn contains(&self, value: u32) -> bool {
+        (self.lower..self.upper).contains(&value)
+    }
+
+    /// The number of values `contains` accepts, zero when `upper` isn't above
+    /// `lower`.
+    pub fn len(&self) -> u32 {
+        self.upper.saturating_sub(self.lower)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 impl RawLength for PascalRange {
@@ -472,13 +755,33 @@ impl RawLength for PascalRange {
     }
 }
 
+impl Serializable for PascalRange {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        let mut out: Vec<u8> = vec![];
+        out.extend(self.name_id.to_be_bytes());
+        out.extend(u32::try_from(&self.typ)?.to_be_bytes());
+        out.extend(self.size.to_be_bytes());
+        out.extend(self.lower.to_be_bytes());
+        out.extend(self.upper.to_be_bytes());
+
+        Ok(w.write_all(&out)?)
+    }
+}
+
 impl Into<Range<u32>> for PascalRange {
     fn into(self) -> Range<u32> {
         self.lower..self.upper
     }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+impl From<PascalRange> for RangeInclusive<u32> {
+    fn from(value: PascalRange) -> Self {
+        value.lower..=value.upper
+    }
+}
+
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PascalSet {
     name_id: u32,
     base: DataType,
@@ -515,7 +818,19 @@ impl RawLength for PascalSet {
     }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+impl Serializable for PascalSet {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        let mut out: Vec<u8> = vec![];
+        out.extend(self.name_id.to_be_bytes());
+        out.extend(u32::try_from(&self.base)?.to_be_bytes());
+        out.extend(self.size.to_be_bytes());
+
+        Ok(w.write_all(&out)?)
+    }
+}
+
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PascalEnum {
     name_id: u32,
     members: Vec<u32>,
@@ -529,13 +844,18 @@ impl Deref for PascalEnum {
     }
 }
 
-impl From<&[u8]> for PascalEnum {
-    fn from(value: &[u8]) -> Self {
+/// Fails if there aren't enough bytes for the members the count declares.
+impl TryFrom<&[u8]> for PascalEnum {
+    type Error = LibmetroError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
         let mut data = value;
 
+        check_len(data, 8)?;
         let name = convert_be_u32(&data[0..4].try_into().unwrap());
-        let num_members = convert_be_u16(&data[4..8].try_into().unwrap());
+        let num_members = convert_be_u32(&data[4..8].try_into().unwrap());
         data = &data[8..];
+        check_len(data, num_members as usize * 4)?;
 
         let mut members: Vec<u32> = vec![];
         for _idx in 0..num_members {
@@ -545,10 +865,10 @@ impl From<&[u8]> for PascalEnum {
             data = &data[4..]
         }
 
-        PascalEnum {
+        Ok(PascalEnum {
             name_id: name,
             members: members,
-        }
+        })
     }
 }
 
@@ -558,7 +878,22 @@ impl RawLength for PascalEnum {
     }
 }
 
-#[derive(NameIdFromObject, Debug, Clone)]
+/// The member count is a u32 on disk, unlike the other member lists.
+impl Serializable for PascalEnum {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        let mut out: Vec<u8> = vec![];
+        out.extend(self.name_id.to_be_bytes());
+        out.extend((self.members.len() as u32).to_be_bytes());
+        for m in self.members.iter() {
+            out.extend(m.to_be_bytes());
+        }
+
+        Ok(w.write_all(&out)?)
+    }
+}
+
+#[derive(NameIdFromObject, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PascalString {
     size: u32,
     name_id: u32,
@@ -588,7 +923,18 @@ impl RawLength for PascalString {
     }
 }
 
-#[derive(Debug, Clone)]
+impl Serializable for PascalString {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        let mut out: Vec<u8> = vec![];
+        out.extend(self.size.to_be_bytes());
+        out.extend(self.name_id.to_be_bytes());
+
+        Ok(w.write_all(&out)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OtherDataType {
     Undefined,
     TypePointer(Pointer),
@@ -602,6 +948,64 @@ pub enum OtherDataType {
     TypePascalString(PascalString),
 }
 
+impl OtherDataType {
+    /// Ids of the types this one refers to, which must be defined before it.
+    ///
+    /// A pointer includes its pointee; `TypeTable::topological_order` drops
+    /// that when the pointee is a struct, which only needs a forward declaration.
+    pub fn dependencies(&self) -> Vec<u32> {
+        let refs: Vec<&DataType> = match self {
+            OtherDataType::Undefined => vec![],
+            OtherDataType::TypePointer(p) => vec![p.data_type()],
+            OtherDataType::TypeArray(a) => vec![a.data_type()],
+            OtherDataType::TypeStruct(s) => s.iter().map(|x| x.data_type()).collect(),
+            OtherDataType::TypeEnum(e) => vec![e.data_type()],
+            OtherDataType::TypePascalArray(pa) => vec![pa.eid()],
+            OtherDataType::TypePascalRange(pr) => vec![pr.data_type()],
+            OtherDataType::TypePascalSet(ps) => vec![ps.base()],
+            OtherDataType::TypePascalEnum(_) => vec![],
+            OtherDataType::TypePascalString(_) => vec![],
+        };
+
+        let mut ids: Vec<u32> = refs
+            .into_iter()
+            .filter_map(|x| match x {
+                DataType::Other(id) => Some(*id),
+                _ => None,
+            })
+            .collect();
+
+        if let OtherDataType::TypePascalArray(pa) = self {
+            if let DataType::Other(id) = DataType::from(pa.iid()) {
+                ids.push(id);
+            }
+        }
+
+        ids
+    }
+}
+
+impl OtherDataType {
+    /// How many bytes a value of this type takes up, see `DataType::size_in_bytes`.
+    ///
+    /// Pascal enums and strings are left as `None`, their in-memory size isn't
+    /// recorded.
+    pub fn size_in_bytes(&self, types: &TypeTable) -> Option<u32> {
+        match self {
+            OtherDataType::Undefined => None,
+            OtherDataType::TypePointer(_) => Some(4),
+            OtherDataType::TypeArray(a) => Some(a.size()),
+            OtherDataType::TypeStruct(s) => Some(s.size()),
+            OtherDataType::TypeEnum(e) => e.data_type().size_in_bytes(types),
+            OtherDataType::TypePascalArray(pa) => Some(pa.size()),
+            OtherDataType::TypePascalRange(pr) => Some(pr.size()),
+            OtherDataType::TypePascalSet(ps) => Some(ps.size),
+            OtherDataType::TypePascalEnum(_) => None,
+            OtherDataType::TypePascalString(_) => None,
+        }
+    }
+}
+
 impl RawLength for OtherDataType {
     fn raw_length(&self) -> usize {
         match self {
@@ -619,7 +1023,28 @@ impl RawLength for OtherDataType {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Writes the body only; the tag and id ahead of it come from `TypeDefinition`.
+impl Serializable for OtherDataType {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        match self {
+            OtherDataType::Undefined => Err(LibmetroError::Invalid(
+                "Undefined type cannot be serialized".to_owned(),
+            )),
+            OtherDataType::TypePointer(p) => p.serialize_out(w),
+            OtherDataType::TypeArray(a) => a.serialize_out(w),
+            OtherDataType::TypeStruct(s) => s.serialize_out(w),
+            OtherDataType::TypeEnum(e) => e.serialize_out(w),
+            OtherDataType::TypePascalArray(pa) => pa.serialize_out(w),
+            OtherDataType::TypePascalRange(pr) => pr.serialize_out(w),
+            OtherDataType::TypePascalSet(ps) => ps.serialize_out(w),
+            OtherDataType::TypePascalEnum(pe) => pe.serialize_out(w),
+            OtherDataType::TypePascalString(ps) => ps.serialize_out(w),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TypeDefinition {
     typ: OtherDataType,
     id: u32,
@@ -627,11 +1052,49 @@ pub struct TypeDefinition {
 
 impl RawLength for TypeDefinition {
     fn raw_length(&self) -> usize {
-        2 + self.typ.raw_length()
+        // u16 tag and u32 id ahead of the body
+        6 + self.typ.raw_length()
+    }
+}
+
+impl Serializable for TypeDefinition {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        let tag = match &self.typ {
+            OtherDataType::Undefined => {
+                return Err(LibmetroError::Invalid(format!(
+                    "Type {} is undefined and has no tag",
+                    self.id
+                )))
+            }
+            OtherDataType::TypePointer(_) => RawOtherDataType::LOCTYPE_POINTER,
+            OtherDataType::TypeArray(_) => RawOtherDataType::LOCTYPE_ARRAY,
+            OtherDataType::TypeStruct(_) => RawOtherDataType::LOCTYPE_STRUCT,
+            OtherDataType::TypeEnum(_) => RawOtherDataType::LOCTYPE_ENUM,
+            OtherDataType::TypePascalArray(_) => RawOtherDataType::LOCTYPE_PARRAY,
+            OtherDataType::TypePascalRange(_) => RawOtherDataType::LOCTYPE_RANGE,
+            OtherDataType::TypePascalSet(_) => RawOtherDataType::LOCTYPE_SET,
+            OtherDataType::TypePascalEnum(_) => RawOtherDataType::LOCTYPE_PENUM,
+            OtherDataType::TypePascalString(_) => RawOtherDataType::LOCTYPE_PSTRING,
+        };
+
+        let mut out: Vec<u8> = vec![];
+        out.extend((tag as u16).to_be_bytes());
+        out.extend(self.id.to_be_bytes());
+        w.write_all(&out)?;
+
+        self.typ.serialize_out(w)
     }
 }
 
 impl TypeDefinition {
+    pub fn type_id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn definition(&self) -> &OtherDataType {
+        &self.typ
+    }
+
     pub fn data_type(self, typ: OtherDataType) -> Self {
         Self {
             id: self.id,
@@ -663,7 +1126,7 @@ enum RawOtherDataType {
 }
 
 impl TryFrom<(u16, u32)> for TypeParseState {
-    type Error = &'static str;
+    type Error = LibmetroError;
 
     fn try_from(value: (u16, u32)) -> Result<Self, Self::Error> {
         match value.0 {
@@ -692,7 +1155,7 @@ impl TryFrom<(u16, u32)> for TypeParseState {
             x if x == RawOtherDataType::LOCTYPE_PSTRING as u16 => {
                 Ok(TypeParseState::ParsePascalString(value.1))
             }
-            _ => Err("Bad branch select for type"),
+            _ => Err(LibmetroError::BadTypeTag(value.0)),
         }
     }
 }
@@ -730,8 +1193,29 @@ impl PartialEq for TypeParseState {
     }
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct TypeTable {
+/// Returned by `TypeTable::topological_order` when types depend on each other
+/// other than through a pointer to a struct.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleError {
+    ids: Vec<u32>,
+}
+
+impl CycleError {
+    /// The ids making up the cycle, each depending on the next and the last on the first.
+    pub fn ids(&self) -> &[u32] {
+        &self.ids
+    }
+}
+
+impl Display for CycleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Cycle between types: {:?}", self.ids)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TypeTable {
     table: Vec<TypeDefinition>,
 }
 
@@ -751,21 +1235,126 @@ impl Deref for TypeTable {
     }
 }
 
+impl TypeTable {
+    pub fn type_by_id(&self, id: u32) -> Option<&TypeDefinition> {
+        self.table.iter().find(|x| x.id == id)
+    }
+
+    /// The definition an `Other` type refers to, `None` for basic types and ids
+    /// missing from the table.
+    pub fn resolve(&self, dt: &DataType) -> Option<&TypeDefinition> {
+        match dt {
+            DataType::Other(id) => self.type_by_id(*id),
+            _ => None,
+        }
+    }
+
+    /// The table's types ordered so that each comes after every type it depends on.
+    ///
+    /// A pointer to a struct doesn't wait for the struct, which can be forward
+    /// declared; a pointer to anything else does. Ties keep table order.
+    /// References to ids that aren't in the table are ignored.
+    pub fn topological_order(&self) -> Result<Vec<&TypeDefinition>, CycleError> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark {
+            Unvisited,
+            InProgress,
+            Done,
+        }
+
+        let index_of = |id: u32| self.table.iter().position(|x| x.id == id);
+        let mut marks = vec![Mark::Unvisited; self.table.len()];
+        let mut order: Vec<&TypeDefinition> = vec![];
+
+        for root in 0..self.table.len() {
+            if marks[root] != Mark::Unvisited {
+                continue;
+            }
+
+            // Each frame is a type and its dependencies still to visit
+            let mut stack: Vec<(usize, Vec<usize>)> = vec![];
+            let deps = |idx: usize| -> Vec<usize> {
+                let is_pointer = matches!(self.table[idx].typ, OtherDataType::TypePointer(_));
+                let mut d: Vec<usize> = self.table[idx]
+                    .typ
+                    .dependencies()
+                    .into_iter()
+                    .filter_map(index_of)
+                    .filter(|x| {
+                        !is_pointer || !matches!(self.table[*x].typ, OtherDataType::TypeStruct(_))
+                    })
+                    .collect();
+                d.reverse();
+                d
+            };
+
+            marks[root] = Mark::InProgress;
+            stack.push((root, deps(root)));
+
+            while let Some((idx, pending)) = stack.last_mut() {
+                match pending.pop() {
+                    Some(next) => match marks[next] {
+                        Mark::Done => {}
+                        Mark::InProgress => {
+                            let start = stack.iter().position(|x| x.0 == next).unwrap();
+                            return Err(CycleError {
+                                ids: stack[start..].iter().map(|x| self.table[x.0].id).collect(),
+                            });
+                        }
+                        Mark::Unvisited => {
+                            marks[next] = Mark::InProgress;
+                            stack.push((next, deps(next)));
+                        }
+                    },
+                    None => {
+                        let idx = *idx;
+                        marks[idx] = Mark::Done;
+                        order.push(&self.table[idx]);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+
+        Ok(order)
+    }
+}
+
 impl RawLength for TypeTable {
     fn raw_length(&self) -> usize {
-        (2 * self.table.len()) + self.table.iter().map(|x| x.raw_length()).sum::<usize>()
+        self.table.iter().map(|x| x.raw_length()).sum::<usize>()
+    }
+}
+
+/// Writes each definition as its tag, id, and body, back to back.
+impl Serializable for TypeTable {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError> {
+        for t in self.table.iter() {
+            t.serialize_out(w)?;
+        }
+
+        Ok(())
     }
 }
 
 impl TryFrom<(&[u8], u32)> for TypeTable {
-    type Error = String;
+    type Error = LibmetroError;
 
     fn try_from(value: (&[u8], u32)) -> Result<Self, Self::Error> {
-        let num_types = value.1;
+        let mut type_start = 0;
+
+        TypeTable::parse(value.0, value.1, &mut type_start).map_err(|e| e.at_offset(type_start))
+    }
+}
+
+impl TypeTable {
+    /// The type table parser, leaving `type_start` at the offset of the type it
+    /// was reading when it stopped.
+    fn parse(value: &[u8], num_types: u32, type_start: &mut usize) -> Result<Self, LibmetroError> {
         if num_types == 0 {
             return Ok(TypeTable { table: vec![] });
         }
-        let mut data: &[u8] = value.0;
+        let mut data: &[u8] = value;
 
         let mut types: Vec<TypeDefinition> = vec![];
         let mut remaining_types = num_types;
@@ -774,50 +1363,62 @@ impl TryFrom<(&[u8], u32)> for TypeTable {
         while state != TypeParseState::End {
             state = match state {
                 TypeParseState::ParseTag => {
+                    *type_start = value.len() - data.len();
+                    check_len(data, 6)?;
                     let tag = convert_be_u16(&data[0..2].try_into().unwrap());
                     let id = convert_be_u32(&data[2..6].try_into().unwrap());
 
                     data = &data[6..];
-                    TypeParseState::try_from((tag, id)).unwrap() // Jump to the proper processing state
+                    TypeParseState::try_from((tag, id))? // Jump to the proper processing state
                 }
 
                 TypeParseState::ParsePointer(id) => {
+                    check_len(data, 6)?;
                     TypeParseState::CommitType(id, OtherDataType::TypePointer(Pointer::from(data)))
                 }
                 TypeParseState::ParseArray(id) => {
+                    check_len(data, 12)?;
                     TypeParseState::CommitType(id, OtherDataType::TypeArray(Array::from(data)))
                 }
-                TypeParseState::ParseStruct(id) => {
-                    TypeParseState::CommitType(id, OtherDataType::TypeStruct(Struct::from(data)))
-                }
-                TypeParseState::ParseEnum(id) => {
-                    let e = match Enum::try_from(data) {
-                        Ok(x) => x,
-                        Err(x) => return Err(x),
-                    };
-
-                    TypeParseState::CommitType(id, OtherDataType::TypeEnum(e))
-                }
-                TypeParseState::ParsePascalArray(id) => TypeParseState::CommitType(
-                    id,
-                    OtherDataType::TypePascalArray(PascalArray::from(data)),
-                ),
-                TypeParseState::ParseRange(id) => TypeParseState::CommitType(
+                TypeParseState::ParseStruct(id) => TypeParseState::CommitType(
                     id,
-                    OtherDataType::TypePascalRange(PascalRange::from(data)),
-                ),
-                TypeParseState::ParseSet(id) => TypeParseState::CommitType(
-                    id,
-                    OtherDataType::TypePascalSet(PascalSet::from(data)),
+                    OtherDataType::TypeStruct(Struct::try_from(data)?),
                 ),
+                TypeParseState::ParseEnum(id) => {
+                    TypeParseState::CommitType(id, OtherDataType::TypeEnum(Enum::try_from(data)?))
+                }
+                TypeParseState::ParsePascalArray(id) => {
+                    check_len(data, 20)?;
+                    TypeParseState::CommitType(
+                        id,
+                        OtherDataType::TypePascalArray(PascalArray::from(data)),
+                    )
+                }
+                TypeParseState::ParseRange(id) => {
+                    check_len(data, 20)?;
+                    TypeParseState::CommitType(
+                        id,
+                        OtherDataType::TypePascalRange(PascalRange::from(data)),
+                    )
+                }
+                TypeParseState::ParseSet(id) => {
+                    check_len(data, 12)?;
+                    TypeParseState::CommitType(
+                        id,
+                        OtherDataType::TypePascalSet(PascalSet::from(data)),
+                    )
+                }
                 TypeParseState::ParsePascalEnum(id) => TypeParseState::CommitType(
                     id,
-                    OtherDataType::TypePascalEnum(PascalEnum::from(data)),
-                ),
-                TypeParseState::ParsePascalString(id) => TypeParseState::CommitType(
-                    id,
-                    OtherDataType::TypePascalString(PascalString::from(data)),
+                    OtherDataType::TypePascalEnum(PascalEnum::try_from(data)?),
                 ),
+                TypeParseState::ParsePascalString(id) => {
+                    check_len(data, 8)?;
+                    TypeParseState::CommitType(
+                        id,
+                        OtherDataType::TypePascalString(PascalString::from(data)),
+                    )
+                }
 
                 TypeParseState::CommitType(id, typ) => {
                     data = &data[typ.raw_length()..];
@@ -837,3 +1438,426 @@ impl TryFrom<(&[u8], u32)> for TypeTable {
         Ok(TypeTable { table: types })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(id: u32, typ: OtherDataType) -> TypeDefinition {
+        TypeDefinition { typ, id }
+    }
+
+    fn pointer_to(id: u32) -> OtherDataType {
+        OtherDataType::TypePointer(Pointer {
+            number: 1,
+            typ: DataType::Other(id),
+        })
+    }
+
+    fn struct_of(ids: &[u32]) -> OtherDataType {
+        OtherDataType::TypeStruct(Struct {
+            name_id: 0,
+            size: 4 * ids.len() as u32,
+            members: ids
+                .iter()
+                .enumerate()
+                .map(|(i, id)| StructMember {
+                    name_id: 0,
+                    typ: DataType::Other(*id),
+                    offset: 4 * i as u32,
+                })
+                .collect(),
+        })
+    }
+
+    fn ids(order: &[&TypeDefinition]) -> Vec<u32> {
+        order.iter().map(|x| x.type_id()).collect()
+    }
+
+    #[test]
+    fn test_topological_order() {
+        let table = TypeTable {
+            table: vec![
+                definition(200, struct_of(&[201, 202])),
+                definition(201, pointer_to(200)),
+                definition(202, struct_of(&[203])),
+                definition(
+                    203,
+                    OtherDataType::TypeArray(Array {
+                        size: 8,
+                        esize: 4,
+                        typ: DataType::BasicDataType(BasicDataType::BasicTypeLong),
+                    }),
+                ),
+            ],
+        };
+
+        // The self-referential pointer doesn't hold anything up
+        let order = table.topological_order().unwrap();
+        assert_eq!(vec![201, 203, 202, 200], ids(&order));
+
+        let table = TypeTable {
+            table: vec![
+                definition(200, struct_of(&[201])),
+                definition(201, struct_of(&[202])),
+                definition(202, struct_of(&[200])),
+            ],
+        };
+        let err = table.topological_order().unwrap_err();
+        assert_eq!(&[200, 201, 202], err.ids());
+
+        // A pointer to anything but a struct waits for its pointee
+        let table = TypeTable {
+            table: vec![
+                definition(200, pointer_to(201)),
+                definition(
+                    201,
+                    OtherDataType::TypeArray(Array {
+                        size: 16,
+                        esize: 2,
+                        typ: DataType::BasicDataType(BasicDataType::BasicTypeWord),
+                    }),
+                ),
+            ],
+        };
+        let order = table.topological_order().unwrap();
+        assert_eq!(vec![201, 200], ids(&order));
+    }
+
+    #[test]
+    fn test_data_type_ids() {
+        let basic = DataType::BasicDataType(BasicDataType::MyBasicTypeFunc);
+        assert_eq!(106u32, u32::try_from(&basic).unwrap());
+        assert_eq!(106u16, u16::try_from(&basic).unwrap());
+
+        let other = DataType::Other(0x1234);
+        assert_eq!(0x1234u32, u32::try_from(&other).unwrap());
+        assert_eq!(0x1234u16, u16::try_from(&other).unwrap());
+
+        let wide = DataType::Other(0x12345);
+        assert_eq!(0x12345u32, u32::try_from(&wide).unwrap());
+        assert!(u16::try_from(&wide).is_err());
+
+        let undefined = DataType::Undefined(());
+        assert!(u32::try_from(&undefined).is_err());
+        assert!(u16::try_from(&undefined).is_err());
+    }
+
+    #[test]
+    fn test_serialize_type_table() {
+        let table = TypeTable {
+            table: vec![
+                definition(200, pointer_to(202)),
+                definition(
+                    201,
+                    OtherDataType::TypeEnum(Enum {
+                        name_id: 3,
+                        typ: DataType::BasicDataType(BasicDataType::BasicTypeUword),
+                        members: vec![
+                            EnumMember {
+                                name_id: 4,
+                                value: 0,
+                            },
+                            EnumMember {
+                                name_id: 5,
+                                value: 7,
+                            },
+                        ],
+                    }),
+                ),
+                definition(
+                    202,
+                    OtherDataType::TypePascalString(PascalString {
+                        size: 32,
+                        name_id: 6,
+                    }),
+                ),
+            ],
+        };
+
+        let mut out: Vec<u8> = vec![];
+        table.serialize_out(&mut out).unwrap();
+        assert_eq!(table.raw_length(), out.len());
+        // The pointer's tag and id lead its entry
+        assert_eq!([0, 0, 0, 0, 0, 200], out[0..6]);
+
+        let reread = TypeTable::try_from((out.as_slice(), 3)).unwrap();
+        assert_eq!(table, reread);
+
+        let undefined = TypeTable {
+            table: vec![definition(203, OtherDataType::Undefined)],
+        };
+        assert!(undefined.serialize_out(&mut vec![]).is_err());
+    }
+
+    #[test]
+    fn test_pascal_enum() {
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend((RawOtherDataType::LOCTYPE_PENUM as u16).to_be_bytes());
+        bytes.extend(204u32.to_be_bytes());
+        bytes.extend([7u32, 2, 8, 9].iter().flat_map(|x| x.to_be_bytes()));
+
+        let table = TypeTable::try_from((bytes.as_slice(), 1)).unwrap();
+        match table[0].definition() {
+            OtherDataType::TypePascalEnum(pe) => {
+                assert_eq!(7, pe.name_id);
+                assert_eq!(vec![8, 9], **pe);
+                assert_eq!(16, pe.raw_length());
+            }
+            x => panic!("Expected a Pascal enum, got: {:?}", x),
+        }
+
+        let mut out: Vec<u8> = vec![];
+        table.serialize_out(&mut out).unwrap();
+        assert_eq!(bytes, out);
+
+        // The second member is cut off
+        assert!(matches!(
+            PascalEnum::try_from(&bytes[6..bytes.len() - 2]),
+            Err(LibmetroError::Truncated {
+                needed: 8,
+                available: 6
+            })
+        ));
+    }
+
+    #[test]
+    fn test_size_in_model() {
+        let four = TargetModel::default();
+        let two = TargetModel {
+            int_size: 2,
+            ..TargetModel::default()
+        };
+
+        for (typ, four_size, two_size) in [
+            (BasicDataType::BasicTypeLong, Some(4), Some(2)),
+            (BasicDataType::BasicTypeUlong, Some(4), Some(2)),
+            (BasicDataType::BasicTypeWord, Some(2), Some(2)),
+            (BasicDataType::BasicTypeFloat8, Some(8), Some(8)),
+            (BasicDataType::MyBasicTypeVoidPtr, Some(4), Some(4)),
+            (BasicDataType::BasicTypeCstring, None, None),
+        ] {
+            assert_eq!(typ.size_in_bytes(), typ.size_in(&four), "{:?}", typ);
+            assert_eq!(four_size, typ.size_in(&four), "{:?}", typ);
+            assert_eq!(two_size, typ.size_in(&two), "{:?}", typ);
+        }
+    }
+
+    #[test]
+    fn test_size_in_bytes() {
+        for (typ, size) in [
+            (BasicDataType::BasicTypeVoid, None),
+            (BasicDataType::BasicTypePstring, None),
+            (BasicDataType::BasicTypeCstring, None),
+            (BasicDataType::BasicTypeAIstring, None),
+            (BasicDataType::BasicTypeBoolean, Some(1)),
+            (BasicDataType::BasicTypeUbyte, Some(1)),
+            (BasicDataType::BasicTypeByte, Some(1)),
+            (BasicDataType::BasicTypeChar, Some(1)),
+            (BasicDataType::BasicTypeWchar, Some(2)),
+            (BasicDataType::BasicTypeUword, Some(2)),
+            (BasicDataType::BasicTypeWord, Some(2)),
+            (BasicDataType::BasicTypeUlong, Some(4)),
+            (BasicDataType::BasicTypeLong, Some(4)),
+            (BasicDataType::BasicTypeFloat4, Some(4)),
+            (BasicDataType::BasicTypeFloat8, Some(8)),
+            (BasicDataType::BasicTypeComp, Some(8)),
+            (BasicDataType::BasicTypeFloat10, Some(10)),
+            (BasicDataType::BasicTypeFloat12, Some(12)),
+            (BasicDataType::MyBasicTypeCharPtr, Some(4)),
+            (BasicDataType::MyBasicTypeFunc, Some(4)),
+        ] {
+            assert_eq!(size, typ.size_in_bytes(), "{:?}", typ);
+        }
+
+        let table = TypeTable {
+            table: vec![
+                definition(200, pointer_to(201)),
+                definition(201, struct_of(&[200, 200, 200])),
+                definition(
+                    202,
+                    OtherDataType::TypeEnum(Enum {
+                        name_id: 0,
+                        typ: DataType::BasicDataType(BasicDataType::BasicTypeWord),
+                        members: vec![],
+                    }),
+                ),
+            ],
+        };
+
+        assert_eq!(Some(4), DataType::Other(200).size_in_bytes(&table));
+        assert_eq!(Some(12), DataType::Other(201).size_in_bytes(&table));
+        assert_eq!(Some(2), DataType::Other(202).size_in_bytes(&table));
+        assert_eq!(None, DataType::Other(203).size_in_bytes(&table));
+        assert_eq!(None, DataType::Undefined(()).size_in_bytes(&table));
+        assert_eq!(
+            Some(4),
+            DataType::from(BasicDataType::BasicTypeLong as u32).size_in_bytes(&table)
+        );
+    }
+
+    #[test]
+    fn test_resolve() {
+        let array = OtherDataType::TypeArray(Array {
+            size: 16,
+            esize: 2,
+            typ: DataType::BasicDataType(BasicDataType::BasicTypeWord),
+        });
+        let table = TypeTable {
+            table: vec![
+                definition(200, struct_of(&[201, 202])),
+                definition(201, array.clone()),
+            ],
+        };
+
+        let OtherDataType::TypeStruct(s) = table[0].definition() else {
+            panic!("Expected a struct, got: {:?}", table[0].definition());
+        };
+
+        let resolved = s[0].resolve_type(&table).unwrap();
+        assert_eq!(201, resolved.type_id());
+        assert_eq!(&array, resolved.definition());
+
+        // 202 was never defined
+        assert_eq!(None, s[1].resolve_type(&table));
+        assert_eq!(
+            None,
+            table.resolve(&DataType::BasicDataType(BasicDataType::BasicTypeLong))
+        );
+    }
+
+    #[test]
+    fn test_entries() {
+        let OtherDataType::TypeStruct(s) = struct_of(&[1, 2, 3]) else {
+            unreachable!()
+        };
+        assert_eq!(
+            s.iter().collect::<Vec<_>>(),
+            s.entries().iter().collect::<Vec<_>>()
+        );
+        assert_eq!((3, false), (s.len(), s.is_empty()));
+        assert_eq!(Some(&s[2]), s.get(2));
+        assert!(s.get(3).is_none());
+
+        let e = Enum {
+            name_id: 0,
+            typ: DataType::from(BasicDataType::BasicTypeWord as u32),
+            members: vec![EnumMember {
+                name_id: 1,
+                value: 7,
+            }],
+        };
+        assert_eq!(
+            e.iter().collect::<Vec<_>>(),
+            e.entries().iter().collect::<Vec<_>>()
+        );
+        assert_eq!(e.iter().next(), e.get(0));
+    }
+
+    #[test]
+    fn test_type_table_padding() {
+        let table = TypeTable {
+            table: vec![definition(200, pointer_to(201))],
+        };
+        let mut bytes: Vec<u8> = vec![];
+        table.serialize_out(&mut bytes).unwrap();
+
+        // Alignment padding after the last entry is left alone
+        bytes.push(0);
+        assert_eq!(table, TypeTable::try_from((bytes.as_slice(), 1)).unwrap());
+
+        // A count one too high runs into the padding
+        assert!(matches!(
+            TypeTable::try_from((bytes.as_slice(), 2))
+                .unwrap_err()
+                .root_cause(),
+            LibmetroError::Truncated {
+                needed: 6,
+                available: 1
+            }
+        ));
+        assert!(TypeTable::try_from((&bytes[..9], 1)).is_err());
+    }
+
+    #[test]
+    fn test_pascal_range() {
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend(0u32.to_be_bytes());
+        bytes.extend((BasicDataType::BasicTypeLong as u32).to_be_bytes());
+        bytes.extend([4u32, 1, 10].iter().flat_map(|x| x.to_be_bytes()));
+
+        let range = PascalRange::from(bytes.as_slice());
+        assert_eq!(9, range.len());
+        assert!(!range.is_empty());
+        assert!(range.contains(1) && range.contains(9));
+        assert!(!range.contains(0) && !range.contains(10));
+        let half_open: Range<u32> = range.clone().into();
+        assert_eq!(1..10, half_open);
+        assert_eq!(1..=10, RangeInclusive::from(range));
+
+        bytes[16..20].copy_from_slice(&1u32.to_be_bytes());
+        let empty = PascalRange::from(bytes.as_slice());
+        assert_eq!(0, empty.len());
+        assert!(empty.is_empty());
+        assert!(!empty.contains(1));
+        assert_eq!(1..=1, RangeInclusive::from(empty));
+    }
+
+    #[test]
+    fn test_truncated_struct() {
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend((RawOtherDataType::LOCTYPE_STRUCT as u16).to_be_bytes());
+        bytes.extend(200u32.to_be_bytes());
+        bytes.extend([1u32, 8].iter().flat_map(|x| x.to_be_bytes()));
+        bytes.extend(3u16.to_be_bytes());
+        bytes.extend([2u32, 3, 0, 4, 3, 4].iter().flat_map(|x| x.to_be_bytes()));
+
+        // Three members declared, two present
+        assert!(matches!(
+            TypeTable::try_from((bytes.as_slice(), 1))
+                .unwrap_err()
+                .root_cause(),
+            LibmetroError::Truncated {
+                needed: 36,
+                available: 24
+            }
+        ));
+        assert!(Struct::try_from(&bytes[6..12]).is_err());
+
+        bytes[14..16].copy_from_slice(&2u16.to_be_bytes());
+        let table = TypeTable::try_from((bytes.as_slice(), 1)).unwrap();
+        match table[0].definition() {
+            OtherDataType::TypeStruct(s) => assert_eq!(2, s.len()),
+            x => panic!("Expected a struct, got: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_truncated_enum() {
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend(1u32.to_be_bytes());
+        bytes.extend((BasicDataType::BasicTypeWord as u16).to_be_bytes());
+        bytes.extend(1000u16.to_be_bytes());
+        bytes.extend([2u32, 7].iter().flat_map(|x| x.to_be_bytes()));
+
+        assert!(matches!(
+            Enum::try_from(bytes.as_slice()),
+            Err(LibmetroError::Truncated {
+                needed: 8000,
+                available: 8
+            })
+        ));
+        assert!(Enum::try_from(&bytes[..6]).is_err());
+
+        bytes[6..8].copy_from_slice(&1u16.to_be_bytes());
+        let e = Enum::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(1, e.len());
+
+        // The base type check still applies
+        bytes[4..6].copy_from_slice(&0x1234u16.to_be_bytes());
+        assert!(matches!(
+            Enum::try_from(bytes.as_slice()),
+            Err(LibmetroError::Malformed(_))
+        ));
+    }
+}
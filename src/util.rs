@@ -1,29 +1,121 @@
+use crate::error::LibmetroError;
 use crate::objects_m68k::MetrowerksObject;
-use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
-use std::{collections::VecDeque, sync::Once};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use std::io::Write;
+use std::{collections::VecDeque, sync::OnceLock};
 
 pub trait NameIdFromObject<'a>: Sized {
-    fn name(&'a self, obj: &'a MetrowerksObject) -> &str;
+    fn name_id(&self) -> u32;
+
+    /// Resolves the name id against the object's name table, or `""` if it isn't present.
+    fn name(&'a self, obj: &'a MetrowerksObject) -> &'a str;
+}
+
+/// Writes a structure back out in the big-endian layout it is parsed from.
+pub trait Serializable {
+    fn serialize_out<W: Write>(&self, w: &mut W) -> Result<(), LibmetroError>;
 }
 
 pub(crate) trait RawLength: Sized {
     fn raw_length(&self) -> usize;
 }
 
+/// The order files are written into a library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ObjectOrder {
+    /// The order the library currently holds them in.
+    #[default]
+    AsGiven,
+    /// Sorted by file name.
+    ByName,
+    /// The order they were read from the original file; only possible when
+    /// every file came from parsing a library.
+    Original,
+}
+
+/// Knobs for how objects and libraries are laid out when written.
+#[derive(Debug, Clone)]
+pub struct SerializeOptions {
+    /// Code and initialized data payloads are zero padded to a multiple of this
+    /// many bytes, with the padding counted in the hunk's size field. The
+    /// default of 1 writes hunks exactly as they are.
+    pub hunk_alignment: usize,
+    pub object_order: ObjectOrder,
+}
+
+impl Default for SerializeOptions {
+    fn default() -> Self {
+        Self {
+            hunk_alignment: 1,
+            object_order: ObjectOrder::default(),
+        }
+    }
+}
+
+/// Knobs for how strictly objects and libraries are checked when parsed.
+///
+/// The default is strict, apart from keeping unknown flag bits, which real
+/// libraries have been seen to set, and reserved hunks, which are left for the
+/// caller to decide on.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Fail with `NameHashMismatch` when a name's stored hash is wrong.
+    pub check_name_hashes: bool,
+    /// Fail on reserved hunks, which have no known layout.
+    pub reject_reserved_hunks: bool,
+    /// Fail with `SizeMismatch` when an object header's code and data sizes
+    /// don't match its hunks.
+    pub check_sizes: bool,
+    /// Keep object and library flag bits with no known meaning rather than
+    /// failing on them.
+    pub allow_unknown_flags: bool,
+    /// Fail when a hunk stream runs out without an End hunk, or goes on past
+    /// one. When off, anything after the End hunk is ignored.
+    pub require_end_hunk: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            check_name_hashes: true,
+            reject_reserved_hunks: false,
+            check_sizes: true,
+            allow_unknown_flags: true,
+            require_end_hunk: true,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Every check off, for salvaged or otherwise damaged files.
+    pub fn lenient() -> Self {
+        Self {
+            check_name_hashes: false,
+            reject_reserved_hunks: false,
+            check_sizes: false,
+            allow_unknown_flags: true,
+            require_end_hunk: false,
+        }
+    }
+}
+
 const NAMEHASH: u16 = 1024;
 
+/// The name table hash of `name`, taken over its MacRoman bytes as stored.
+/// Names MacRoman can't encode are hashed as UTF-8.
 pub fn nametable_hash(name: &str) -> u16 {
     let mut hashval: u16;
     let mut u: u8;
-    let s: VecDeque<u8> = name.as_bytes().to_owned().into();
+    let s: VecDeque<u8> = encode_mac_roman(name)
+        .unwrap_or_else(|_| name.as_bytes().to_owned())
+        .into();
 
-    hashval = (name.len() as u32 & 0x00ff) as u16;
+    hashval = (s.len() as u32 & 0x00ff) as u16;
 
     if hashval != 0 {
         u = 0;
         for c in s.iter() {
-            u = (u >> 3) | (u << 5);
-            u += *c;
+            u = u.rotate_right(3).wrapping_add(*c);
         }
         hashval = (hashval << 8) | (u as u16);
     }
@@ -32,43 +124,141 @@ pub fn nametable_hash(name: &str) -> u16 {
 }
 
 pub fn convert_be_u16(data: &[u8; 2]) -> u16 {
-    let res: u16 = unsafe { std::mem::transmute(*data) };
-    u16::from_be(res)
+    u16::from_be_bytes(*data)
 }
 
 pub fn convert_be_u32(data: &[u8; 4]) -> u32 {
-    let res: u32 = unsafe { std::mem::transmute(*data) };
-    u32::from_be(res)
+    u32::from_be_bytes(*data)
 }
 
 pub fn convert_be_i16(data: &[u8; 2]) -> i16 {
-    let res: i16 = unsafe { std::mem::transmute(*data) };
-    i16::from_be(res)
+    i16::from_be_bytes(*data)
 }
 
 pub fn convert_be_i32(data: &[u8; 4]) -> i32 {
-    let res: i32 = unsafe { std::mem::transmute(*data) };
-    i32::from_be(res)
+    i32::from_be_bytes(*data)
 }
 
-/* Timestamp conversion */
-static mut MAC_EPOCH_OFFSET: i64 = 0;
-static INIT_MAC_EPOCH_OFFSET: Once = Once::new();
+pub fn convert_be_u64(data: &[u8; 8]) -> u64 {
+    u64::from_be_bytes(*data)
+}
 
-fn get_offset() -> i64 {
-    unsafe {
-        INIT_MAC_EPOCH_OFFSET.call_once(|| {
-            MAC_EPOCH_OFFSET = NaiveDate::from_ymd_opt(1904, 1, 1)
-                .unwrap()
-                .and_hms_opt(0, 0, 0)
-                .unwrap()
-                .and_local_timezone(Local)
-                .unwrap()
-                .timestamp()
-                .abs()
+pub fn convert_be_i64(data: &[u8; 8]) -> i64 {
+    i64::from_be_bytes(*data)
+}
+
+/// Checks at least `needed` bytes remain in `data` before reading them.
+pub(crate) fn check_len(data: &[u8], needed: usize) -> Result<(), LibmetroError> {
+    if data.len() < needed {
+        return Err(LibmetroError::Truncated {
+            needed,
+            available: data.len(),
         });
-        MAC_EPOCH_OFFSET
     }
+
+    Ok(())
+}
+
+/// The characters MacRoman puts at 0x80..=0xff, below that it's ASCII.
+const MAC_ROMAN_HIGH: [char; 128] = [
+    'Ä', 'Å', 'Ç', 'É', 'Ñ', 'Ö', 'Ü', 'á', 'à', 'â', 'ä', 'ã', 'å', 'ç', 'é', 'è', 'ê', 'ë', 'í',
+    'ì', 'î', 'ï', 'ñ', 'ó', 'ò', 'ô', 'ö', 'õ', 'ú', 'ù', 'û', 'ü', '†', '°', '¢', '£', '§', '•',
+    '¶', 'ß', '®', '©', '™', '´', '¨', '≠', 'Æ', 'Ø', '∞', '±', '≤', '≥', '¥', 'µ', '∂', '∑', '∏',
+    'π', '∫', 'ª', 'º', 'Ω', 'æ', 'ø', '¿', '¡', '¬', '√', 'ƒ', '≈', '∆', '«', '»', '…',
+    '\u{00a0}', 'À', 'Ã', 'Õ', 'Œ', 'œ', '–', '—', '“', '”', '‘', '’', '÷', '◊', 'ÿ', 'Ÿ', '⁄',
+    '€', '‹', '›', 'ﬁ', 'ﬂ', '‡', '·', '‚', '„', '‰', 'Â', 'Ê', 'Á', 'Ë', 'È', 'Í', 'Î', 'Ï', 'Ì',
+    'Ó', 'Ô', '\u{f8ff}', 'Ò', 'Ú', 'Û', 'Ù', 'ı', 'ˆ', '˜', '¯', '˘', '˙', '˚', '¸', '˝', '˛',
+    'ˇ',
+];
+
+/// Decodes MacRoman text, the encoding classic MacOS names are stored in.
+pub fn decode_mac_roman(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| match b {
+            0x00..=0x7f => b as char,
+            _ => MAC_ROMAN_HIGH[b as usize - 0x80],
+        })
+        .collect()
+}
+
+/// Encodes `s` as MacRoman, one byte per character.
+///
+/// Fails on the first character MacRoman doesn't have.
+pub fn encode_mac_roman(s: &str) -> Result<Vec<u8>, LibmetroError> {
+    s.chars()
+        .map(|c| match c {
+            '\0'..='\x7f' => Ok(c as u8),
+            _ => MAC_ROMAN_HIGH
+                .iter()
+                .position(|x| *x == c)
+                .map(|idx| idx as u8 + 0x80)
+                .ok_or_else(|| {
+                    LibmetroError::Invalid(format!("{:?} has no MacRoman encoding, in: {:?}", c, s))
+                }),
+        })
+        .collect()
+}
+
+/// Reads the NUL terminated MacRoman string at the start of `bytes`.
+///
+/// Callers bound `bytes` to the longest string they accept, a string running
+/// past that fails with `NoNulTerminator`. The string takes up one byte per
+/// character plus the NUL.
+pub fn read_c_string(bytes: &[u8]) -> Result<String, LibmetroError> {
+    let end = bytes
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(LibmetroError::NoNulTerminator)?;
+
+    Ok(decode_mac_roman(&bytes[..end]))
+}
+
+fn fourcc(value: u32) -> String {
+    value
+        .to_be_bytes()
+        .iter()
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        })
+        .collect()
+}
+
+/// Describes a magic word mismatch with both values in hex and as four
+/// characters, calling out a byte-swapped file when `got` is `expected` reversed.
+pub fn magic_mismatch(expected: u32, got: u32) -> String {
+    let mut msg = format!(
+        "Bad magic word, Expected: {:#010x} ('{}'), got: {:#010x} ('{}')",
+        expected,
+        fourcc(expected),
+        got,
+        fourcc(got)
+    );
+
+    if got == expected.swap_bytes() {
+        msg.push_str("; the file appears to be little-endian/byte-swapped");
+    }
+
+    msg
+}
+
+/* Timestamp conversion */
+static MAC_EPOCH_OFFSET: OnceLock<i64> = OnceLock::new();
+
+fn get_offset() -> i64 {
+    *MAC_EPOCH_OFFSET.get_or_init(|| {
+        NaiveDate::from_ymd_opt(1904, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc()
+            .timestamp()
+            .abs()
+    })
 }
 
 pub fn from_mac_datetime(date: u32) -> DateTime<Utc> {
@@ -76,7 +266,190 @@ pub fn from_mac_datetime(date: u32) -> DateTime<Utc> {
     Utc.timestamp_opt((date as i64) - get_offset(), 0).unwrap()
 }
 
+/// Converts a date into a classic MacOS timestamp.
+///
+/// Dates outside of the representable range (1904-01-01 through 2040-02-06) are
+/// saturated to the nearest end of it; use `try_to_mac_datetime` to catch them.
 pub fn to_mac_datetime<T: TimeZone>(date: DateTime<T>) -> u32 {
     // Classic MacOS timestamps start from midnight on January 1, 1904.
-    (date.to_utc().timestamp() + get_offset()) as u32
+    (date.to_utc().timestamp() + get_offset()).clamp(0, u32::MAX as i64) as u32
+}
+
+/// Converts a date into a classic MacOS timestamp, failing if it does not fit in a u32.
+pub fn try_to_mac_datetime<T: TimeZone>(date: DateTime<T>) -> Result<u32, LibmetroError> {
+    let date = date.to_utc();
+
+    u32::try_from(date.timestamp() + get_offset()).map_err(|_| LibmetroError::DateOutOfRange(date))
+}
+
+/// A classic MacOS timestamp, kept as the raw seconds since 1904 so it is
+/// written back exactly as it was read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MacDateTime(u32);
+
+impl MacDateTime {
+    /// Converts a date, saturating it to the representable range like `to_mac_datetime`.
+    pub fn from_datetime<T: TimeZone>(date: DateTime<T>) -> Self {
+        MacDateTime(to_mac_datetime(date))
+    }
+
+    pub fn to_datetime(&self) -> DateTime<Utc> {
+        from_mac_datetime(self.0)
+    }
+
+    pub fn as_raw(&self) -> u32 {
+        self.0
+    }
+}
+
+impl From<u32> for MacDateTime {
+    fn from(value: u32) -> Self {
+        MacDateTime(value)
+    }
+}
+
+impl From<DateTime<Utc>> for MacDateTime {
+    fn from(value: DateTime<Utc>) -> Self {
+        MacDateTime::from_datetime(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_magic_mismatch() {
+        let msg = magic_mismatch(0x4d574f42, 0x424f574d);
+        assert!(msg.contains("0x4d574f42 ('MWOB')"));
+        assert!(msg.contains("0x424f574d ('BOWM')"));
+        assert!(msg.contains("byte-swapped"));
+
+        let msg = magic_mismatch(0xfeedbead, 0x00000001);
+        assert!(msg.contains("0xfeedbead ('....')"));
+        assert!(!msg.contains("byte-swapped"));
+    }
+
+    #[test]
+    fn test_nametable_hash() {
+        // Pairs read from the name tables of the sample libraries
+        for (name, hash) in [
+            ("add", 0x376),
+            ("a", 0x161),
+            ("is_lower", 0x61),
+            ("set_volume", 0x281),
+            ("qd", 0x292),
+        ] {
+            assert_eq!(hash, nametable_hash(name), "{}", name);
+        }
+
+        assert_eq!(0, nametable_hash(""));
+        // Hashed as the MacRoman bytes, whose high bytes overflow the byte
+        // sum, which wraps
+        assert_eq!(0x1d1, nametable_hash("Größe"));
+        assert_eq!(
+            nametable_hash("Größe"),
+            nametable_hash(&decode_mac_roman(b"Gr\x9a\xa7e"))
+        );
+    }
+
+    #[test]
+    fn test_read_c_string() {
+        assert_eq!("Café", read_c_string(b"Caf\x8e\0trailing").unwrap());
+        assert_eq!("π∞", read_c_string(b"\xb9\xb0\0").unwrap());
+        assert_eq!("", read_c_string(b"\0").unwrap());
+        assert!(matches!(
+            read_c_string(b"Caf\x8e"),
+            Err(LibmetroError::NoNulTerminator)
+        ));
+
+        assert_eq!(b"Caf\x8e".to_vec(), encode_mac_roman("Café").unwrap());
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(all, encode_mac_roman(&decode_mac_roman(&all)).unwrap());
+        assert!(encode_mac_roman("日本").is_err());
+    }
+
+    #[test]
+    fn test_convert_be_byte_order() {
+        assert_eq!(0x1234, convert_be_u16(&[0x12, 0x34]));
+        assert_eq!(0x12345678, convert_be_u32(&[0x12, 0x34, 0x56, 0x78]));
+        assert_eq!(-2, convert_be_i16(&[0xff, 0xfe]));
+        assert_eq!(-1, convert_be_i32(&[0xff; 4]));
+        assert_eq!(i32::MIN, convert_be_i32(&[0x80, 0, 0, 0]));
+
+        for value in [0, 1, 0x0123456789abcdef, u64::MAX] {
+            assert_eq!(value, convert_be_u64(&value.to_be_bytes()));
+        }
+        for value in [0, -1, i64::MIN, i64::MAX] {
+            assert_eq!(value, convert_be_i64(&value.to_be_bytes()));
+        }
+        assert_eq!(
+            0x0102030405060708,
+            convert_be_u64(&[1, 2, 3, 4, 5, 6, 7, 8])
+        );
+        assert_eq!(
+            -2,
+            convert_be_i64(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe])
+        );
+    }
+
+    #[test]
+    fn test_mac_epoch_is_utc() {
+        // Fixed in UTC, whatever TZ the host has
+        assert_eq!(
+            Utc.with_ymd_and_hms(1904, 1, 1, 0, 0, 0).unwrap(),
+            from_mac_datetime(0)
+        );
+        assert_eq!(
+            Utc.with_ymd_and_hms(2001, 1, 1, 0, 0, 0).unwrap(),
+            from_mac_datetime(3061152000)
+        );
+        assert_eq!(2082844800, to_mac_datetime(DateTime::UNIX_EPOCH));
+    }
+
+    #[test]
+    fn test_mac_datetime_newtype() {
+        for raw in [0, 1, 3061152000, u32::MAX] {
+            let mac = MacDateTime::from(raw);
+            assert_eq!(raw, mac.as_raw());
+            assert_eq!(mac, MacDateTime::from(mac.to_datetime()));
+            assert_eq!(
+                mac,
+                MacDateTime::from_datetime(mac.to_datetime().with_timezone(&chrono::Local))
+            );
+        }
+
+        // Sub-second precision is dropped
+        let date = Utc.with_ymd_and_hms(2001, 1, 1, 0, 0, 0).unwrap();
+        let mac = MacDateTime::from(date + chrono::TimeDelta::milliseconds(500));
+        assert_eq!(3061152000, mac.as_raw());
+        assert_eq!(date, mac.to_datetime());
+    }
+
+    #[test]
+    fn test_mac_datetime_range() {
+        let modern = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let mac = try_to_mac_datetime(modern).unwrap();
+        assert_eq!(modern, from_mac_datetime(mac));
+        assert_eq!(mac, to_mac_datetime(modern));
+
+        let too_early = Utc.with_ymd_and_hms(1903, 12, 31, 23, 59, 59).unwrap();
+        assert!(try_to_mac_datetime(too_early).is_err());
+        assert_eq!(0, to_mac_datetime(too_early));
+
+        let too_late = Utc.with_ymd_and_hms(2041, 1, 1, 0, 0, 0).unwrap();
+        assert!(try_to_mac_datetime(too_late).is_err());
+        assert_eq!(u32::MAX, to_mac_datetime(too_late));
+
+        // Both ends of the range are representable, a second past either isn't
+        let first = Utc.with_ymd_and_hms(1904, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(0, try_to_mac_datetime(first).unwrap());
+        let last = Utc.with_ymd_and_hms(2040, 2, 6, 6, 28, 15).unwrap();
+        assert_eq!(u32::MAX, try_to_mac_datetime(last).unwrap());
+        assert!(matches!(
+            try_to_mac_datetime(last + chrono::TimeDelta::seconds(1)),
+            Err(LibmetroError::DateOutOfRange(_))
+        ));
+    }
 }